@@ -0,0 +1,76 @@
+//! Emoji lookups of a 64x64 frame of distinct colors, with a palette
+//! of 3000 entries, compared to scanning the whole palette for each
+//! color.
+//!
+//! Run with `cargo bench --bench emoji_lookup`.
+
+use backgif::conv::fmtr::{ColorMetric, EmojiFrameFormatter};
+use palette::color_difference::Ciede2000;
+use palette::convert::FromColorUnclamped;
+use palette::{Lab, Srgb};
+use std::time::Instant;
+
+const PALETTE_SIZE: usize = 3000;
+const FRAME_DOTS: usize = 64 * 64;
+
+fn to_lab(rgb: [u8; 3]) -> Lab {
+    Lab::from_color_unclamped(Srgb::new(
+        rgb[0] as f32 / 255.0,
+        rgb[1] as f32 / 255.0,
+        rgb[2] as f32 / 255.0,
+    ))
+}
+
+fn main() {
+    // Colors from a fixed seed, so that runs are comparable.
+    let mut state: u64 = 0x2545f4914f6cdd1d;
+    let mut next_rgb = || {
+        [0, 1, 2].map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 56) as u8
+        })
+    };
+    let palette = (0..PALETTE_SIZE).map(|_| next_rgb()).collect::<Vec<_>>();
+    let frame = (0..FRAME_DOTS).map(|_| next_rgb()).collect::<Vec<_>>();
+
+    let palette_path =
+        std::env::temp_dir().join(format!("backgif-bench-{}.json", std::process::id()));
+    let json = palette
+        .iter()
+        .enumerate()
+        .map(|(i, rgb)| {
+            let emoji = char::from_u32(0x1f300 + i as u32).unwrap();
+            serde_json::json!([rgb[2], rgb[1], rgb[0], emoji.to_string()])
+        })
+        .collect::<Vec<_>>();
+    std::fs::write(&palette_path, serde_json::to_string(&json).unwrap()).unwrap();
+
+    for metric in [
+        ColorMetric::Ciede2000,
+        ColorMetric::Cie76,
+        ColorMetric::WeightedRgb,
+    ] {
+        let formatter = EmojiFrameFormatter::new(Some(&palette_path), false, metric).unwrap();
+        let start = Instant::now();
+        for rgb in &frame {
+            formatter.lookup(vec![rgb[0], rgb[1], rgb[2], 255]);
+        }
+        println!("{:?}: {:?}", metric, start.elapsed());
+    }
+    std::fs::remove_file(&palette_path).unwrap();
+
+    let palette_labs = palette.iter().copied().map(to_lab).collect::<Vec<_>>();
+    let start = Instant::now();
+    for rgb in &frame {
+        let lab = to_lab(*rgb);
+        std::hint::black_box(
+            palette_labs
+                .iter()
+                .map(|palette_lab| palette_lab.difference(lab))
+                .fold(f32::MAX, f32::min),
+        );
+    }
+    println!("Ciede2000 scan: {:?}", start.elapsed());
+}
@@ -0,0 +1,78 @@
+/*
+ * Callbacks and helpers for custom input (`backgif -f c`).
+ *
+ * The generated start function calls `init()` once, then, for each
+ * frame, `update_frame()`, followed by `draw_line()` for each frame
+ * line. Frame lines are symbol names already filled with placeholder
+ * truecolor dots, which `draw_line()` updates in place.
+ *
+ * Emitted with `backgif --emit-header`, and available to custom input
+ * as `#include "backgif.h"`.
+ */
+
+#ifndef BACKGIF_H
+#define BACKGIF_H
+
+#include <stdint.h>
+
+/*
+ * Called at the beginning of the start function, supplying a `seed`
+ * (see `--seed`) for the initial state of PRNGs, along with the
+ * configured frame width `w` and height `h`.
+ */
+void init(uint64_t seed, uint16_t w, uint16_t h);
+
+/* Called at the beginning of each frame. */
+void update_frame(void);
+
+/*
+ * Renders frame line `n` containing up to `width` dots, updating the
+ * corresponding symbol at `addr`. First dot is after frame line
+ * prefix `offs` (one of the `BACKGIF_PREFIX_*` lengths).
+ */
+void draw_line(uint8_t *addr, uint8_t offs, uint16_t n);
+
+/* Prefix of the last frame line: `\x1b[1;1H\x1b[2K`. */
+#define BACKGIF_PREFIX_LAST 10
+
+/* Prefix of other frame lines: `\x1b[2K\x1b[99D`. */
+#define BACKGIF_PREFIX_OTHER 9
+
+/*
+ * Each dot is `\x1b[48:2::RRR:GGG:BBBm  \x1b[49m`, with 3 decimal
+ * digits per component (leading zeros are valid in color codes).
+ */
+#define BACKGIF_DOT_LEN 27
+#define BACKGIF_DOT_R 8
+#define BACKGIF_DOT_G 12
+#define BACKGIF_DOT_B 16
+
+/* Write `v` as 3 decimal digits at `p`. */
+static inline void backgif_write_component(uint8_t *p, uint8_t v) {
+    p[0] = '0' + v / 100;
+    p[1] = '0' + v / 10 % 10;
+    p[2] = '0' + v % 10;
+}
+
+/* Write color `r:g:b` to dot `i` of the line starting at `line`. */
+static inline void backgif_write_dot(uint8_t *line, uint16_t i,
+                                     uint8_t r, uint8_t g, uint8_t b) {
+    uint8_t *dot = line + BACKGIF_DOT_LEN * i;
+    backgif_write_component(dot + BACKGIF_DOT_R, r);
+    backgif_write_component(dot + BACKGIF_DOT_G, g);
+    backgif_write_component(dot + BACKGIF_DOT_B, b);
+}
+
+/*
+ * Write color `r:g:b` to dot `i` of frame line `addr` drawn after
+ * prefix `offs`, as passed to `draw_line()`.
+ */
+#define BACKGIF_DOT(addr, offs, i, r, g, b) \
+    backgif_write_dot((addr) + (offs), (i), (r), (g), (b))
+
+/* Same as `BACKGIF_DOT()`, with a `0xRRGGBB` color. */
+#define BACKGIF_DOT_RGB(addr, offs, i, rgb) \
+    BACKGIF_DOT((addr), (offs), (i), ((rgb) >> 16) & 0xff, \
+                ((rgb) >> 8) & 0xff, (rgb) & 0xff)
+
+#endif /* BACKGIF_H */
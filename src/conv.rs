@@ -1,33 +1,37 @@
 //! Frame conversion types.
 
 pub mod fmtr;
-pub mod log;
+pub mod font;
+pub mod kdtree;
+pub mod quant;
 
-use crate::conv::log::debug;
 use colored::Colorize;
 use fmtr::FrameFormatter;
 use iced_x86::{
     Decoder, DecoderOptions, Instruction, InstructionInfoFactory, Mnemonic, OpAccess, OpKind,
+    Register,
 };
 use itertools::Itertools;
 use lief::elf::Section;
-use lief::generic::Symbol;
+use lief::generic::{Section as _, Symbol};
+use log::{debug, trace};
 use memchr::memmem;
-use std::collections::HashMap;
+use palette::{LinSrgb, Srgb};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::Write;
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use unicode_width::UnicodeWidthStr;
 
 pub trait FrameParser {
-    fn from_input(
-        &self,
-        filename: &PathBuf,
-        clear_line: bool,
-        delay: Option<u16>,
-    ) -> Vec<FrameInfo>;
+    fn from_input(&self, filename: &PathBuf, clear_line: bool, delay: Option<u16>) -> Animation;
 
     fn to_frameline_names(
         &self,
@@ -43,7 +47,15 @@ pub trait FrameParser {
             formatter.to_frameline(name)
         };
 
-        let tmp_name = format!(
+        let tmp_name = self.to_tmp_name(&frameline_name, i);
+
+        (frameline_name, tmp_name)
+    }
+
+    /// Temporary name with the same length as the frame line it
+    /// will be patched with, suffixed by the function index `i`.
+    fn to_tmp_name(&self, frameline_name: &str, i: usize) -> String {
+        format!(
             "{}{:08x}",
             "A".repeat(if frameline_name.len() < 9 {
                 1
@@ -51,9 +63,16 @@ pub trait FrameParser {
                 frameline_name.len() - 8
             }),
             i
-        );
+        )
+    }
 
-        (frameline_name, tmp_name)
+    /// Whether frame lines are called in reverse, so that the bottom
+    /// line is printed first in the backtrace.
+    fn bottom_up(&self) -> bool;
+
+    /// Whether all lines of a frame are drawn by a single frame line.
+    fn single_line(&self) -> bool {
+        false
     }
 
     fn prepare_frame(
@@ -70,15 +89,40 @@ pub trait FrameParser {
             first_name: String::new(),
             last_name: String::new(),
             delay,
+            colors: None,
         };
-        for (i, name) in fn_names.iter().rev().enumerate() {
-            let (frameline_name, tmp_name) = self.to_frameline_names(
-                formatter,
-                name,
-                *fn_idx,
-                i == fn_names.len() - 1,
-                clear_line,
-            );
+        // Innermost function is printed first, so the outermost
+        // function (called by the entrypoint) is the last line printed.
+        let rows = if self.bottom_up() {
+            fn_names.iter().enumerate().collect_vec()
+        } else {
+            fn_names.iter().enumerate().rev().collect_vec()
+        };
+        let framelines = if self.single_line() {
+            vec![formatter.to_frameline_rows(&fn_names, clear_line)]
+        } else {
+            rows.into_iter()
+                .enumerate()
+                .map(|(i, (row, name))| {
+                    let at_origin = i == fn_names.len() - 1;
+                    if self.bottom_up() || formatter.positions_rows() {
+                        formatter.to_frameline_at_row(
+                            name,
+                            row,
+                            fn_names.len(),
+                            at_origin,
+                            clear_line,
+                        )
+                    } else if at_origin {
+                        formatter.to_frameline_at_origin(name, clear_line)
+                    } else {
+                        formatter.to_frameline(name)
+                    }
+                })
+                .collect_vec()
+        };
+        for (i, frameline_name) in framelines.into_iter().enumerate() {
+            let tmp_name = self.to_tmp_name(&frameline_name, *fn_idx);
             *fn_idx += 1;
 
             if i == 0 {
@@ -96,324 +140,2559 @@ pub trait FrameParser {
     }
 }
 
+/// Check that a frame line is terminal-safe text, i.e. without control
+/// characters other than the escape character starting each terminal
+/// sequence, and the bell character ending operating system commands
+/// (e.g. hyperlinks). In particular, a null byte would terminate the
+/// patched symbol name early.
+fn validate_frameline(frameline: &str) -> Result<(), String> {
+    let mut osc = false;
+    let mut prev = None;
+    for (i, c) in frameline.char_indices() {
+        match c {
+            ']' if prev == Some('\x1b') => osc = true,
+            '\\' if prev == Some('\x1b') => osc = false,
+            '\x07' if osc => osc = false,
+            '\x1b' => {}
+            c if c.is_control() => {
+                return Err(format!(
+                    "unexpected control character {:?} at byte {}",
+                    c, i
+                ));
+            }
+            _ => {}
+        }
+        prev = Some(c);
+    }
+
+    Ok(())
+}
+
 pub struct GifFrameParser<'a> {
     pub formatter: &'a dyn FrameFormatter,
+    pub bottom_up: bool,
+
+    /// Draw each frame's index and start time over its bottom right corner.
+    pub overlay_index: bool,
+
+    /// Fraction of the previous composited frame blended into each frame.
+    pub motion_blur: f32,
+
+    /// Text drawn in a title card shown before the first frame.
+    pub caption: Option<String>,
+
+    /// Title card delay in units of 10 ms.
+    pub caption_delay: u16,
+
+    /// Matrix applied to the RGB components of each color, before
+    /// converting them to frame dots.
+    pub color_matrix: Option<[[f32; 3]; 3]>,
+
+    /// Adjustments applied to each color after the color matrix.
+    pub adjustments: ColorAdjustments,
+
+    /// Draw all lines of each frame with a single frame line.
+    pub single_line: bool,
+
+    /// Match the color of each frame dot, instead of once per palette
+    /// entry, so that color match statistics are weighted by frame dots.
+    pub match_per_dot: bool,
+
+    /// Diffuse the error of colors approximated by the formatter's
+    /// palette into neighboring frame dots.
+    pub dither: bool,
+
+    /// Reduce the colors of all frames to a palette of at most this
+    /// many colors, so that similar frame dots draw the same frame lines.
+    pub max_colors: Option<usize>,
+
+    /// Keep the colors drawn by each frame dot in its frame info,
+    /// e.g. to reconstruct frames as images.
+    pub keep_colors: bool,
+
+    /// Number of threads formatting frames, or 0 for one per core.
+    pub jobs: usize,
+
+    /// Dimensions each composited frame is resized to. If only one
+    /// is given, the other one preserves the aspect ratio.
+    pub height: Option<u16>,
+    pub width: Option<u16>,
+
+    /// Frame delays below this value are raised to it, unless
+    /// overridden by a custom delay.
+    pub min_delay: u16,
+
+    /// Region `[x, y, w, h]` each composited frame is cropped to,
+    /// before resizing. Regions out of bounds are clamped.
+    pub crop: Option<[u16; 4]>,
+
+    /// Mirror each frame left to right, after cropping and resizing.
+    pub flip_horizontal: bool,
+
+    /// Mirror each frame top to bottom, after cropping and resizing.
+    pub flip_vertical: bool,
+
+    /// Don't print warnings, such as for clamped crop regions.
+    pub no_warnings: bool,
+
+    /// Terminal columns and rows, to check that frames fit on screen
+    /// when neither dimension is given.
+    pub terminal_size: Option<(u16, u16)>,
+
+    /// Downscale frames that don't fit on screen, keeping their
+    /// aspect ratio.
+    pub fit: bool,
+
+    /// Width and height proportions of terminal cells, correcting the
+    /// aspect ratio of resized frames.
+    pub cell_aspect: [u16; 2],
+
+    /// Decode input as a single still image (e.g. PNG, JPEG, or BMP)
+    /// instead of a GIF, drawn as a one-frame animation.
+    pub still_image: bool,
+
+    /// Report progress of decoding and formatting frames, unless quiet.
+    pub verbosity: Verbosity,
 }
 
-pub struct CustomFrameParser<'a> {
-    pub formatter: &'a dyn FrameFormatter,
-    pub height: u16,
-    pub width: u16,
+/// Color adjustments for frames that render too dark or too bright.
+#[derive(Debug, Clone, Copy)]
+pub struct ColorAdjustments {
+    /// Invert each sRGB component.
+    pub invert: bool,
+
+    /// Factor scaling linear light, where 1 keeps colors unchanged.
+    pub brightness: f32,
+
+    /// Factor scaling the difference of linear light from mid gray,
+    /// where 1 keeps colors unchanged.
+    pub contrast: f32,
 }
 
-impl GifFrameParser<'_> {
-    fn prepare_names(&self, frame: &gif::Frame, w: u16, h: u16) -> Vec<String> {
-        let rgba_chunks: Vec<_> = frame.buffer.chunks(4).map(|c| c.to_vec()).collect();
-        let lines: Vec<_> = rgba_chunks
-            .chunks(frame.width.into())
-            .map(|c| c.to_vec())
-            .collect();
-        let mut lines_out: Vec<_> = vec![];
-        for _ in 0..frame.top {
-            lines_out.push(self.formatter.blank().repeat(w as usize));
-        }
-        for line in lines {
-            let mut line_format = String::new();
-            for _ in 0..frame.left {
-                line_format += self.formatter.blank();
-            }
-            for rgba in line {
-                line_format += self.formatter.to_framedot(Some(rgba)).as_str();
-            }
-            for _ in frame.left + frame.width..w {
-                line_format += self.formatter.blank();
-            }
-            lines_out.push(line_format);
-        }
-        for _ in frame.top + frame.height..h {
-            lines_out.push(self.formatter.blank().repeat(w as usize));
+impl ColorAdjustments {
+    fn is_identity(&self) -> bool {
+        !self.invert && self.brightness == 1.0 && self.contrast == 1.0
+    }
+
+    /// Adjust an sRGB color, inverting it before scaling linear light,
+    /// then clamping it back to 8-bit components.
+    fn apply(&self, rgb: [u8; 3]) -> [u8; 3] {
+        if self.is_identity() {
+            return rgb;
         }
+        let rgb = if self.invert {
+            rgb.map(|c| 255 - c)
+        } else {
+            rgb
+        };
+        let (r, g, b) = Srgb::new(rgb[0], rgb[1], rgb[2])
+            .into_format::<f32>()
+            .into_linear::<f32>()
+            .into_components();
+        // sRGB mid gray, which is kept unchanged by contrast.
+        let mid = Srgb::new(0.5f32, 0.5, 0.5).into_linear::<f32>().red;
+        let adjust = |c: f32| (((c - mid) * self.contrast + mid) * self.brightness).clamp(0.0, 1.0);
+        let (r, g, b) = Srgb::<f32>::from_linear(LinSrgb::new(adjust(r), adjust(g), adjust(b)))
+            .into_format::<u8>()
+            .into_components();
 
-        lines_out
+        [r, g, b]
     }
 }
 
-impl FrameParser for GifFrameParser<'_> {
-    fn from_input(
-        &self,
-        filename: &PathBuf,
-        clear_line: bool,
-        delay: Option<u16>,
-    ) -> Vec<FrameInfo> {
-        let file = File::open(filename).unwrap();
-        let mut decoder = gif::DecodeOptions::new();
-        decoder.set_color_output(gif::ColorOutput::RGBA);
-        let mut decoder = decoder.read_info(file).unwrap();
-        let w = decoder.width();
-        let h = decoder.height();
-        debug!("dim {}x{}", w, h);
+pub struct ApngFrameParser<'a> {
+    pub formatter: &'a dyn FrameFormatter,
+    pub bottom_up: bool,
 
-        let mut fn_idx: usize = 1;
-        let mut frame_infos: Vec<FrameInfo> = vec![];
-        while let Some(frame) = decoder.read_next_frame().unwrap() {
-            debug!(
-                "frame +{}+{} {}x{} delay {}",
-                frame.left, frame.top, frame.width, frame.height, frame.delay
-            );
+    /// Draw all lines of each frame with a single frame line.
+    pub single_line: bool,
+}
 
-            let fn_names = self.prepare_names(&frame, w, h);
-            frame_infos.push(self.prepare_frame(
-                self.formatter,
-                fn_names,
-                &mut fn_idx,
-                delay.unwrap_or(frame.delay),
-                clear_line,
-            ));
-        }
+pub struct SequenceFrameParser<'a> {
+    pub formatter: &'a dyn FrameFormatter,
+    pub bottom_up: bool,
 
-        frame_infos
-    }
+    /// Draw all lines of each frame with a single frame line.
+    pub single_line: bool,
+
+    /// Dimensions each image is resized to. If neither is given,
+    /// all images must have the same dimensions.
+    pub height: Option<u16>,
+    pub width: Option<u16>,
 }
 
-impl FrameParser for CustomFrameParser<'_> {
-    fn from_input(
-        &self,
-        _filename: &PathBuf,
-        clear_line: bool,
-        delay: Option<u16>,
-    ) -> Vec<FrameInfo> {
-        let mut fn_idx: usize = 1;
-        let mut frame_infos: Vec<FrameInfo> = vec![];
-        let mut fn_names: Vec<_> = vec![];
-        for _ in 0..self.height {
-            let mut line = String::new();
-            for _ in 0..self.width {
-                line += &*self.formatter.to_framedot(None);
-            }
-            fn_names.push(line);
-        }
-        frame_infos.push(self.prepare_frame(
-            self.formatter,
-            fn_names,
-            &mut fn_idx,
-            delay.unwrap_or(100),
-            clear_line,
-        ));
+pub struct VideoFrameParser<'a> {
+    /// Parser of the frames extracted from the video.
+    pub sequence: SequenceFrameParser<'a>,
 
-        frame_infos
-    }
+    /// Frame rate frames are sampled at, instead of every frame.
+    pub fps: Option<f32>,
 }
 
-const COMPILER_ARGS: &[&str] = &[
-    "-fdiagnostics-color=always",
-    "-std=gnu99",
-    "-O0",
-    "-nostdlib",
-    "-static",
-    "-Wall",
-    "-Werror",
-];
-
-/// Placeholder address for `.symtab` offsets embedded in `.data` section.
-const PLACEHOLDER_SYMTAB_ADDR: u64 = 0x01020304;
+pub struct TextFrameParser<'a> {
+    pub formatter: &'a dyn FrameFormatter,
+    pub bottom_up: bool,
 
-/// Placeholder address for `.debug_str` offsets embedded in `.data` section.
-const PLACEHOLDER_DEBUGSTR_ADDR: u64 = 0x05060708;
+    /// Draw all lines of each frame with a single frame line.
+    pub single_line: bool,
 
-#[derive(Debug)]
-pub struct FrameInfo {
-    delay: u16,
-    first_name: String,
-    last_name: String,
-    tmp_names: Vec<String>,
-    tmp_to_frameline: HashMap<String, String>,
+    /// Line separating the text blocks of consecutive frames.
+    pub delimiter: String,
 }
 
-#[derive(Debug)]
-pub struct SymbolInfo {
-    addr: u64,
-    offs: Vec<u64>,
+pub struct QoiFrameParser<'a> {
+    /// Parser of a directory of QOI files, or of a single one.
+    pub sequence: SequenceFrameParser<'a>,
 }
 
-#[derive(Debug)]
-pub struct BinInfo {
-    pub build_id_desc_offs: u64,
-    pub build_id_desc: Vec<u8>,
-    pub name_to_info: HashMap<String, SymbolInfo>,
-    pub section_offs: HashMap<String, u64>,
-    pub size: u64,
+pub struct CustomFrameParser<'a> {
+    pub formatter: &'a dyn FrameFormatter,
+    pub bottom_up: bool,
+    pub height: u16,
+    pub width: u16,
 }
 
-pub trait FrameConverter {
-    /// `.data` address defined in linker script.
-    fn data_section_addr(&self) -> u64 {
-        0
+impl GifFrameParser<'_> {
+    /// Composite `frame` over the canvas, leaving colors that weren't
+    /// matched with a palette entry to be formatted later.
+    fn prepare_pixels(
+        &self,
+        frame: &gif::Frame,
+        palette: &[u8],
+        canvas: &mut GifCanvas,
+    ) -> FramePixels {
+        if self.motion_blur > 0.0
+            || self.match_per_dot
+            || self.dither
+            || self.max_colors.is_some()
+            || self.keep_colors
+            || self.formatter.groups_pixels()
+        {
+            FramePixels::Colors(self.prepare_blended_colors(frame, palette, canvas))
+        } else {
+            FramePixels::Dots(self.prepare_dots(frame, palette, canvas))
+        }
     }
 
-    /// `.text` address defined in linker script.
-    fn text_section_addr(&self) -> u64 {
-        0x401000
+    /// Dimensions of resized frames, if they are resized from `w`x`h`.
+    /// Unless both dimensions are given, frames are first stretched
+    /// vertically to undo the aspect ratio of drawn pixels.
+    fn resized_dimensions(&self, w: u16, h: u16) -> Option<(usize, usize)> {
+        let stretch = self.pixel_aspect();
+        let corrected = (stretch != 1.0).then(|| {
+            let h = (h as f64 / stretch).round().clamp(1.0, u16::MAX as f64) as u16;
+            (w, h)
+        });
+        let (w, h) = corrected.unwrap_or((w, h));
+        let scaled = |from: u16, to: u16, other: u16| {
+            ((other as f64 * to as f64 / from as f64).round() as usize).max(1)
+        };
+        match (self.width, self.height) {
+            (None, None) => self
+                .fitted_dimensions(w, h)
+                .or(corrected.map(|(w, h)| (w as usize, h as usize))),
+            (Some(width), Some(height)) => Some((width as usize, height as usize)),
+            (Some(width), None) => Some((width as usize, scaled(w, width, h))),
+            (None, Some(height)) => Some((scaled(h, height, w), height as usize)),
+        }
     }
 
-    fn parser(&self) -> &dyn FrameParser;
+    /// Ratio of the height to the width of each pixel drawn on screen,
+    /// given the aspect ratio of terminal cells and how many columns
+    /// and pixels are taken by each frame dot.
+    fn pixel_aspect(&self) -> f64 {
+        let [cell_w, cell_h] = self.cell_aspect.map(f64::from);
+        let (pixels_w, pixels_h) = self.formatter.cell_size();
+        let pixel_w = self.formatter.dot_width() as f64 * cell_w / pixels_w as f64;
+        let pixel_h = cell_h / pixels_h as f64;
 
-    /// Convert function names to temporary names and frame lines.
-    fn parse_input(
-        &self,
-        filename: &PathBuf,
-        clear_line: bool,
-        delay: Option<u16>,
-    ) -> Vec<FrameInfo> {
-        self.parser().from_input(filename, clear_line, delay)
+        pixel_h / pixel_w
     }
 
-    /// Get C source code with nested function calls for each
-    /// frame to render. Functions prototypes use the generated
-    /// temporary names.
-    fn prepare_src(
-        &self,
-        frame_infos: &Vec<FrameInfo>,
-        start_tmp_name: &str,
-        _has_debug_info: bool,
-    ) -> String {
-        let heads = frame_infos
-            .iter()
-            .map(|n| format!("{}();", n.first_name))
-            .collect::<Vec<String>>()
-            .join("\n    ");
-        let calls = frame_infos
-            .iter()
-            .map(|n| {
-                let mut o = String::new();
-                for (prev, next) in n.tmp_names.iter().tuple_windows() {
-                    o = format!(
-                        r#"
-void {}() {{
-    {}();
-}}
-{}"#,
-                        prev, next, o
-                    );
-                }
-                format!(
-                    r#"
-void {}() {{
-    return;
-}}
-{}"#,
-                    n.tmp_names.last().unwrap(),
-                    o
-                )
-            })
-            .collect::<Vec<String>>()
-            .join("\n");
+    /// Dimensions of `w`x`h` frames downscaled to fit the terminal,
+    /// if they don't fit on screen and fitting is enabled.
+    fn fitted_dimensions(&self, w: u16, h: u16) -> Option<(usize, usize)> {
+        let (columns, rows) = self.terminal_size?;
+        // Largest frame in pixels, accounting for frame dots spanning
+        // multiple columns and drawing multiple pixels.
+        let max_w =
+            (columns as usize / self.formatter.dot_width()).max(1) * self.formatter.cell_size().0;
+        let max_h = rows as usize * self.formatter.rows_per_line();
+        if w as usize <= max_w && h as usize <= max_h {
+            return None;
+        }
+        if !self.fit {
+            self.warn(&format!(
+                "[!] {}x{} frames don't fit the {}x{} terminal, pass --fit to downscale them.",
+                w, h, columns, rows
+            ));
+            return None;
+        }
 
-        format!(
-            r#"
-{}
+        let scale = f64::min(max_w as f64 / w as f64, max_h as f64 / h as f64);
+        let fitted = (
+            ((w as f64 * scale) as usize).max(1),
+            ((h as f64 * scale) as usize).max(1),
+        );
+        debug!(
+            "fit {}x{} to {:?} in {}x{} terminal",
+            w, h, fitted, columns, rows
+        );
 
-void {}() {{
-loop:
-    {}
-    goto loop;
-}}"#,
-            calls, start_tmp_name, heads
-        )
+        Some(fitted)
     }
 
-    /// Compile the generated C source code, optionally including
-    /// debug info sections.
-    fn compile(
-        &self,
-        src: &str,
-        compiler: &str,
-        start_tmp_name: &str,
-        include_debug_info: bool,
-    ) -> Result<(), Box<dyn Error>> {
-        let name = std::path::Path::new("a.c");
-        let mut file = std::fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(name)?;
-        file.write_all(src.as_bytes())?;
-        spawn(
-            Command::new(compiler).args(
-                include_debug_info
-                    .then_some(&["-g"])
-                    .into_iter()
-                    .flatten()
-                    .chain(COMPILER_ARGS)
-                    .chain(&[
-                        "-Wl,--build-id",
-                        &format!("-Wl,--entry={}", start_tmp_name),
-                        name.to_str().unwrap(),
-                    ]),
-            ),
-        )
+    /// Print an advisory warning, unless suppressed.
+    fn warn(&self, message: &str) {
+        if !self.no_warnings {
+            eprintln!("{}\n", message.red().bold());
+        }
     }
 
-    fn parse_build_id(&self, file: &mut File, build_id: Option<Section>) -> (u64, Vec<u8>) {
-        build_id.map_or((0, vec![]), |section| {
-            if section.get_type() != lief::elf::section::Type::NOTE {
-                panic!("Unexpected type '{:?}' for build id", section.get_type());
-            }
+    /// Crop region clamped to the bounds of `w`x`h` frames.
+    fn clamped_crop(&self, w: u16, h: u16) -> Option<[u16; 4]> {
+        let crop = self.crop?;
+        let [x, y, crop_w, crop_h] = crop;
+        let (x, y) = (x.min(w.saturating_sub(1)), y.min(h.saturating_sub(1)));
+        let clamped = [x, y, crop_w.min(w - x), crop_h.min(h - y)];
+        if clamped != crop {
+            self.warn(&format!(
+                "[!] Crop region {:?} out of bounds of {}x{} frames, clamped to {:?}.",
+                crop, w, h, clamped
+            ));
+        }
 
-            let mut offs = section.file_offset();
-            let mut buf4 = [0; 4];
-            file.seek(std::io::SeekFrom::Start(offs))
-                .expect(&*format!("Can't seek to 0x{:08x}", offs));
-            file.read_exact(&mut buf4).expect("Can't read bin");
-            let name_len = u32::from_le_bytes(buf4);
-            offs += 4;
-
-            file.seek(std::io::SeekFrom::Start(offs))
-                .expect(&*format!("Can't seek to 0x{:08x}", offs));
-            file.read_exact(&mut buf4).expect("Can't read bin");
-            let desc_len = u32::from_le_bytes(buf4);
-            offs += 4 + 4 + name_len as u64; // Skip `type`.
-
-            let mut desc = vec![0; desc_len as usize];
-            file.seek(std::io::SeekFrom::Start(offs))
-                .expect(&*format!("Can't seek to 0x{:08x}", offs));
-            file.read_exact(&mut desc).expect("Can't read bin");
-
-            (offs, desc)
-        })
+        Some(clamped)
     }
 
-    fn parse_debug_str(&self, debug_str: Option<Section>) -> HashMap<String, u64> {
-        let mut name_to_debug_offs = HashMap::new();
+    /// Crop, resize, then flip composited `pixels`, as configured.
+    fn reshape(
+        &self,
+        mut pixels: FramePixels,
+        crop: Option<[u16; 4]>,
+        resized: Option<(usize, usize)>,
+    ) -> FramePixels {
+        if let Some([x, y, w, h]) = crop {
+            pixels = pixels.crop(x as usize, y as usize, w as usize, h as usize);
+        }
+        if let Some((w, h)) = resized {
+            pixels = pixels.resize(w, h);
+        }
+        if self.flip_horizontal || self.flip_vertical {
+            pixels = pixels.flip(self.flip_horizontal, self.flip_vertical);
+        }
 
-        // Find offsets, assuming strings are separated by a single null byte.
-        //
-        // TODO: A more robust approach would be to parse
-        // relocations in .debug_info and .debug_types sections
-        // that refer to the .debug_str section.
-        debug_str.map(|section| {
-            let section_offs = section.file_offset();
-            let mut prev_i = 0;
-            let haystack = section.content();
-            for i in memmem::find_iter(haystack, b"\x00") {
-                let name = str::from_utf8(&haystack[prev_i as usize..i])
-                    .unwrap()
-                    .to_string();
-                name_to_debug_offs.insert(name, section_offs + prev_i);
-                prev_i = i as u64 + 1;
-            }
-        });
+        pixels
+    }
 
-        name_to_debug_offs
+    /// Format composited `frames` in parallel, each along with its
+    /// overlay text and delay.
+    fn format_frames(
+        &self,
+        frames: Vec<(FramePixels, Option<String>, u16)>,
+        clear_line: bool,
+    ) -> Vec<FrameInfo> {
+        let frames = match self.max_colors {
+            Some(max_colors) => reduce_colors(frames, max_colors),
+            None => frames,
+        };
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.jobs)
+            .build()
+            .unwrap();
+        let progress = Progress::new("Matching colors", Some(frames.len()), self.verbosity);
+        let (names_and_colors, delays): (Vec<_>, Vec<_>) = pool.install(|| {
+            frames
+                .into_par_iter()
+                .map(|(pixels, overlay, delay)| {
+                    let names = self.prepare_names(pixels, overlay);
+                    progress.inc();
+                    (names, delay)
+                })
+                .unzip()
+        });
+        drop(progress);
+        let (fn_names, colors): (Vec<_>, Vec<_>) = names_and_colors.into_iter().unzip();
+        // Functions are numbered across frames, so each frame starts
+        // after the frame lines of previous frames.
+        let first_fn_idxs = fn_names
+            .iter()
+            .scan(1, |fn_idx, names| {
+                let first = *fn_idx;
+                *fn_idx += if self.single_line() { 1 } else { names.len() };
+                Some(first)
+            })
+            .collect_vec();
+        let progress = Progress::new("Formatting frames", Some(fn_names.len()), self.verbosity);
+        pool.install(|| {
+            fn_names
+                .into_par_iter()
+                .zip(first_fn_idxs)
+                .zip(delays)
+                .zip(colors)
+                .map(|(((names, mut fn_idx), delay), colors)| {
+                    let mut frame_info =
+                        self.prepare_frame(self.formatter, names, &mut fn_idx, delay, clear_line);
+                    frame_info.colors = colors;
+                    progress.inc();
+                    frame_info
+                })
+                .collect()
+        })
     }
 
-    fn parse_bin(&self, file: &str) -> BinInfo {
-        let mut name_to_info = HashMap::new();
+    /// Single frame drawing the still image in `filename`, preceded by
+    /// the title card if any. Images without alpha are fully opaque.
+    fn from_still_image(&self, filename: &Path, clear_line: bool, delay: Option<u16>) -> Animation {
+        let image = image::open(filename)
+            .unwrap_or_else(|e| panic!("Can't decode image {}: {}", filename.display(), e))
+            .to_rgba8();
+        let (w, h) = match (u16::try_from(image.width()), u16::try_from(image.height())) {
+            (Ok(w), Ok(h)) => (w, h),
+            _ => panic!(
+                "Image {} has dimensions {}x{}, expected at most {}x{}.",
+                filename.display(),
+                image.width(),
+                image.height(),
+                u16::MAX,
+                u16::MAX
+            ),
+        };
+        let crop = self.clamped_crop(w, h);
+        let resized = match crop {
+            Some([_, _, crop_w, crop_h]) => self.resized_dimensions(crop_w, crop_h),
+            None => self.resized_dimensions(w, h),
+        };
+        debug!("dim {}x{} crop {:?} resized {:?}", w, h, crop, resized);
+
+        // Colors are transformed as a palette of all pixels, keeping
+        // their alpha.
+        let rgb = image
+            .pixels()
+            .flat_map(|rgba| &rgba.0[..3])
+            .copied()
+            .collect_vec();
+        let colors = self
+            .transform_palette(&rgb)
+            .chunks_exact(3)
+            .zip(image.pixels())
+            .map(|(rgb, rgba)| [rgb[0], rgb[1], rgb[2], rgba.0[3]])
+            .collect_vec()
+            .chunks_exact(w as usize)
+            .map(<[_]>::to_vec)
+            .collect_vec();
+
+        let mut frames: Vec<(FramePixels, Option<String>, u16)> = vec![];
+        let mut elapsed: u64 = 0;
+        if let Some(caption) = &self.caption {
+            let colors = self.prepare_caption_colors(caption);
+            frames.push((FramePixels::Colors(colors), None, self.caption_delay));
+            elapsed += self.caption_delay as u64;
+        }
+        let overlay = self
+            .overlay_index
+            .then(|| format!("{} {:.2}s", frames.len(), elapsed as f64 / 100.0));
+        let pixels = self.reshape(FramePixels::Colors(colors), crop, resized);
+        frames.push((pixels, overlay, delay.unwrap_or(STATIC_DELAY)));
+
+        Animation {
+            frame_infos: self.format_frames(frames, clear_line),
+            loops: 0,
+        }
+    }
+
+    /// Frame lines drawing `pixels`, along with the colors drawn by
+    /// each frame dot if they are kept.
+    fn prepare_names(
+        &self,
+        pixels: FramePixels,
+        overlay: Option<String>,
+    ) -> (Vec<String>, Option<Vec<Vec<[u8; 4]>>>) {
+        let mut kept_colors = None;
+        let mut lines_out = match pixels {
+            FramePixels::Dots(dots) => dots,
+            FramePixels::Colors(mut colors) => {
+                if self.dither {
+                    dither(self.formatter, &mut colors);
+                }
+                if self.keep_colors {
+                    kept_colors = Some(quantize_colors(self.formatter, &colors));
+                }
+                to_framedots(self.formatter, &colors)
+            }
+        };
+
+        // Overlay text is right-aligned on the bottom line, with as many
+        // characters per frame dot as its width, truncated if the frame
+        // is too narrow.
+        if let Some(text) = overlay {
+            let dot_width = self.formatter.dot_width();
+            let chars = format!("{:>1$}", text, text.len().next_multiple_of(dot_width))
+                .chars()
+                .collect_vec();
+            let mut dots = chars
+                .chunks(dot_width)
+                .map(|c| {
+                    self.formatter
+                        .to_framedot_text(&c.iter().collect::<String>())
+                })
+                .collect_vec();
+            if let Some(last_line) = lines_out.last_mut() {
+                dots.truncate(last_line.len());
+                let start = last_line.len() - dots.len();
+                last_line.splice(start.., dots);
+            }
+        }
+
+        (
+            lines_out.into_iter().map(|line| line.concat()).collect(),
+            kept_colors,
+        )
+    }
+
+    /// Palette with each color transformed by the color matrix.
+    /// Transparency is given by palette indexes, so it's unaffected.
+    fn transform_palette(&self, palette: &[u8]) -> Vec<u8> {
+        if self.color_matrix.is_none() && self.adjustments.is_identity() {
+            return palette.to_vec();
+        }
+        palette
+            .chunks_exact(3)
+            .flat_map(|rgb| {
+                let rgb = match self.color_matrix {
+                    Some(matrix) => matrix.map(|row| {
+                        row.iter()
+                            .zip(rgb)
+                            .map(|(k, c)| k * *c as f32)
+                            .sum::<f32>()
+                            .round()
+                            .clamp(0.0, 255.0) as u8
+                    }),
+                    None => [rgb[0], rgb[1], rgb[2]],
+                };
+                self.adjustments.apply(rgb)
+            })
+            .collect()
+    }
+
+    /// Colors of a title card, with `caption` drawn as white block
+    /// characters surrounded by a black margin of one frame dot.
+    fn prepare_caption_colors(&self, caption: &str) -> Vec<Vec<[u8; 4]>> {
+        // Captions are validated with the other options.
+        let rows = font::render(caption).unwrap();
+        let margin_row = vec![false; rows[0].len()];
+        std::iter::once(&margin_row)
+            .chain(rows.iter())
+            .chain(std::iter::once(&margin_row))
+            .map(|row| {
+                std::iter::once(&false)
+                    .chain(row.iter())
+                    .chain(std::iter::once(&false))
+                    .map(|filled| {
+                        if *filled {
+                            [255, 255, 255, 255]
+                        } else {
+                            [0, 0, 0, 255]
+                        }
+                    })
+                    .collect_vec()
+            })
+            .collect_vec()
+    }
+
+    /// Frame dots of `frame` composited over the canvas.
+    fn prepare_dots(
+        &self,
+        frame: &gif::Frame,
+        palette: &[u8],
+        canvas: &mut GifCanvas,
+    ) -> Vec<Vec<String>> {
+        // Colors are matched once per palette entry instead of once per
+        // frame dot, since frames only index a small set of colors.
+        let palette_dots = palette
+            .chunks_exact(3)
+            .map(|rgb| {
+                self.formatter
+                    .to_framedot(Some(vec![rgb[0], rgb[1], rgb[2], 0xff]))
+            })
+            .collect_vec();
+        let blank = String::from(self.formatter.blank());
+        composite(&mut canvas.dots, &frame.into(), &blank, |i, _| {
+            let idx = frame.buffer[i];
+            (frame.transparent != Some(idx)).then(|| {
+                palette_dots
+                    .get(idx as usize)
+                    .map_or(blank.to_owned(), String::to_owned)
+            })
+        })
+    }
+
+    /// Colors of `frame` composited over the canvas, after blending
+    /// the previous blended canvas into it. Blending only applies
+    /// where both dots are opaque, so transparent dots stay blank.
+    fn prepare_blended_colors(
+        &self,
+        frame: &gif::Frame,
+        palette: &[u8],
+        canvas: &mut GifCanvas,
+    ) -> Vec<Vec<[u8; 4]>> {
+        let mut colors = composite(&mut canvas.colors, &frame.into(), &[0; 4], |i, _| {
+            let idx = frame.buffer[i];
+            (frame.transparent != Some(idx)).then(|| {
+                let offset = idx as usize * 3;
+                palette
+                    .get(offset..offset + 3)
+                    .map_or([0; 4], |rgb| [rgb[0], rgb[1], rgb[2], 0xff])
+            })
+        });
+        if let Some(blended) = &canvas.blended {
+            for (rgba, prev_rgba) in colors.iter_mut().flatten().zip(blended.iter().flatten()) {
+                if rgba[3] == 0 || prev_rgba[3] == 0 {
+                    continue;
+                }
+                for c in 0..3 {
+                    rgba[c] = (rgba[c] as f32 * (1.0 - self.motion_blur)
+                        + prev_rgba[c] as f32 * self.motion_blur)
+                        .round() as u8;
+                }
+            }
+        }
+
+        canvas.blended = Some(colors.to_owned());
+
+        colors
+    }
+}
+
+/// Frame composited over the canvas, as either frame dots matched
+/// once per palette entry, or colors still to be formatted.
+enum FramePixels {
+    Dots(Vec<Vec<String>>),
+    Colors(Vec<Vec<[u8; 4]>>),
+}
+
+impl FramePixels {
+    /// Mirror columns with `horizontal`, and rows with `vertical`.
+    /// Rows are flipped before frame lines are formatted, so any
+    /// row order still draws the mirrored frame.
+    fn flip(self, horizontal: bool, vertical: bool) -> Self {
+        fn flip_grid<T>(mut grid: Vec<Vec<T>>, horizontal: bool, vertical: bool) -> Vec<Vec<T>> {
+            if horizontal {
+                grid.iter_mut().for_each(|row| row.reverse());
+            }
+            if vertical {
+                grid.reverse();
+            }
+            grid
+        }
+
+        match self {
+            FramePixels::Dots(dots) => FramePixels::Dots(flip_grid(dots, horizontal, vertical)),
+            FramePixels::Colors(colors) => {
+                FramePixels::Colors(flip_grid(colors, horizontal, vertical))
+            }
+        }
+    }
+
+    /// Region of `w`x`h` frame dots starting at column `x` and row `y`.
+    fn crop(self, x: usize, y: usize, w: usize, h: usize) -> Self {
+        fn crop_grid<T>(grid: Vec<Vec<T>>, x: usize, y: usize, w: usize, h: usize) -> Vec<Vec<T>> {
+            grid.into_iter()
+                .skip(y)
+                .take(h)
+                .map(|row| row.into_iter().skip(x).take(w).collect())
+                .collect()
+        }
+
+        match self {
+            FramePixels::Dots(dots) => FramePixels::Dots(crop_grid(dots, x, y, w, h)),
+            FramePixels::Colors(colors) => FramePixels::Colors(crop_grid(colors, x, y, w, h)),
+        }
+    }
+
+    /// Resample to `w`x`h` by nearest neighbor, which keeps frame dots
+    /// matched per palette entry, since no new colors are introduced.
+    fn resize(self, w: usize, h: usize) -> Self {
+        fn resize_grid<T: Clone>(grid: Vec<Vec<T>>, w: usize, h: usize) -> Vec<Vec<T>> {
+            let (src_w, src_h) = (grid[0].len(), grid.len());
+            (0..h)
+                .map(|y| {
+                    let row = &grid[(2 * y + 1) * src_h / (2 * h)];
+                    (0..w)
+                        .map(|x| row[(2 * x + 1) * src_w / (2 * w)].to_owned())
+                        .collect()
+                })
+                .collect()
+        }
+
+        match self {
+            FramePixels::Dots(dots) => FramePixels::Dots(resize_grid(dots, w, h)),
+            FramePixels::Colors(colors) => FramePixels::Colors(resize_grid(colors, w, h)),
+        }
+    }
+}
+
+/// Floyd-Steinberg dithering of `colors` with the palette of
+/// `formatter`, leaving colors unchanged if it doesn't have one.
+/// Errors are only diffused between opaque colors, so that transparent
+/// colors stay blank.
+fn dither(formatter: &dyn FrameFormatter, colors: &mut [Vec<[u8; 4]>]) {
+    let w = colors[0].len();
+    // Errors diffused into the current and next rows.
+    let mut errors = vec![[0.0f32; 3]; w];
+    let mut next_errors = vec![[0.0f32; 3]; w];
+    for row in colors.iter_mut() {
+        for x in 0..w {
+            let rgba = &mut row[x];
+            if rgba[3] == 0 {
+                continue;
+            }
+            for c in 0..3 {
+                rgba[c] = (rgba[c] as f32 + errors[x][c]).round().clamp(0.0, 255.0) as u8;
+            }
+            let Some(quantized) = formatter.quantize(rgba) else {
+                return;
+            };
+            let error = [0, 1, 2].map(|c| rgba[c] as f32 - quantized[c] as f32);
+            for (dx, dy, weight) in [(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)] {
+                let Some(nx) = x.checked_add_signed(dx).filter(|nx| *nx < w) else {
+                    continue;
+                };
+                let target = if dy == 0 {
+                    &mut errors[nx]
+                } else {
+                    &mut next_errors[nx]
+                };
+                for c in 0..3 {
+                    target[c] += error[c] * weight / 16.0;
+                }
+            }
+        }
+        errors = std::mem::replace(&mut next_errors, vec![[0.0; 3]; w]);
+    }
+}
+
+/// Colors drawn for `colors` by `formatter`, approximated by its
+/// palette, or unchanged if it doesn't have one.
+fn quantize_colors(formatter: &dyn FrameFormatter, colors: &[Vec<[u8; 4]>]) -> Vec<Vec<[u8; 4]>> {
+    colors
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|rgba| match (rgba[3], formatter.quantize(rgba)) {
+                    (0, _) | (_, None) => *rgba,
+                    (a, Some([r, g, b])) => [r, g, b, a],
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Opaque colors of `frames` mapped to a palette of at most
+/// `max_colors` colors, built by median cut over the colors of all
+/// frames, so that frames share the same reduced colors.
+fn reduce_colors(
+    mut frames: Vec<(FramePixels, Option<String>, u16)>,
+    max_colors: usize,
+) -> Vec<(FramePixels, Option<String>, u16)> {
+    let mut counts = HashMap::new();
+    for (pixels, _, _) in &frames {
+        if let FramePixels::Colors(colors) = pixels {
+            for rgba in colors.iter().flatten().filter(|rgba| rgba[3] != 0) {
+                *counts.entry([rgba[0], rgba[1], rgba[2]]).or_insert(0) += 1;
+            }
+        }
+    }
+    let quantizer = quant::Quantizer::new(quant::median_cut(&counts, max_colors));
+    let reduced = counts
+        .into_keys()
+        .map(|rgb| (rgb, quantizer.nearest(rgb)))
+        .collect::<HashMap<_, _>>();
+    debug!(
+        "reduced {} colors to {}",
+        reduced.len(),
+        reduced.values().unique().count()
+    );
+    for (pixels, _, _) in &mut frames {
+        if let FramePixels::Colors(colors) = pixels {
+            for rgba in colors.iter_mut().flatten().filter(|rgba| rgba[3] != 0) {
+                let [r, g, b] = reduced[&[rgba[0], rgba[1], rgba[2]]];
+                *rgba = [r, g, b, rgba[3]];
+            }
+        }
+    }
+
+    frames
+}
+
+/// Frame dots of each frame line drawing `colors`, with as many
+/// rows per frame line as drawn by `formatter`, or a single frame
+/// line if the whole frame is drawn at once.
+fn to_framedots(formatter: &dyn FrameFormatter, colors: &[Vec<[u8; 4]>]) -> Vec<Vec<String>> {
+    if let Some(frame) = formatter.whole_frame(colors, colors[0].len(), colors.len()) {
+        return vec![vec![frame]];
+    }
+
+    colors
+        .chunks(formatter.rows_per_line())
+        .map(|lines| formatter.to_framedots_band(lines))
+        .collect()
+}
+
+/// Canvas carried across GIF frames, since each frame only updates
+/// a sub-rect of it.
+struct GifCanvas {
+    /// Frame dots, when colors are matched once per palette entry.
+    dots: Vec<Vec<String>>,
+
+    /// RGBA colors, when colors are matched for each frame dot.
+    colors: Vec<Vec<[u8; 4]>>,
+
+    /// RGBA colors of the previous frame after blending.
+    blended: Option<Vec<Vec<[u8; 4]>>>,
+}
+
+/// How a frame's sub-rect is disposed before the next frame.
+enum Dispose {
+    Keep,
+    Background,
+    Previous,
+}
+
+/// Sub-rect of the canvas updated by a frame.
+struct FrameRegion {
+    left: usize,
+    top: usize,
+    width: usize,
+    height: usize,
+    dispose: Dispose,
+}
+
+impl From<&gif::Frame<'_>> for FrameRegion {
+    fn from(frame: &gif::Frame) -> Self {
+        FrameRegion {
+            left: frame.left as usize,
+            top: frame.top as usize,
+            width: frame.width as usize,
+            height: frame.height as usize,
+            dispose: match frame.dispose {
+                gif::DisposalMethod::Background => Dispose::Background,
+                gif::DisposalMethod::Previous => Dispose::Previous,
+                gif::DisposalMethod::Any | gif::DisposalMethod::Keep => Dispose::Keep,
+            },
+        }
+    }
+}
+
+/// Paint the sub-rect given by `region` over `canvas`, where `dot` maps
+/// each sub-rect index and underlying dot to a dot, or to `None` for
+/// transparent dots that leave the underlying dot intact. Returns the
+/// painted canvas, then disposes the sub-rect as specified, preparing
+/// the canvas for the next frame.
+fn composite<T: Clone>(
+    canvas: &mut Vec<Vec<T>>,
+    region: &FrameRegion,
+    blank: &T,
+    dot: impl Fn(usize, &T) -> Option<T>,
+) -> Vec<Vec<T>> {
+    let restored = matches!(region.dispose, Dispose::Previous).then(|| canvas.clone());
+    let rect = |i: usize| {
+        (
+            region.left + i % region.width,
+            region.top + i / region.width,
+        )
+    };
+    for i in 0..region.width * region.height {
+        let (x, y) = rect(i);
+        if let Some(canvas_dot) = canvas.get_mut(y).and_then(|line| line.get_mut(x))
+            && let Some(dot) = dot(i, canvas_dot)
+        {
+            *canvas_dot = dot;
+        }
+    }
+    let painted = canvas.clone();
+
+    match region.dispose {
+        Dispose::Background => {
+            for i in 0..region.width * region.height {
+                let (x, y) = rect(i);
+                if let Some(canvas_dot) = canvas.get_mut(y).and_then(|line| line.get_mut(x)) {
+                    *canvas_dot = blank.to_owned();
+                }
+            }
+        }
+        Dispose::Previous => *canvas = restored.unwrap(),
+        Dispose::Keep => {}
+    }
+
+    painted
+}
+
+impl FrameParser for GifFrameParser<'_> {
+    fn bottom_up(&self) -> bool {
+        self.bottom_up
+    }
+
+    fn single_line(&self) -> bool {
+        self.single_line
+    }
+
+    fn from_input(&self, filename: &PathBuf, clear_line: bool, delay: Option<u16>) -> Animation {
+        if self.still_image {
+            return self.from_still_image(filename, clear_line, delay);
+        }
+
+        let input: Box<dyn Read> = if filename.as_os_str() == "-" {
+            // Standard input is read in full, as a GIF file would be.
+            let mut bytes = vec![];
+            std::io::stdin()
+                .read_to_end(&mut bytes)
+                .expect("Can't read standard input");
+            Box::new(std::io::Cursor::new(bytes))
+        } else {
+            Box::new(File::open(filename).unwrap())
+        };
+        let mut decoder = gif::DecodeOptions::new();
+        decoder.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = match decoder.read_info(input) {
+            Ok(decoder) => decoder,
+            // Files without image data fail before the first frame,
+            // which is reported as not decoding any frames.
+            Err(e) => {
+                debug!("Can't read GIF info: {}", e);
+                return Animation {
+                    frame_infos: vec![],
+                    loops: 0,
+                };
+            }
+        };
+        let global_palette = decoder.global_palette().map(|palette| palette.to_vec());
+        let w = decoder.width();
+        let h = decoder.height();
+        let crop = self.clamped_crop(w, h);
+        let resized = match crop {
+            Some([_, _, crop_w, crop_h]) => self.resized_dimensions(crop_w, crop_h),
+            None => self.resized_dimensions(w, h),
+        };
+        debug!("dim {}x{} crop {:?} resized {:?}", w, h, crop, resized);
+
+        let mut elapsed: u64 = 0;
+        let mut canvas = GifCanvas {
+            dots: vec![vec![String::from(self.formatter.blank()); w as usize]; h as usize],
+            colors: vec![vec![[0; 4]; w as usize]; h as usize],
+            blended: None,
+        };
+        // Frames are composited in order, since each one is drawn over
+        // the previous ones, then formatted in parallel.
+        let mut frames: Vec<(FramePixels, Option<String>, u16)> = vec![];
+        if let Some(caption) = &self.caption {
+            let colors = self.prepare_caption_colors(caption);
+            frames.push((FramePixels::Colors(colors), None, self.caption_delay));
+            elapsed += self.caption_delay as u64;
+        }
+        // Frame count isn't known until all frames are decoded.
+        let progress = Progress::new("Decoding frames", None, self.verbosity);
+        // Truncated files keep the frames decoded before the error.
+        while let Some(frame) = decoder.read_next_frame().unwrap_or_else(|e| {
+            debug!("Can't read GIF frame: {}", e);
+            None
+        }) {
+            trace!(
+                "frame +{}+{} {}x{} delay {} dispose {:?}",
+                frame.left, frame.top, frame.width, frame.height, frame.delay, frame.dispose
+            );
+
+            let frame_delay = delay.unwrap_or(frame.delay.max(self.min_delay));
+            let overlay = self
+                .overlay_index
+                .then(|| format!("{} {:.2}s", frames.len(), elapsed as f64 / 100.0));
+            elapsed += frame_delay as u64;
+
+            let palette = frame
+                .palette
+                .as_deref()
+                .or(global_palette.as_deref())
+                .expect("No color table available for frame");
+            let palette = &self.transform_palette(palette);
+            let pixels = self.prepare_pixels(frame, palette, &mut canvas);
+            frames.push((self.reshape(pixels, crop, resized), overlay, frame_delay));
+            progress.inc();
+        }
+        drop(progress);
+
+        let frame_infos = self.format_frames(frames, clear_line);
+
+        Animation {
+            frame_infos,
+            loops: match decoder.repeat() {
+                gif::Repeat::Infinite => 0,
+                // Without a loop count, frames are also looped forever.
+                gif::Repeat::Finite(loops) => loops.into(),
+            },
+        }
+    }
+}
+
+/// Default delay in units of 10 ms for static images.
+const STATIC_DELAY: u16 = 100;
+
+/// RGBA colors of each pixel in `bytes`, decoded with 8-bit samples.
+fn to_rgba_pixels(bytes: &[u8], color_type: png::ColorType) -> Vec<[u8; 4]> {
+    match color_type {
+        png::ColorType::Grayscale => bytes.iter().map(|g| [*g, *g, *g, 0xff]).collect(),
+        png::ColorType::GrayscaleAlpha => bytes
+            .chunks_exact(2)
+            .map(|ga| [ga[0], ga[0], ga[0], ga[1]])
+            .collect(),
+        png::ColorType::Rgb => bytes
+            .chunks_exact(3)
+            .map(|rgb| [rgb[0], rgb[1], rgb[2], 0xff])
+            .collect(),
+        png::ColorType::Rgba => bytes
+            .chunks_exact(4)
+            .map(|rgba| [rgba[0], rgba[1], rgba[2], rgba[3]])
+            .collect(),
+        png::ColorType::Indexed => panic!("Indexed colors should have been expanded"),
+    }
+}
+
+/// Color `src` alpha-blended over color `dst`.
+fn blend_over(src: [u8; 4], dst: [u8; 4]) -> [u8; 4] {
+    let src_a = src[3] as f32 / 255.0;
+    let dst_a = dst[3] as f32 / 255.0 * (1.0 - src_a);
+    let a = src_a + dst_a;
+    if a == 0.0 {
+        return [0; 4];
+    }
+    let mut rgba = [0, 0, 0, (a * 255.0).round() as u8];
+    for c in 0..3 {
+        rgba[c] = ((src[c] as f32 * src_a + dst[c] as f32 * dst_a) / a).round() as u8;
+    }
+    rgba
+}
+
+impl FrameParser for ApngFrameParser<'_> {
+    fn bottom_up(&self) -> bool {
+        self.bottom_up
+    }
+
+    fn single_line(&self) -> bool {
+        self.single_line
+    }
+
+    fn from_input(&self, filename: &PathBuf, clear_line: bool, delay: Option<u16>) -> Animation {
+        let file = File::open(filename).unwrap();
+        let mut decoder = png::Decoder::new(file);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = match decoder.read_info() {
+            Ok(reader) => reader,
+            // Malformed headers are reported as not decoding any frames.
+            Err(e) => {
+                debug!("Can't read APNG info: {}", e);
+                return Animation {
+                    frame_infos: vec![],
+                    loops: 0,
+                };
+            }
+        };
+        let w = reader.info().width;
+        let h = reader.info().height;
+        let animation_control = reader.info().animation_control;
+        debug!("dim {}x{} animation {:?}", w, h, animation_control);
+
+        // The default image is only part of the animation if it's
+        // preceded by a frame control chunk, otherwise it's a fallback
+        // for decoders without APNG support.
+        let skip_default = animation_control.is_some() && reader.info().frame_control.is_none();
+        let num_frames =
+            animation_control.map_or(1, |control| control.num_frames) + skip_default as u32;
+
+        let mut fn_idx: usize = 1;
+        let mut canvas = vec![vec![[0; 4]; w as usize]; h as usize];
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let mut frame_infos: Vec<FrameInfo> = vec![];
+        for i in 0..num_frames {
+            // Truncated files keep the frames decoded before the error.
+            let output = match reader.next_frame(&mut buf) {
+                Ok(output) => output,
+                Err(e) => {
+                    debug!("Can't read APNG frame: {}", e);
+                    break;
+                }
+            };
+            if skip_default && i == 0 {
+                continue;
+            }
+
+            let frame_control = reader.info().frame_control;
+            trace!("frame {:?}", frame_control);
+            let pixels = to_rgba_pixels(&buf[..output.buffer_size()], output.color_type);
+            let (region, blend_op, frame_delay) = match frame_control {
+                Some(control) => (
+                    FrameRegion {
+                        left: control.x_offset as usize,
+                        top: control.y_offset as usize,
+                        width: control.width as usize,
+                        height: control.height as usize,
+                        // Restoring the canvas before the first frame
+                        // is the same as clearing it.
+                        dispose: match control.dispose_op {
+                            png::DisposeOp::None => Dispose::Keep,
+                            png::DisposeOp::Previous if !frame_infos.is_empty() => {
+                                Dispose::Previous
+                            }
+                            png::DisposeOp::Background | png::DisposeOp::Previous => {
+                                Dispose::Background
+                            }
+                        },
+                    },
+                    control.blend_op,
+                    // A zero denominator is treated as 100, so that the
+                    // delay is in units of 10 ms. Longer delays than
+                    // frames can hold are clamped.
+                    u16::try_from(
+                        control.delay_num as u32 * 100
+                            / match control.delay_den {
+                                0 => 100,
+                                den => den as u32,
+                            },
+                    )
+                    .unwrap_or(u16::MAX),
+                ),
+                None => (
+                    FrameRegion {
+                        left: 0,
+                        top: 0,
+                        width: output.width as usize,
+                        height: output.height as usize,
+                        dispose: Dispose::Keep,
+                    },
+                    png::BlendOp::Source,
+                    STATIC_DELAY,
+                ),
+            };
+
+            let colors = composite(&mut canvas, &region, &[0; 4], |i, rgba| {
+                Some(match blend_op {
+                    png::BlendOp::Source => pixels[i],
+                    png::BlendOp::Over => blend_over(pixels[i], *rgba),
+                })
+            });
+            let fn_names = to_framedots(self.formatter, &colors)
+                .into_iter()
+                .map(|line| line.concat())
+                .collect_vec();
+            frame_infos.push(self.prepare_frame(
+                self.formatter,
+                fn_names,
+                &mut fn_idx,
+                delay.unwrap_or(frame_delay),
+                clear_line,
+            ));
+        }
+
+        Animation {
+            frame_infos,
+            loops: animation_control.map_or(0, |control| control.num_plays),
+        }
+    }
+}
+
+/// Run of a file name, ordered naturally.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum NaturalRun {
+    /// Digits without leading zeros, preceded by their count, so that
+    /// numbers of any length are ordered by their value.
+    Number(usize, String),
+    Text(String),
+}
+
+/// Key ordering file names naturally, with runs of digits
+/// compared by their numeric value.
+fn natural_key(name: &str) -> Vec<NaturalRun> {
+    name.chars()
+        .chunk_by(|c| c.is_ascii_digit())
+        .into_iter()
+        .map(|(is_digit, run)| {
+            let run: String = run.collect();
+            if is_digit {
+                let digits = run.trim_start_matches('0');
+                NaturalRun::Number(digits.len(), String::from(digits))
+            } else {
+                NaturalRun::Text(run)
+            }
+        })
+        .collect()
+}
+
+/// Files matched by the glob `pattern` (e.g. `frames/*.png`), in
+/// natural order of their paths, so that matches in the same directory
+/// are ordered by file name.
+fn glob_paths(pattern: &Path) -> Vec<PathBuf> {
+    let pattern = pattern.to_string_lossy();
+    let mut paths = glob::glob(&pattern)
+        .unwrap_or_else(|e| panic!("Can't parse pattern {}: {}", pattern, e))
+        .map(|entry| entry.unwrap_or_else(|e| panic!("Can't match pattern {}: {}", pattern, e)))
+        .filter(|path| path.is_file())
+        .collect_vec();
+    paths.sort_by_cached_key(|path| natural_key(&path.to_string_lossy()));
+    if paths.is_empty() {
+        panic!("No images match pattern {}.", pattern);
+    }
+
+    paths
+}
+
+/// Image in `path` with RGBA colors, where QOI files are decoded by
+/// the `qoi` crate. Images without alpha are fully opaque.
+fn open_rgba(path: &Path) -> image::RgbaImage {
+    let decode_error =
+        |e: &dyn std::fmt::Display| -> ! { panic!("Can't decode image {}: {}", path.display(), e) };
+    if !path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("qoi"))
+    {
+        return image::open(path)
+            .unwrap_or_else(|e| decode_error(&e))
+            .to_rgba8();
+    }
+
+    let bytes = std::fs::read(path).unwrap_or_else(|e| decode_error(&e));
+    let (header, pixels) = qoi::decode_to_vec(bytes).unwrap_or_else(|e| decode_error(&e));
+    let rgba = match header.channels {
+        qoi::Channels::Rgb => pixels
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 0xff])
+            .collect(),
+        qoi::Channels::Rgba => pixels,
+    };
+
+    image::RgbaImage::from_raw(header.width, header.height, rgba).unwrap()
+}
+
+impl SequenceFrameParser<'_> {
+    /// Frames drawing the image in each of `paths`, waiting for the
+    /// delay at the same index of `delays`.
+    fn from_paths(
+        &self,
+        paths: Vec<PathBuf>,
+        delays: Vec<u16>,
+        clear_line: bool,
+    ) -> Vec<FrameInfo> {
+        let mut fn_idx: usize = 1;
+        let mut dim: Option<(u32, u32)> = None;
+        let mut frame_infos: Vec<FrameInfo> = vec![];
+        for (path, delay) in paths.into_iter().zip(delays) {
+            let image = open_rgba(&path);
+            let image = if self.width.is_some() || self.height.is_some() {
+                let w = self.width.map_or(image.width(), u32::from);
+                let h = self.height.map_or(image.height(), u32::from);
+                image::imageops::resize(&image, w, h, image::imageops::FilterType::Triangle)
+            } else {
+                image
+            };
+            trace!(
+                "frame {} {}x{}",
+                path.display(),
+                image.width(),
+                image.height()
+            );
+
+            let (w, h) = *dim.get_or_insert(image.dimensions());
+            if image.dimensions() != (w, h) {
+                panic!(
+                    "Image {} has dimensions {}x{}, expected {}x{}, consider passing `--width` and `--height` to resize images.",
+                    path.display(),
+                    image.width(),
+                    image.height(),
+                    w,
+                    h
+                );
+            }
+
+            let colors = image
+                .rows()
+                .map(|line| line.map(|rgba| rgba.0).collect_vec())
+                .collect_vec();
+            let fn_names = to_framedots(self.formatter, &colors)
+                .into_iter()
+                .map(|line| line.concat())
+                .collect_vec();
+            frame_infos.push(self.prepare_frame(
+                self.formatter,
+                fn_names,
+                &mut fn_idx,
+                delay,
+                clear_line,
+            ));
+        }
+
+        frame_infos
+    }
+}
+
+impl FrameParser for SequenceFrameParser<'_> {
+    fn bottom_up(&self) -> bool {
+        self.bottom_up
+    }
+
+    fn single_line(&self) -> bool {
+        self.single_line
+    }
+
+    fn from_input(&self, filename: &PathBuf, clear_line: bool, delay: Option<u16>) -> Animation {
+        let paths = if filename.is_dir() {
+            let mut paths = std::fs::read_dir(filename)
+                .unwrap_or_else(|e| panic!("Can't read directory {}: {}", filename.display(), e))
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.is_file())
+                .collect_vec();
+            paths.sort_by_cached_key(|path| {
+                natural_key(&path.file_name().unwrap().to_string_lossy())
+            });
+            if paths.is_empty() {
+                panic!("No images found in directory {}.", filename.display());
+            }
+            paths
+        } else {
+            glob_paths(filename)
+        };
+        let delays = vec![delay.unwrap_or(100); paths.len()];
+
+        Animation {
+            frame_infos: self.from_paths(paths, delays, clear_line),
+            loops: 0,
+        }
+    }
+}
+
+/// Presentation time in seconds of each frame logged by ffmpeg's
+/// `showinfo` filter, in `stderr`.
+fn showinfo_times(stderr: &str) -> Vec<f64> {
+    stderr
+        .lines()
+        .filter(|line| line.contains("Parsed_showinfo"))
+        .filter_map(|line| {
+            let (_, rest) = line.split_once("pts_time:")?;
+            rest.split_whitespace().next()?.parse().ok()
+        })
+        .collect()
+}
+
+/// Delays in units of 10 ms between frames presented at `times`, with
+/// the last frame waiting as long as the previous one. Delays are
+/// taken between rounded times, so that they don't drift.
+fn delays_from_times(times: &[f64]) -> Vec<u16> {
+    let ticks = times
+        .iter()
+        .map(|time| (time * 100.0).round() as i64)
+        .collect_vec();
+    let mut delays = ticks
+        .iter()
+        .tuple_windows()
+        .map(|(prev, next)| (next - prev).clamp(1, u16::MAX as i64) as u16)
+        .collect_vec();
+    delays.push(delays.last().copied().unwrap_or(STATIC_DELAY));
+
+    delays
+}
+
+impl FrameParser for VideoFrameParser<'_> {
+    fn bottom_up(&self) -> bool {
+        self.sequence.bottom_up
+    }
+
+    fn single_line(&self) -> bool {
+        self.sequence.single_line
+    }
+
+    fn from_input(&self, filename: &PathBuf, clear_line: bool, delay: Option<u16>) -> Animation {
+        // Frames are extracted to a directory unique to this process,
+        // then removed once decoded.
+        let dir = std::env::temp_dir().join(format!("backgif-video-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)
+            .unwrap_or_else(|e| panic!("Can't create directory {}: {}", dir.display(), e));
+        let filter = match self.fps {
+            Some(fps) => format!("fps={},showinfo", fps),
+            None => String::from("showinfo"),
+        };
+        let output = Command::new("ffmpeg")
+            .args(["-hide_banner", "-nostdin", "-loglevel", "info", "-i"])
+            .arg(filename)
+            .args(["-vf", &filter, "-fps_mode", "passthrough"])
+            .arg(dir.join("frame_%d.png"))
+            .output()
+            .unwrap_or_else(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => {
+                    panic!("ffmpeg not found in PATH; install it to convert video input.")
+                }
+                _ => panic!("Can't run ffmpeg: {}", e),
+            });
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            std::fs::remove_dir_all(&dir).ok();
+            panic!(
+                "Can't decode video {}, `ffmpeg` failed with {}:\n{}",
+                filename.display(),
+                output.status,
+                stderr.trim_end()
+            );
+        }
+
+        let paths = glob_paths(&dir.join("frame_*.png"));
+        let times = showinfo_times(&stderr);
+        if times.len() != paths.len() {
+            panic!(
+                "Decoded {} frames from {}, but ffmpeg logged {} timestamps.",
+                paths.len(),
+                filename.display(),
+                times.len()
+            );
+        }
+        debug!("video frames {} times {:?}", paths.len(), times);
+        let delays = match delay {
+            Some(delay) => vec![delay; paths.len()],
+            None => delays_from_times(&times),
+        };
+        let frame_infos = self.sequence.from_paths(paths, delays, clear_line);
+        std::fs::remove_dir_all(&dir).ok();
+
+        Animation {
+            frame_infos,
+            loops: 0,
+        }
+    }
+}
+
+impl FrameParser for QoiFrameParser<'_> {
+    fn bottom_up(&self) -> bool {
+        self.sequence.bottom_up
+    }
+
+    fn single_line(&self) -> bool {
+        self.sequence.single_line
+    }
+
+    fn from_input(&self, filename: &PathBuf, clear_line: bool, delay: Option<u16>) -> Animation {
+        if filename.is_dir() {
+            return self.sequence.from_input(filename, clear_line, delay);
+        }
+
+        Animation {
+            frame_infos: self.sequence.from_paths(
+                vec![filename.to_owned()],
+                vec![delay.unwrap_or(STATIC_DELAY)],
+                clear_line,
+            ),
+            loops: 0,
+        }
+    }
+}
+
+/// Columns taken by `line` on screen, skipping escape sequences.
+fn visible_width(line: &str) -> usize {
+    let mut visible = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            visible.push(c);
+            continue;
+        }
+        // Control sequences end with a byte in `@` to `~`, operating
+        // system commands with a bell or a string terminator, other
+        // escape sequences with the character after the escape.
+        match chars.next() {
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    visible.width()
+}
+
+/// `line` with tabs expanded to spaces, up to the next tab stop every
+/// 8 columns.
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::new();
+    for (i, segment) in line.split('\t').enumerate() {
+        if i > 0 {
+            expanded += &" ".repeat(8 - visible_width(&expanded) % 8);
+        }
+        expanded += segment;
+    }
+
+    expanded
+}
+
+impl FrameParser for TextFrameParser<'_> {
+    fn bottom_up(&self) -> bool {
+        self.bottom_up
+    }
+
+    fn single_line(&self) -> bool {
+        self.single_line
+    }
+
+    fn from_input(&self, filename: &PathBuf, clear_line: bool, delay: Option<u16>) -> Animation {
+        let text = std::fs::read_to_string(filename)
+            .unwrap_or_else(|e| panic!("Can't read text {}: {}", filename.display(), e));
+        let lines = text.lines().map(expand_tabs).collect_vec();
+        let blocks = lines
+            .split(|line| *line == self.delimiter)
+            .filter(|block| !block.is_empty())
+            .map(<[_]>::to_vec)
+            .collect_vec();
+        // Lines are padded to the dimensions of the largest frame, so
+        // that each frame covers all lines of the previous frame.
+        let width = blocks
+            .iter()
+            .flatten()
+            .map(|line| visible_width(line))
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let height = blocks.iter().map(Vec::len).max().unwrap_or(0);
+        debug!("text frames {} dim {}x{}", blocks.len(), width, height);
+
+        let mut fn_idx: usize = 1;
+        let mut frame_infos: Vec<FrameInfo> = vec![];
+        for block in blocks {
+            let fn_names = block
+                .iter()
+                .map(String::as_str)
+                .chain(std::iter::repeat_n("", height - block.len()))
+                .map(|line| format!("{}{}", line, " ".repeat(width - visible_width(line))))
+                .collect_vec();
+            frame_infos.push(self.prepare_frame(
+                self.formatter,
+                fn_names,
+                &mut fn_idx,
+                delay.unwrap_or(100),
+                clear_line,
+            ));
+        }
+
+        Animation {
+            frame_infos,
+            loops: 0,
+        }
+    }
+}
+
+impl FrameParser for CustomFrameParser<'_> {
+    fn bottom_up(&self) -> bool {
+        self.bottom_up
+    }
+
+    fn from_input(&self, _filename: &PathBuf, clear_line: bool, delay: Option<u16>) -> Animation {
+        let mut fn_idx: usize = 1;
+        let mut frame_infos: Vec<FrameInfo> = vec![];
+        let mut fn_names: Vec<_> = vec![];
+        for _ in 0..self.height {
+            let mut line = String::new();
+            for _ in 0..self.width {
+                line += &*self.formatter.to_framedot(None);
+            }
+            fn_names.push(line);
+        }
+        frame_infos.push(self.prepare_frame(
+            self.formatter,
+            fn_names,
+            &mut fn_idx,
+            delay.unwrap_or(100),
+            clear_line,
+        ));
+
+        Animation {
+            frame_infos,
+            loops: 0,
+        }
+    }
+}
+
+const COMPILER_ARGS: &[&str] = &[
+    "-fdiagnostics-color=always",
+    "-std=gnu99",
+    "-O0",
+    "-nostdlib",
+    "-static",
+    "-Wall",
+    "-Werror",
+];
+
+/// Header declaring the callbacks of custom input, along with helpers
+/// for writing frame dots, which custom input can include by name.
+pub const C_HEADER: &str = include_str!("../include/backgif.h");
+
+/// Name custom input includes `C_HEADER` with.
+pub const C_HEADER_NAME: &str = "backgif.h";
+
+/// User compiler flags contradicting `-nostdlib -static`, or the
+/// single output binary expected by the rest of the pipeline.
+pub const CONFLICTING_CFLAGS: &[&str] = &[
+    "-shared",
+    "-dynamic",
+    "-pie",
+    "-static-pie",
+    "-c",
+    "-S",
+    "-E",
+    "-o",
+    "/c",
+    "/LD",
+    "/link",
+];
+
+/// Default address of `.text` in linker scripts.
+const TEXT_SECTION_ADDR: u64 = 0x401000;
+
+/// Size of the memory region of `.text` in linker scripts.
+const LINKER_ROM_LENGTH: u64 = 0x200000;
+
+/// Size of the memory region of `.data` in linker scripts, which
+/// must fit the embedded binary.
+const LINKER_RAM_LENGTH: u64 = 0x100000;
+
+/// Placeholder address for `.symtab` offsets embedded in `.data` section.
+const PLACEHOLDER_SYMTAB_ADDR: u64 = 0x01020304;
+
+/// Placeholder address for `.debug_str` offsets embedded in `.data` section.
+const PLACEHOLDER_DEBUGSTR_ADDR: u64 = 0x05060708;
+
+const CL_COMPILER_ARGS: &[&str] = &["/nologo", "/Od", "/GS-", "/W3", "/WX", "-fuse-ld=lld"];
+
+/// Linker arguments passed to `lld-link`, which only emits a COFF
+/// symbol table when explicitly requested with `/debug:symtab`.
+/// Identical code folding is disabled, otherwise the empty
+/// functions at the bottom of each frame would share an address.
+const CL_LINKER_ARGS: &[&str] = &[
+    "/subsystem:console",
+    "/nodefaultlib",
+    "/dynamicbase:no",
+    "/incremental:no",
+    "/opt:noicf",
+    "/debug:symtab",
+];
+
+/// Size of each ELF relocation entry with addend (`Elf64_Rela`).
+const ELF64_RELA_SIZE: u64 = 24;
+
+/// Size of each COFF symbol table entry (`IMAGE_SYMBOL`).
+const COFF_SYMBOL_SIZE: u64 = 18;
+
+/// Complex type of COFF function symbols (`IMAGE_SYM_DTYPE_FUNCTION << 4`).
+const COFF_SYMBOL_TYPE_FUNCTION: u16 = 0x20;
+
+/// Functions in the COFF symbol table of PE `contents`, with
+/// `symtab_len` entries at `symtab_offs` followed by the string table,
+/// where sections are loaded at `section_addrs` relative to `imagebase`.
+/// Returns `None` if the symbol table is truncated or malformed.
+pub fn parse_coff_symbols(
+    contents: &[u8],
+    symtab_offs: u64,
+    symtab_len: u64,
+    section_addrs: &[u64],
+    imagebase: u64,
+) -> Option<HashMap<String, SymbolInfo>> {
+    let mut name_to_info = HashMap::new();
+    let strtab_offs = symtab_offs.checked_add(symtab_len.checked_mul(COFF_SYMBOL_SIZE)?)?;
+    let read_u32 = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap()) as u64;
+
+    // Each `IMAGE_SYMBOL` entry is laid out as:
+    //
+    // 0x00 Name (inline, or zero + `.strtab` relative offset)
+    // 0x08 Value (offset in section)
+    // 0x0c SectionNumber (1-based)
+    // 0x0e Type
+    // 0x10 StorageClass
+    // 0x11 NumberOfAuxSymbols
+    let mut i = 0;
+    while i < symtab_len {
+        let entry_offs = (symtab_offs + i * COFF_SYMBOL_SIZE) as usize;
+        let entry = contents.get(entry_offs..entry_offs + COFF_SYMBOL_SIZE as usize)?;
+        i += 1 + entry[0x11] as u64;
+
+        let section_number = i16::from_le_bytes([entry[0x0c], entry[0x0d]]);
+        let symbol_type = u16::from_le_bytes([entry[0x0e], entry[0x0f]]);
+        if symbol_type != COFF_SYMBOL_TYPE_FUNCTION || section_number < 1 {
+            continue;
+        }
+
+        // Temporary names are always longer than 8 bytes,
+        // so inline names can be skipped.
+        if entry[..4] != [0; 4] {
+            continue;
+        }
+        let offs = strtab_offs + read_u32(&entry[0x04..0x08]);
+        let name_bytes = contents.get(offs as usize..)?;
+        let name_len = memchr::memchr(0, name_bytes)?;
+        let name = str::from_utf8(&name_bytes[..name_len]).ok()?.to_string();
+
+        let addr = imagebase
+            + section_addrs.get(section_number as usize - 1)?
+            + read_u32(&entry[0x08..0x0c]);
+        trace!("coff i={} @ {:08x} name={}", i, offs, &name);
+
+        name_to_info.insert(
+            name,
+            SymbolInfo {
+                addr,
+                offs: vec![offs],
+            },
+        );
+    }
+
+    Some(name_to_info)
+}
+
+/// Frames parsed from input, along with how they are played.
+#[derive(Debug)]
+pub struct Animation {
+    pub frame_infos: Vec<FrameInfo>,
+
+    /// Number of times frames are played, or 0 to loop forever.
+    pub loops: u32,
+}
+
+#[derive(Debug)]
+pub struct FrameInfo {
+    delay: u16,
+    first_name: String,
+    last_name: String,
+    tmp_names: Vec<String>,
+    tmp_to_frameline: HashMap<String, String>,
+
+    /// Colors drawn by each frame dot, if kept by the parser.
+    colors: Option<Vec<Vec<[u8; 4]>>>,
+}
+
+impl FrameInfo {
+    /// Frame lines in the order they are printed in a backtrace,
+    /// starting from the innermost function.
+    pub fn to_lines(&self) -> Vec<&String> {
+        self.tmp_names
+            .iter()
+            .rev()
+            .map(|name| self.tmp_to_frameline.get(name).unwrap())
+            .collect()
+    }
+}
+
+/// Rename functions so that functions drawing the same frame line
+/// and calling the same function are shared by all frames, which
+/// happens when frames repeat their innermost lines.
+fn share_framelines(frame_infos: &mut [FrameInfo]) {
+    let fn_count = frame_infos
+        .iter()
+        .map(|frame_info| frame_info.tmp_names.len())
+        .sum::<usize>();
+    let mut shared_names: HashMap<(String, Option<String>), String> = HashMap::new();
+    for frame_info in frame_infos.iter_mut() {
+        let mut tmp_to_frameline = HashMap::new();
+        let mut next_name = None;
+        for tmp_name in frame_info.tmp_names.iter_mut().rev() {
+            let frameline = frame_info.tmp_to_frameline.remove(tmp_name).unwrap();
+            // Names are padded to the length of their frame line,
+            // so shared names still fit the patched frame line.
+            *tmp_name = shared_names
+                .entry((frameline.to_owned(), next_name))
+                .or_insert(tmp_name.to_owned())
+                .to_owned();
+            tmp_to_frameline.insert(tmp_name.to_owned(), frameline);
+            next_name = Some(tmp_name.to_owned());
+        }
+        frame_info.tmp_to_frameline = tmp_to_frameline;
+        frame_info.first_name = frame_info.tmp_names.first().unwrap().to_owned();
+        frame_info.last_name = frame_info.tmp_names.last().unwrap().to_owned();
+    }
+    debug!(
+        "{} frame line functions, {} after sharing.",
+        fn_count,
+        shared_names.len()
+    );
+}
+
+/// Write C functions of each frame to `out`, calling the function of
+/// each next frame line. Shared functions are only defined once, and
+/// callees are defined before their callers.
+fn write_frameline_fns(out: &mut dyn Write, frame_infos: &[FrameInfo]) -> std::io::Result<()> {
+    let mut defined_names = HashSet::new();
+    for (i, n) in frame_infos.iter().enumerate() {
+        // Frames are separated by an empty line.
+        if i > 0 {
+            out.write_all(b"\n")?;
+        }
+        let mut next_name = None;
+        for name in n.tmp_names.iter().rev() {
+            if defined_names.insert(name) {
+                write!(
+                    out,
+                    r#"
+void {}() {{
+    {};
+}}
+"#,
+                    name,
+                    next_name.map_or(String::from("return"), |next| format!("{}()", next))
+                )?;
+            }
+            next_name = Some(name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Frame lines of `frame_info` as printed in a backtrace, followed
+/// by sequences restoring the terminal, so that it can be printed on its own.
+fn to_snippet(frame_info: &FrameInfo) -> String {
+    // \x1b[0m => Reset character attributes, revealing text hidden by the last frame line;
+    // \x1b[?25h => Show cursor (DECTCEM);
+    format!(
+        "{}\n\x1b[0m\x1b[?25h",
+        frame_info.to_lines().into_iter().join("\n")
+    )
+}
+
+/// Estimated size in bytes of the code of each function drawing a
+/// frame line, which calls the next function with placeholder
+/// addresses.
+const FN_SIZE_ESTIMATE: u64 = 32;
+
+/// Number of functions drawing the frame lines of `frame_infos`, each
+/// defining a symbol, along with an estimate of the size in bytes of
+/// binaries with `bits` word size defining them, mostly taken by
+/// symbol names and their `.symtab` entries.
+pub fn estimate_symbols(frame_infos: &[FrameInfo], bits: u32) -> (usize, u64) {
+    let sym_size = if bits == 32 { 16 } else { 24 };
+    frame_infos
+        .iter()
+        .flat_map(|frame_info| {
+            frame_info
+                .tmp_names
+                .iter()
+                .map(|name| (name, &frame_info.tmp_to_frameline[name]))
+        })
+        .unique_by(|(name, _)| *name)
+        .fold((0, 0), |(count, size), (_, frameline)| {
+            (
+                count + 1,
+                size + frameline.len() as u64 + 1 + sym_size + FN_SIZE_ESTIMATE,
+            )
+        })
+}
+
+/// Write each frame's lines to a file in `dir`, named by the
+/// zero-padded frame index, so that it can be printed on its own.
+pub fn write_snippets(frame_infos: &[FrameInfo], dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let width = frame_infos.len().to_string().len();
+    for (i, frame_info) in frame_infos.iter().enumerate() {
+        std::fs::write(
+            dir.join(format!("frame_{:0width$}.txt", i)),
+            to_snippet(frame_info),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Print each frame's lines to stdout, waiting for its delay before
+/// the next frame, as the debugger would when hitting breakpoints.
+/// Frames are played `loops` times, or until interrupted if 0.
+pub fn preview(frame_infos: &[FrameInfo], loops: u32) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    let mut played = 0;
+    while loops == 0 || played < loops {
+        for frame_info in frame_infos {
+            // Terminal is restored after each frame, since interrupting
+            // the preview most likely happens while waiting.
+            write!(stdout, "{}", to_snippet(frame_info))?;
+            stdout.flush()?;
+            std::thread::sleep(std::time::Duration::from_millis(
+                frame_info.delay as u64 * 10,
+            ));
+        }
+        played += 1;
+    }
+
+    Ok(())
+}
+
+/// Write an asciinema v2 recording to `path` of a `columns`x`rows`
+/// terminal, printing each frame's lines as the debugger would when
+/// hitting breakpoints, with timestamps advanced by each frame's delay.
+/// Frames are played `loops` times, or once if 0, since recordings
+/// can't loop forever.
+///
+/// See: <https://docs.asciinema.org/manual/asciicast/v2/>
+pub fn write_cast(
+    frame_infos: &[FrameInfo],
+    path: &Path,
+    loops: u32,
+    (columns, rows): (u16, u16),
+) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::new(File::create(path)?);
+    writeln!(
+        file,
+        "{}",
+        serde_json::json!({"version": 2, "width": columns, "height": rows})
+    )?;
+    let mut elapsed: u64 = 0;
+    for _ in 0..loops.max(1) {
+        for frame_info in frame_infos {
+            // Terminal output needs carriage returns, which the
+            // debugger's terminal would add to line feeds.
+            let lines = format!("{}\r\n", frame_info.to_lines().into_iter().join("\r\n"));
+            writeln!(
+                file,
+                "{}",
+                serde_json::json!([elapsed as f64 / 100.0, "o", lines])
+            )?;
+            elapsed += frame_info.delay as u64;
+        }
+    }
+    // Terminal is restored once the last frame's delay elapsed.
+    // \x1b[0m => Reset character attributes, revealing text hidden by the last frame line;
+    // \x1b[?25h => Show cursor (DECTCEM);
+    writeln!(
+        file,
+        "{}",
+        serde_json::json!([elapsed as f64 / 100.0, "o", "\x1b[0m\x1b[?25h"])
+    )?;
+
+    file.flush()
+}
+
+/// Write a bash script to `path` printing each frame's lines as the
+/// debugger would when hitting breakpoints, sleeping for its delay
+/// before the next frame. Frames are played `loops` times, or until
+/// interrupted if 0.
+pub fn write_player_script(
+    frame_infos: &[FrameInfo],
+    path: &Path,
+    loops: u32,
+) -> std::io::Result<()> {
+    // Frame lines are printed verbatim as a `printf` argument,
+    // single-quoted, so only single quotes need escaping.
+    let frames = frame_infos
+        .iter()
+        .map(|frame_info| {
+            let lines = frame_info.to_lines().into_iter().join("\n");
+            format!(
+                "    printf '%s\\n' '{}'\n    sleep {}\n",
+                lines.replace('\'', r"'\''"),
+                frame_info.delay as f64 / 100.0
+            )
+        })
+        .collect::<String>();
+    let repeat = match loops {
+        0 => String::from("while :"),
+        loops => format!("for ((i = 0; i < {}; i++))", loops),
+    };
+
+    // \x1b[0m => Reset character attributes, revealing text hidden by the last frame line;
+    // \x1b[?25h => Show cursor (DECTCEM);
+    std::fs::write(
+        path,
+        format!(
+            r#"#!/usr/bin/env bash
+restore() {{
+    printf '\033[0m\033[?25h\n'
+}}
+trap 'restore; exit 130' INT
+{}; do
+{}done
+restore
+"#,
+            repeat, frames
+        ),
+    )
+}
+
+/// Side in pixels of the square drawn for each frame dot in
+/// verification GIFs.
+const VERIFY_DOT_PIXELS: u16 = 8;
+
+/// Write a GIF to `path` drawing the colors kept in each frame's info,
+/// with a square of pixels per frame dot, played `loops` times, or
+/// forever if 0. Frames without kept colors are skipped.
+pub fn write_verify_gif(frame_infos: &[FrameInfo], path: &Path, loops: u32) -> std::io::Result<()> {
+    let frames = frame_infos
+        .iter()
+        .filter_map(|frame_info| Some((frame_info.colors.as_ref()?, frame_info.delay)))
+        .collect_vec();
+    let Some((first, _)) = frames.first() else {
+        return Err(std::io::Error::other("no frame kept its colors"));
+    };
+    let dimension = |dots: usize| {
+        u16::try_from(dots)
+            .ok()
+            .and_then(|dots| dots.checked_mul(VERIFY_DOT_PIXELS))
+            .ok_or_else(|| std::io::Error::other("frames too large for a GIF"))
+    };
+    let (w, h) = (dimension(first[0].len())?, dimension(first.len())?);
+
+    let mut encoder =
+        gif::Encoder::new(File::create(path)?, w, h, &[]).map_err(std::io::Error::other)?;
+    encoder
+        .set_repeat(match loops {
+            0 => gif::Repeat::Infinite,
+            loops => gif::Repeat::Finite(loops.min(u16::MAX as u32) as u16),
+        })
+        .map_err(std::io::Error::other)?;
+    let dot_pixels = VERIFY_DOT_PIXELS as usize;
+    for (colors, delay) in frames {
+        let mut buffer = colors
+            .iter()
+            .flat_map(|row| {
+                std::iter::repeat_n(row, dot_pixels).flat_map(move |row| {
+                    row.iter()
+                        .flat_map(move |rgba| std::iter::repeat_n(rgba, dot_pixels))
+                        .flatten()
+                        .copied()
+                })
+            })
+            .collect_vec();
+        let mut frame = gif::Frame::from_rgba_speed(w, h, &mut buffer, 10);
+        frame.delay = delay;
+        frame.dispose = gif::DisposalMethod::Background;
+        encoder.write_frame(&frame).map_err(std::io::Error::other)?;
+    }
+
+    Ok(())
+}
+
+/// Bytes of `frameline` replacing temporary name `tmp_name`, padded
+/// with null bytes to its length. Temporary names are at least 9 bytes
+/// long, so their tail would otherwise be printed after short frame lines.
+fn to_patched_name(frameline: &str, tmp_name: &str) -> Vec<u8> {
+    let mut bytes = frameline.as_bytes().to_vec();
+    bytes.resize(bytes.len().max(tmp_name.len()), 0);
+    bytes
+}
+
+/// Errors decoding input, or building or reading a compiled binary.
+#[derive(Debug)]
+pub enum ConvError {
+    /// Input decoded to no frames, e.g. if it's truncated.
+    NoFrames { path: PathBuf },
+
+    /// Compiler or linker failed, with a message including its stderr.
+    Build { message: String },
+
+    /// File can't be opened, read, or written.
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    /// Binary isn't in the format produced for the debugger.
+    UnexpectedFormat {
+        path: PathBuf,
+        expected: &'static str,
+    },
+
+    /// Binary is missing a section required to patch symbols.
+    MissingSection {
+        path: PathBuf,
+        section: &'static str,
+    },
+
+    /// Option can't be used, or can't be combined with other options.
+    InvalidOption { message: String },
+
+    /// Frame line isn't terminal-safe text.
+    InvalidFrameLine {
+        frame: usize,
+        line: usize,
+        message: String,
+    },
+
+    /// Number of selected frames differs from `--expect-frames`.
+    UnexpectedFrameCount { expected: usize, actual: usize },
+
+    /// Debugger script isn't valid Python, with the line and column
+    /// of the syntax error if it was reported.
+    InvalidScript {
+        path: PathBuf,
+        location: Option<(usize, usize)>,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for ConvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConvError::NoFrames { path } => {
+                write!(f, "No frames decoded from {}.", path.display())
+            }
+            ConvError::Build { message } => write!(f, "Can't build binary: {}", message),
+            ConvError::Io { path, source } => {
+                write!(f, "Can't access {}: {}.", path.display(), source)
+            }
+            ConvError::UnexpectedFormat { path, expected } => {
+                write!(
+                    f,
+                    "Can't parse {}, expected {} file.",
+                    path.display(),
+                    expected
+                )
+            }
+            ConvError::MissingSection { path, section } => write!(
+                f,
+                "Missing {} in {}, was it stripped or linked with other options?",
+                section,
+                path.display()
+            ),
+            ConvError::InvalidOption { message } => write!(f, "{}", message),
+            ConvError::InvalidFrameLine {
+                frame,
+                line,
+                message,
+            } => write!(
+                f,
+                "Invalid frame line {} in frame {}: {}.",
+                line, frame, message
+            ),
+            ConvError::UnexpectedFrameCount { expected, actual } => write!(
+                f,
+                "Expected {} frames, but {} frames were selected from input.",
+                expected, actual
+            ),
+            ConvError::InvalidScript {
+                path,
+                location: Some((line, column)),
+                message,
+            } => write!(
+                f,
+                "Invalid Python syntax in {} at line {}, column {}: {}.",
+                path.display(),
+                line,
+                column,
+                message
+            ),
+            ConvError::InvalidScript {
+                path,
+                location: None,
+                message,
+            } => write!(
+                f,
+                "Can't check Python syntax of {}: {}",
+                path.display(),
+                message
+            ),
+        }
+    }
+}
+
+impl Error for ConvError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConvError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SymbolInfo {
+    pub addr: u64,
+    pub offs: Vec<u64>,
+}
+
+#[derive(Debug)]
+pub struct BinInfo {
+    pub build_id_desc_offs: u64,
+    pub build_id_desc: Vec<u8>,
+    pub name_to_info: HashMap<String, SymbolInfo>,
+    pub section_offs: HashMap<String, u64>,
+    pub size: u64,
+
+    /// Word size of the binary, from its file class.
+    pub bits: u32,
+}
+
+pub trait FrameConverter {
+    /// `.data` address defined in linker script.
+    fn data_section_addr(&self) -> u64 {
+        0
+    }
+
+    /// `.text` address defined in linker script.
+    fn text_section_addr(&self) -> u64 {
+        TEXT_SECTION_ADDR
+    }
+
+    /// Whether the memory regions of `.text` and `.data` in linker
+    /// scripts overlap, including the page mapping headers before `.text`.
+    fn sections_overlap(&self) -> bool {
+        let text_start = self.text_section_addr() - 0x1000;
+        let data_start = self.data_section_addr();
+        text_start < data_start + LINKER_RAM_LENGTH && data_start < text_start + LINKER_ROM_LENGTH
+    }
+
+    fn artifacts(&self) -> &Artifacts;
+
+    /// Word size of compiled binaries, either 32 (i386) or 64 (x86-64).
+    fn bits(&self) -> u32;
+
+    /// Output binary name.
+    fn bin_name(&self) -> String {
+        self.artifacts().name(".out")
+    }
+
+    /// Names of intermediate files written while building the output
+    /// binary, which aren't needed to run the debugger script.
+    fn temp_names(&self) -> Vec<String> {
+        vec![self.artifacts().name(".c")]
+    }
+
+    /// Write a linker script placing `.text` at `text_section_addr()`,
+    /// returning its name. With `embedded_bin`, that binary is also
+    /// embedded as a writable `.data` section at `data_section_addr()`,
+    /// so that debuggers can reload its symbols from memory.
+    fn write_linker_script(&self, embedded_bin: Option<&str>) -> std::io::Result<String> {
+        let text_addr = self.text_section_addr();
+        // Headers are mapped on the page before `.text`.
+        let rom = format!(
+            "rom (rx)  : ORIGIN = 0x{:x}, LENGTH = 0x{:x}",
+            text_addr - 0x1000,
+            LINKER_ROM_LENGTH
+        );
+        let discard = "/DISCARD/ : { *(.comment) *(.eh_frame*) *(.gnu*) }";
+        let format = if self.bits() == 32 {
+            "elf32-i386"
+        } else {
+            "elf64-x86-64"
+        };
+        let (name, script) = match embedded_bin {
+            None => (
+                self.artifacts().name(".ld"),
+                format!(
+                    r#"OUTPUT_FORMAT({format})
+
+MEMORY
+{{
+    {rom}
+}}
+
+SECTIONS
+{{
+    .text 0x{text_addr:x} : {{ *(.text) }} > rom
+
+    {discard}
+}}
+"#
+                ),
+            ),
+            Some(bin) => {
+                let data_addr = self.data_section_addr();
+                let ram = format!(
+                    "ram (rwx) : ORIGIN = 0x{:x}, LENGTH = 0x{:x}",
+                    data_addr, LINKER_RAM_LENGTH
+                );
+                (
+                    self.artifacts().name(&format!("2.0x{:04x}.ld", data_addr)),
+                    format!(
+                        r#"TARGET(binary)
+INPUT(./{bin})
+OUTPUT_FORMAT({format})
+
+MEMORY
+{{
+    {ram}
+    {rom}
+}}
+
+SECTIONS
+{{
+    .data              : {{ ./{bin} }} > ram
+    .note.gnu.build-id : {{ *(.note.gnu.build-id) }} > ram
+    .text 0x{text_addr:x}     : {{ *(.text) }} > rom
+
+    {discard}
+}}
+"#
+                    ),
+                )
+            }
+        };
+        std::fs::write(self.artifacts().dir.join(&name), script)?;
+
+        Ok(name)
+    }
+
+    /// Output debugger script name.
+    fn script_name(&self) -> String;
+
+    /// Write debugger `script` to the artifacts directory.
+    fn write_script(&self, script: &str) -> Result<(), ConvError> {
+        let path = self.artifacts().dir.join(self.script_name());
+        std::fs::write(&path, script).map_err(|source| ConvError::Io { path, source })
+    }
+
+    fn parser(&self) -> &dyn FrameParser;
+
+    /// Whether frames can share functions drawing the same frame lines.
+    fn shares_framelines(&self) -> bool {
+        true
+    }
+
+    /// Whether consecutive frames with the same lines are merged
+    /// into a single breakpoint.
+    fn merges_frames(&self) -> bool {
+        false
+    }
+
+    /// Convert function names to temporary names and frame lines.
+    fn parse_input(
+        &self,
+        filename: &PathBuf,
+        clear_line: bool,
+        delay: Option<u16>,
+    ) -> Result<Animation, ConvError> {
+        let mut animation = self.parser().from_input(filename, clear_line, delay);
+        if animation.frame_infos.is_empty() {
+            return Err(ConvError::NoFrames {
+                path: filename.to_owned(),
+            });
+        }
+        for (frame, frame_info) in animation.frame_infos.iter().enumerate() {
+            for (line, tmp_name) in frame_info.tmp_names.iter().enumerate() {
+                validate_frameline(&frame_info.tmp_to_frameline[tmp_name]).map_err(|message| {
+                    ConvError::InvalidFrameLine {
+                        frame,
+                        line,
+                        message,
+                    }
+                })?;
+            }
+        }
+        if self.shares_framelines() {
+            share_framelines(&mut animation.frame_infos);
+        }
+
+        Ok(animation)
+    }
+
+    /// Write C source code with nested function calls for each
+    /// frame to render to `out`, as it's generated, so that large
+    /// animations aren't held in memory as a single string. Functions
+    /// prototypes use the generated temporary names.
+    fn write_src(
+        &self,
+        out: &mut dyn Write,
+        frame_infos: &[FrameInfo],
+        start_tmp_name: &str,
+        _has_debug_info: bool,
+    ) -> std::io::Result<()> {
+        out.write_all(b"\n")?;
+        write_frameline_fns(out, frame_infos)?;
+        write!(
+            out,
+            r#"
+
+void {}() {{
+loop:
+    {}
+    goto loop;
+}}"#,
+            start_tmp_name,
+            // Merged frames are drawn by the breakpoint of the
+            // previous frame, so their functions aren't called.
+            frame_infos
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !is_merged_frame(frame_infos, *i, self.merges_frames()))
+                .map(|(_, n)| format!("{}();", n.first_name))
+                .format("\n    ")
+        )
+    }
+
+    /// Write the generated C source code to the `.c` artifact.
+    fn write_src_file(
+        &self,
+        frame_infos: &[FrameInfo],
+        start_tmp_name: &str,
+        has_debug_info: bool,
+    ) -> std::io::Result<()> {
+        let mut out = std::io::BufWriter::new(File::create(self.artifacts().path(".c"))?);
+        self.write_src(&mut out, frame_infos, start_tmp_name, has_debug_info)?;
+        out.flush()
+    }
+
+    /// Compile the generated C source code, optionally including
+    /// debug info sections. Extra `cflags` follow the fixed arguments.
+    fn compile(
+        &self,
+        frame_infos: &[FrameInfo],
+        compiler: &str,
+        cflags: &[&str],
+        start_tmp_name: &str,
+        include_debug_info: bool,
+        verbosity: Verbosity,
+    ) -> Result<(), Box<dyn Error>> {
+        let artifacts = self.artifacts();
+        self.write_src_file(frame_infos, start_tmp_name, include_debug_info)?;
+        spawn_tool(
+            Command::new(compiler).current_dir(&artifacts.dir).args(
+                include_debug_info
+                    // Relocations locate names in `.debug_str`.
+                    .then_some(&["-g", "-Wl,--emit-relocs"])
+                    .into_iter()
+                    .flatten()
+                    .chain(COMPILER_ARGS)
+                    .chain(cflags)
+                    .chain((self.bits() == 32).then_some(&"-m32"))
+                    .chain(&[
+                        "-Wl,--build-id",
+                        &format!("-Wl,--entry={}", start_tmp_name),
+                        "-o",
+                        &self.bin_name(),
+                        &artifacts.name(".c"),
+                    ]),
+            ),
+            "--compiler-path",
+            verbosity,
+        )?;
+
+        Ok(())
+    }
+
+    /// File offset and contents of the build id descriptor, if any,
+    /// or `None` if the note is malformed.
+    fn parse_build_id(&self, build_id: Option<Section>) -> Option<(u64, Vec<u8>)> {
+        let Some(section) = build_id else {
+            return Some((0, vec![]));
+        };
+        if section.get_type() != lief::elf::section::Type::NOTE {
+            debug!("Unexpected type '{:?}' for build id", section.get_type());
+            return None;
+        }
+
+        let content = section.content();
+        let field = |offs: usize| {
+            content
+                .get(offs..offs + 4)
+                .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+        };
+        let name_len = field(0)?;
+        let desc_len = field(4)?;
+        let desc_offs = 4 + 4 + 4 + name_len; // Skip `type`.
+        let desc = content.get(desc_offs..desc_offs.checked_add(desc_len)?)?;
+
+        Some((section.file_offset() + desc_offs as u64, desc.to_vec()))
+    }
+
+    /// Offsets of names in `.debug_str`, from relocations of
+    /// `.debug_info` and `.debug_types` that refer to its strings,
+    /// which are kept when linking with `--emit-relocs`.
+    fn parse_debug_str(&self, elf: &lief::elf::Binary) -> HashMap<String, u64> {
+        let Some(debug_str) = elf.section_by_name(".debug_str") else {
+            return HashMap::new();
+        };
+        let relocs = [".rela.debug_info", ".rela.debug_types"]
+            .iter()
+            .filter_map(|name| elf.section_by_name(name))
+            .filter(|section| section.entry_size() == ELF64_RELA_SIZE)
+            .collect_vec();
+        if relocs.is_empty() {
+            return self.scan_debug_str(debug_str);
+        }
+
+        let symbols = elf.symtab_symbols().collect_vec();
+        let section_offs = debug_str.file_offset();
+        let haystack = debug_str.content();
+        let mut name_to_debug_offs = HashMap::new();
+        for section in &relocs {
+            // Each `Elf64_Rela` entry is laid out as:
+            //
+            // 0x00 r_offset (in `.debug_info`)
+            // 0x08 r_info (symbol index << 32 | type)
+            // 0x10 r_addend
+            for entry in section.content().chunks_exact(ELF64_RELA_SIZE as usize) {
+                let sym_idx = u64::from_le_bytes(entry[0x08..0x10].try_into().unwrap()) >> 32;
+                let is_debug_str = symbols
+                    .get(sym_idx as usize)
+                    .and_then(|sym| sym.section())
+                    .is_some_and(|section| section.name() == ".debug_str");
+                if !is_debug_str {
+                    continue;
+                }
+
+                // Section symbols of sections that aren't loaded
+                // have a zero value, so the addend is the offset.
+                let offs = i64::from_le_bytes(entry[0x10..0x18].try_into().unwrap()) as usize;
+                let Some(name_len) = haystack
+                    .get(offs..)
+                    .and_then(|haystack| memchr::memchr(0, haystack))
+                else {
+                    continue;
+                };
+                let name = String::from_utf8_lossy(&haystack[offs..offs + name_len]).to_string();
+                name_to_debug_offs.insert(name, section_offs + offs as u64);
+            }
+        }
+
+        name_to_debug_offs
+    }
+
+    /// Offsets of names in `.debug_str`, for binaries linked without
+    /// relocations, assuming strings are separated by a single null
+    /// byte, although strings may also be suffixes of other strings.
+    fn scan_debug_str(&self, debug_str: Section) -> HashMap<String, u64> {
+        let mut name_to_debug_offs = HashMap::new();
+        let section_offs = debug_str.file_offset();
+        let mut prev_i = 0;
+        let haystack = debug_str.content();
+        for i in memmem::find_iter(haystack, b"\x00") {
+            // Names of patched symbols are ASCII, so other strings
+            // only need to be distinct.
+            let name = String::from_utf8_lossy(&haystack[prev_i as usize..i]).to_string();
+            name_to_debug_offs.insert(name, section_offs + prev_i);
+            prev_i = i as u64 + 1;
+        }
+
+        name_to_debug_offs
+    }
+
+    fn parse_bin(&self, path: &Path) -> Result<BinInfo, ConvError> {
+        let mut name_to_info = HashMap::new();
+        let io_error = |source| ConvError::Io {
+            path: path.to_owned(),
+            source,
+        };
+        let missing_section = |section| ConvError::MissingSection {
+            path: path.to_owned(),
+            section,
+        };
+        let unexpected_format = || ConvError::UnexpectedFormat {
+            path: path.to_owned(),
+            expected: "an ELF",
+        };
         let mut file = std::fs::OpenOptions::new()
             .read(true)
             .write(true)
-            .open(file)
-            .expect("Can't open output file");
+            .open(path)
+            .map_err(io_error)?;
         match lief::Binary::from(&mut file) {
             Some(lief::Binary::ELF(elf)) => {
                 let section_offs = [".data", ".strtab", ".text"]
@@ -427,16 +2706,21 @@ loop:
                     })
                     .collect();
 
-                let symtab = elf.section_by_name(".symtab").unwrap();
+                let symtab = elf
+                    .section_by_name(".symtab")
+                    .ok_or_else(|| missing_section("section `.symtab`"))?;
                 let symtab_content = symtab.content();
 
-                let strtab = elf.section_by_name(".strtab").unwrap();
+                let strtab = elf
+                    .section_by_name(".strtab")
+                    .ok_or_else(|| missing_section("section `.strtab`"))?;
                 let strtab_offs = strtab.file_offset();
 
-                let (build_id_desc_offs, build_id_desc) =
-                    self.parse_build_id(&mut file, elf.section_by_name(".note.gnu.build-id"));
+                let (build_id_desc_offs, build_id_desc) = self
+                    .parse_build_id(elf.section_by_name(".note.gnu.build-id"))
+                    .ok_or_else(unexpected_format)?;
 
-                let name_to_debug_offs = self.parse_debug_str(elf.section_by_name(".debug_str"));
+                let name_to_debug_offs = self.parse_debug_str(&elf);
 
                 for (i, sym) in elf.symtab_symbols().enumerate() {
                     if sym.get_type() != lief::elf::symbol::Type::FUNC {
@@ -446,15 +2730,18 @@ loop:
                     // Symbol name file offset is not provided,
                     // we have to parse it manually from
                     // relative offset in `.symtab` entry, then
-                    // read bytes from `.strtab`.
+                    // read bytes from `.strtab`. Entries are 16 bytes
+                    // (`Elf32_Sym`) or 24 bytes (`Elf64_Sym`) long, both
+                    // starting with `st_name`.
                     let strtab_sym_offs = symtab.entry_size() as usize * i;
-                    let mut buf4 = [0; 4];
-                    buf4.copy_from_slice(&symtab_content[strtab_sym_offs..strtab_sym_offs + 4]);
-                    let offs = strtab_offs + u32::from_le_bytes(buf4) as u64;
+                    let st_name = symtab_content
+                        .get(strtab_sym_offs..strtab_sym_offs + 4)
+                        .ok_or_else(unexpected_format)?;
+                    let offs = strtab_offs + u32::from_le_bytes(st_name.try_into().unwrap()) as u64;
 
                     let addr = sym.value();
                     let name = sym.demangled_name();
-                    debug!("symtab i={} @ {:08x} name={}", i, offs, &name);
+                    trace!("symtab i={} @ {:08x} name={}", i, offs, &name);
 
                     let mut all_offs = vec![offs];
                     name_to_debug_offs
@@ -469,50 +2756,67 @@ loop:
                     );
                 }
 
-                let size = file
-                    .seek(std::io::SeekFrom::End(0))
-                    .expect("Can't seek to end");
+                let size = file.seek(std::io::SeekFrom::End(0)).map_err(io_error)?;
 
-                BinInfo {
+                Ok(BinInfo {
                     build_id_desc_offs,
                     build_id_desc,
                     name_to_info,
                     section_offs,
                     size,
-                }
+                    bits: match elf.header().identity_class() {
+                        lief::elf::header::Class::ELF32 => 32,
+                        _ => 64,
+                    },
+                })
             }
-            _ => panic!("Can't parse bin."),
+            _ => Err(unexpected_format()),
         }
     }
 
+    /// Patch temporary names in the symbol tables of binary `path`.
     fn patch_syms(
         &self,
+        path: &Path,
         name_to_info: &HashMap<String, SymbolInfo>,
-        frame_infos: &Vec<FrameInfo>,
+        frame_infos: &[FrameInfo],
         start_tmp_name: &str,
         start_name: &str,
+        verbosity: Verbosity,
     ) {
         let mut file = std::fs::OpenOptions::new()
             .read(true)
             .write(true)
-            .open("a.out")
+            .open(path)
             .expect("Can't open bin");
 
-        for frame_info in frame_infos {
-            for name in &frame_info.tmp_names {
-                let frameline = frame_info.tmp_to_frameline.get(name).unwrap();
-                name_to_info
-                    .get(name)
-                    .unwrap()
-                    .offs
+        // Shared functions are patched once.
+        let names = frame_infos
+            .iter()
+            .flat_map(|frame_info| {
+                frame_info
+                    .tmp_names
                     .iter()
-                    .for_each(|offs| {
-                        file.seek(std::io::SeekFrom::Start(*offs))
-                            .expect(&*format!("Can't seek to 0x{:08x}", *offs));
-                        file.write(frameline.as_bytes()).expect("Can't write bin");
-                    });
-            }
+                    .map(|name| (name, frame_info.tmp_to_frameline.get(name).unwrap()))
+            })
+            .unique_by(|(name, _)| *name)
+            .collect_vec();
+        let progress = Progress::new("Patching symbols", Some(names.len()), verbosity);
+        for (name, frameline) in names {
+            name_to_info
+                .get(name)
+                .unwrap()
+                .offs
+                .iter()
+                .for_each(|offs| {
+                    file.seek(std::io::SeekFrom::Start(*offs))
+                        .expect(&*format!("Can't seek to 0x{:08x}", *offs));
+                    file.write(&to_patched_name(frameline, name))
+                        .expect("Can't write bin");
+                });
+            progress.inc();
         }
+        drop(progress);
 
         name_to_info
             .get(start_tmp_name)
@@ -522,7 +2826,8 @@ loop:
             .for_each(|offs| {
                 file.seek(std::io::SeekFrom::Start(*offs))
                     .expect(&*format!("Can't seek to 0x{:08x}", *offs));
-                file.write(start_name.as_bytes()).expect("Can't write bin");
+                file.write(&to_patched_name(start_name, start_tmp_name))
+                    .expect("Can't write bin");
             });
     }
 
@@ -534,11 +2839,22 @@ loop:
         start_tmp_name: &str,
         start_name: &str,
         _build_id_offs: u64,
-    ) {
-        self.patch_syms(name_to_info, frame_infos, start_tmp_name, start_name);
+        verbosity: Verbosity,
+    ) -> Result<(), ConvError> {
+        self.patch_syms(
+            &self.artifacts().dir.join(self.bin_name()),
+            name_to_info,
+            frame_infos,
+            start_tmp_name,
+            start_name,
+            verbosity,
+        );
+
+        Ok(())
     }
 
-    /// Output commands for debugging patched binary.
+    /// Write a script that plays frames `loops` times, or forever if
+    /// 0, returning the commands debugging the patched binary.
     fn write_dbg_script(
         &self,
         frame_infos: &Vec<FrameInfo>,
@@ -546,36 +2862,415 @@ loop:
         size: u64,
         is_updated: bool,
         bin: &str,
+        loops: u32,
+    ) -> Result<Vec<RenderCommand>, ConvError>;
+
+    /// VSCode launch configuration debugging `bin` with the debugger
+    /// script, which sets breakpoints and reloads symbols, for
+    /// debuggers supported by a VSCode extension.
+    fn vscode_launch(&self, _bin: &str) -> Option<serde_json::Value> {
+        None
+    }
+}
+
+/// Options for generated debugger scripts.
+#[derive(Debug, Default)]
+pub struct ScriptOptions {
+    /// Debugger executable in printed render commands.
+    pub debugger: String,
+
+    /// Debugger expression evaluated when each frame is hit,
+    /// only rendering the frame if it evaluates to non-zero.
+    pub frame_condition: Option<String>,
+
+    /// Merge consecutive frames with the same lines.
+    pub merge_frames: bool,
+
+    /// Let users pause with Ctrl-C, then step through frames or
+    /// resume with debugger commands, for debuggers supporting it.
+    pub interactive: bool,
+
+    /// Wait for each frame until a deadline advanced by its delay,
+    /// instead of waiting for the whole delay after drawing it.
+    pub precise_timing: bool,
+
+    /// File LLDB dumps memory to for reloading symbols, instead of
+    /// a file in the temporary directory named after the script and
+    /// the debugger's pid.
+    pub lldb_mem_path: Option<PathBuf>,
+
+    /// Dump LLDB memory to an in-memory file created with
+    /// `memfd_create`, where supported (Linux), instead of a file.
+    pub lldb_memfd: bool,
+
+    /// Write a GDB command file instead of a Python script, for GDB
+    /// builds or environments without the Python API.
+    pub gdb_commands: bool,
+
+    /// `.text` address in linker scripts, instead of the debugger's default.
+    pub text_addr: Option<u64>,
+
+    /// `.data` address in linker scripts, instead of the debugger's default.
+    pub data_addr: Option<u64>,
+}
+
+/// Files written by a conversion, named by a common prefix
+/// followed by a suffix for each artifact.
+#[derive(Debug)]
+pub struct Artifacts {
+    /// Directory where artifacts are written, which is also the
+    /// working directory of compilers and linkers.
+    pub dir: PathBuf,
+
+    pub prefix: String,
+
+    /// Commands rendering the converted frames, empty if no
+    /// debugger script was written.
+    pub commands: Vec<RenderCommand>,
+}
+
+impl Default for Artifacts {
+    fn default() -> Self {
+        Artifacts {
+            dir: PathBuf::from("."),
+            prefix: String::from("a"),
+            commands: vec![],
+        }
+    }
+}
+
+impl Artifacts {
+    /// File name of the artifact ending with `suffix`, as referenced
+    /// by commands run from the artifacts directory.
+    pub fn name(&self, suffix: &str) -> String {
+        format!("{}{}", self.prefix, suffix)
+    }
+
+    /// Path of the artifact ending with `suffix`.
+    pub fn path(&self, suffix: &str) -> PathBuf {
+        self.dir.join(self.name(suffix))
+    }
+}
+
+/// Command rendering converted frames, run from the artifacts directory.
+#[derive(Debug, Clone)]
+pub struct RenderCommand {
+    /// How frames are rendered by the command.
+    pub title: String,
+
+    pub command: String,
+}
+
+impl RenderCommand {
+    fn new(title: &str, command: String) -> Self {
+        RenderCommand {
+            title: title.to_owned(),
+            command,
+        }
+    }
+}
+
+/// Intermediate files removed when dropped, unless `keep` is set,
+/// so that they are also removed when the conversion fails.
+pub struct TempFiles {
+    pub paths: Vec<PathBuf>,
+
+    pub keep: bool,
+}
+
+impl Drop for TempFiles {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+
+        for path in &self.paths {
+            // Files not written before an error are missing.
+            if let Err(e) = std::fs::remove_file(path)
+                && e.kind() != std::io::ErrorKind::NotFound
+            {
+                debug!("Can't remove {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// Python function of debugger scripts restoring the terminal left by
+/// frame lines, registered to run when the debugger exits.
+// \x1b[?25h => Show cursor (DECTCEM);
+// \x1b[0m => Reset character attributes, revealing text hidden by the last frame line;
+// \x1b[2J => Erase all in display;
+// \x1b[H => Set cursor position to screen origin;
+const PY_RESTORE_TERMINAL: &str = r#"def restore_terminal():
+    sys.stdout.write("\x1b[?25h\x1b[0m\x1b[2J\x1b[H")
+    sys.stdout.flush()
+
+atexit.register(restore_terminal)
+"#;
+
+/// Python statements of debugger scripts waiting for a frame's
+/// `delay` in milliseconds, with lines after the first one indented
+/// by `indent`. With `precise`, waiting ends at a deadline advanced
+/// by each delay, so that time spent printing the backtrace and
+/// reloading symbols doesn't add up, and is skipped when behind.
+fn to_py_sleep(delay: &str, indent: &str, precise: bool) -> String {
+    if !precise {
+        return format!("time.sleep({} / 1000)", delay);
+    }
+
+    format!(
+        r#"global deadline
+{indent}now = time.monotonic()
+{indent}deadline = (now if deadline is None else deadline) + {delay} / 1000
+{indent}if deadline > now:
+{indent}    time.sleep(deadline - now)"#
+    )
+}
+
+/// Whether the frame at `i` is merged into the previous frame, if
+/// `merge_frames` and both frames have the same lines.
+fn is_merged_frame(frame_infos: &[FrameInfo], i: usize, merge_frames: bool) -> bool {
+    merge_frames && i > 0 && frame_infos[i - 1].to_lines() == frame_infos[i].to_lines()
+}
+
+/// Breakpoint address and delay of each frame. If `merge_frames`,
+/// consecutive frames with the same lines share a single breakpoint,
+/// delayed by the sum of their delays.
+fn to_bp_info(
+    frame_infos: &[FrameInfo],
+    name_to_info: &HashMap<String, SymbolInfo>,
+    merge_frames: bool,
+) -> Vec<(u64, u32)> {
+    let mut bp_info: Vec<(u64, u32)> = vec![];
+    for (i, frame_info) in frame_infos.iter().enumerate() {
+        if is_merged_frame(frame_infos, i, merge_frames) {
+            bp_info.last_mut().unwrap().1 += frame_info.delay as u32;
+        } else {
+            bp_info.push((
+                name_to_info.get(&frame_info.last_name).unwrap().addr,
+                frame_info.delay as u32,
+            ));
+        }
+    }
+    debug!(
+        "{} frames, {} breakpoints after merging.",
+        frame_infos.len(),
+        bp_info.len()
     );
+
+    bp_info
 }
 
 pub struct GdbFrameConverter<'a> {
     pub parser: &'a dyn FrameParser,
+    pub script: &'a ScriptOptions,
+    pub artifacts: &'a Artifacts,
+    pub bits: u32,
+}
+
+/// Converter for `rr`, which records a run of the same binary
+/// as gdb and replays it in gdb.
+pub struct RrFrameConverter<'a> {
+    pub parser: &'a dyn FrameParser,
+    pub script: &'a ScriptOptions,
+    pub artifacts: &'a Artifacts,
+    pub bits: u32,
+}
+
+/// Converter for radare2, driven by an r2pipe script.
+pub struct R2FrameConverter<'a> {
+    pub parser: &'a dyn FrameParser,
+    pub script: &'a ScriptOptions,
+    pub artifacts: &'a Artifacts,
+    pub bits: u32,
 }
 
 pub struct LldbFrameConverter<'a> {
     pub parser: &'a dyn FrameParser,
+    pub script: &'a ScriptOptions,
+    pub artifacts: &'a Artifacts,
+    pub bits: u32,
+}
+
+pub struct WinDbgFrameConverter<'a> {
+    pub parser: &'a dyn FrameParser,
+    pub script: &'a ScriptOptions,
+    pub artifacts: &'a Artifacts,
+    pub bits: u32,
 }
 
 pub struct CustomFrameConverter<'a> {
     pub inner: &'a dyn FrameConverter,
     pub file: &'a PathBuf,
+
+    /// Linker executable.
+    pub ld: &'a str,
     pub height: u16,
     pub width: u16,
+
+    /// Value for the `seed` parameter of `init(uint64_t seed, ...)`.
+    pub seed: u64,
+
+    /// Absolute paths of C sources or headers included before the
+    /// input source, whose directories are searched for includes.
+    pub includes: &'a [PathBuf],
 }
 
 impl CustomFrameConverter<'_> {
+    /// Linker arguments selecting the emulation of 32-bit objects,
+    /// since the linker defaults to the host's emulation.
+    fn ld_emulation(&self) -> Vec<&str> {
+        if self.bits() == 32 {
+            vec!["-m", "elf_i386"]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Placeholder addresses passed as the first argument of the
+    /// first `count` calls from `start_offs` taking one, in call order,
+    /// along with the file offset and size of each placeholder constant.
+    ///
+    /// Placeholders are followed from their constant, through `mov`
+    /// and `lea` into any register, or pushed on the stack with 32-bit
+    /// calling conventions, up to the call taking them, so that calls
+    /// don't depend on the instructions selected by the compiler.
+    fn find_placeholder_args(
+        contents: &[u8],
+        start_offs: u64,
+        bits: u32,
+        count: usize,
+    ) -> Vec<(u64, u64, usize)> {
+        let placeholder_addrs = [PLACEHOLDER_SYMTAB_ADDR, PLACEHOLDER_DEBUGSTR_ADDR];
+        let mut decoder = Decoder::with_ip(
+            bits,
+            &contents[start_offs as usize..],
+            start_offs,
+            DecoderOptions::NONE,
+        );
+        let mut instr = Instruction::default();
+        let mut info_factory = InstructionInfoFactory::new();
+        // Registers holding placeholders, and placeholders pushed as
+        // arguments of the next call.
+        let mut regs: HashMap<Register, (u64, u64, usize)> = HashMap::new();
+        let mut pushed = vec![];
+        let mut args = vec![];
+        while args.len() < count && decoder.can_decode() {
+            decoder.decode_out(&mut instr);
+            let offsets = decoder.get_constant_offsets(&instr);
+            trace!(
+                "@ {:08x} => {:?} {:?}",
+                instr.ip(),
+                instr.code(),
+                instr.op_kinds().collect::<Vec<OpKind>>()
+            );
+
+            // b8 04 03 02 01          mov  eax,0x01020304
+            // 8d 3c 25 04 03 02 01    lea  edi,[0x01020304]
+            let imm = (0..instr.op_count()).find_map(|i| instr.try_immediate(i).ok());
+            let constant = if let Some(imm) = imm
+                && offsets.has_immediate()
+                && placeholder_addrs.contains(&imm)
+            {
+                Some((
+                    imm,
+                    instr.ip() + offsets.immediate_offset() as u64,
+                    offsets.immediate_size(),
+                ))
+            } else if instr.mnemonic() == Mnemonic::Lea
+                && offsets.has_displacement()
+                && instr.memory_base() == Register::None
+                && instr.memory_index() == Register::None
+                && placeholder_addrs.contains(&instr.memory_displacement64())
+            {
+                Some((
+                    instr.memory_displacement64(),
+                    instr.ip() + offsets.displacement_offset() as u64,
+                    offsets.displacement_size(),
+                ))
+            } else {
+                None
+            };
+            let reg_source = |i| {
+                (instr.op_kind(i) == OpKind::Register)
+                    .then(|| regs.get(&instr.op_register(i).full_register()).copied())
+                    .flatten()
+            };
+
+            if instr.is_call_near() {
+                // 64-bit calling conventions pass the first argument in
+                // `rdi`, while 32-bit ones push it last.
+                let arg = if bits == 32 {
+                    pushed.last().copied()
+                } else {
+                    regs.get(&Register::RDI).copied()
+                };
+                args.extend(arg);
+                pushed.clear();
+                // Caller-saved registers.
+                for reg in [
+                    Register::RAX,
+                    Register::RCX,
+                    Register::RDX,
+                    Register::RSI,
+                    Register::RDI,
+                    Register::R8,
+                    Register::R9,
+                    Register::R10,
+                    Register::R11,
+                ] {
+                    regs.remove(&reg);
+                }
+            } else if instr.mnemonic() == Mnemonic::Push {
+                // 68 04 03 02 01    push  0x01020304
+                pushed.extend(constant.or_else(|| reg_source(0)));
+            } else if matches!(instr.mnemonic(), Mnemonic::Mov | Mnemonic::Lea)
+                && instr.op0_kind() == OpKind::Register
+            {
+                // 48 89 df    mov  rdi,rbx
+                let reg = instr.op0_register().full_register();
+                match constant.or_else(|| reg_source(1)) {
+                    Some(source) => regs.insert(reg, source),
+                    None => regs.remove(&reg),
+                };
+            } else if instr.mnemonic() == Mnemonic::Mov
+                && instr.op0_kind() == OpKind::Memory
+                && instr.memory_base().full_register() == Register::RSP
+            {
+                // c7 04 24 04 03 02 01    mov  DWORD PTR [esp],0x01020304
+                pushed.extend(constant.or_else(|| reg_source(1)));
+            } else {
+                for used in info_factory.info(&instr).used_registers() {
+                    if matches!(
+                        used.access(),
+                        OpAccess::Write
+                            | OpAccess::CondWrite
+                            | OpAccess::ReadWrite
+                            | OpAccess::ReadCondWrite
+                    ) {
+                        regs.remove(&used.register().full_register());
+                    }
+                }
+            }
+        }
+
+        args
+    }
+
     fn patch_addrs(
         &self,
+        path: &Path,
         name_to_info: &HashMap<String, SymbolInfo>,
-        frame_infos: &Vec<FrameInfo>,
+        frame_infos: &[FrameInfo],
         text_offs: &u64,
         start_addr: u64,
+        bits: u32,
+        verbosity: Verbosity,
     ) {
         let mut file = std::fs::OpenOptions::new()
             .read(true)
             .write(true)
-            .open("a2.out")
+            .open(path)
             .expect("Can't open bin");
 
         file.seek(std::io::SeekFrom::Start(0))
@@ -583,73 +3278,76 @@ impl CustomFrameConverter<'_> {
         let mut contents = vec![];
         file.read_to_end(&mut contents).expect("Can't read bin");
 
+        let placeholder_addrs = [PLACEHOLDER_SYMTAB_ADDR, PLACEHOLDER_DEBUGSTR_ADDR];
+        // Shared functions are patched once, in the order their frame
+        // lines are drawn by the start function.
+        let expected_args = frame_infos
+            .iter()
+            .flat_map(|frame_info| &frame_info.tmp_names)
+            .unique()
+            .flat_map(|name| {
+                name_to_info
+                    .get(name)
+                    .unwrap()
+                    .offs
+                    .iter()
+                    .enumerate()
+                    .map(move |(i, offs)| (name, placeholder_addrs[i], offs))
+            })
+            .collect_vec();
+
         let start_offs = start_addr - self.text_section_addr() + text_offs;
-        let contents_at_text_section = &contents[start_offs as usize..];
-        let mut decoder = Decoder::with_ip(
-            64,
-            contents_at_text_section,
+        let args = CustomFrameConverter::find_placeholder_args(
+            &contents,
             start_offs,
-            DecoderOptions::NONE,
+            bits,
+            expected_args.len(),
         );
-        let mut instr = Instruction::default();
-        let mut info_factory = InstructionInfoFactory::new();
-        let placeholder_addrs = [PLACEHOLDER_SYMTAB_ADDR, PLACEHOLDER_DEBUGSTR_ADDR];
-        for frame_info in frame_infos {
-            for name in &frame_info.tmp_names {
-                for (i, offs) in name_to_info.get(name).unwrap().offs.iter().enumerate() {
-                    debug!(
-                        "{} for {} {:08x} {:08x}",
-                        name, i, offs, placeholder_addrs[i]
-                    );
-                    let mut target_offs = None;
-                    while decoder.can_decode() {
-                        decoder.decode_out(&mut instr);
-                        debug!(
-                            "@ {:08x} => {:?} {:?}",
-                            instr.ip(),
-                            instr.code(),
-                            instr.op_kinds().collect::<Vec<OpKind>>()
-                        );
-
-                        // bf 04 03 02 01    mov   edi,0x01020304
-                        // e8 0e fe ff ff    call  0x4011fd <draw_line>
-                        let info = info_factory.info(&instr);
-                        if instr.op_count() == 2
-                            && info.used_registers().len() == 1
-                                && info.used_registers().first().unwrap().access() == OpAccess::Write
-                                && instr.op0_kind() == OpKind::Register
-                                && instr.op1_kind() == OpKind::Immediate32
-                                // Assumes instruction order is preserved between calls.
-                                && instr.try_immediate(1).unwrap() == placeholder_addrs[i]
-                        {
-                            target_offs = Some(instr.ip() + 1);
-                        } else if instr.op_count() == 1
-                            && instr.op0_kind() == OpKind::NearBranch64
-                            && instr.mnemonic() == Mnemonic::Call
-                            && target_offs.is_some()
-                        {
-                            break;
-                        }
-                    }
-                    if target_offs.is_none() {
-                        panic!("Compiler generated unhandled instructions?");
-                    }
+        if args.len() < expected_args.len() {
+            panic!(
+                "Found {} of {} calls taking placeholder addresses, was `draw_line()` inlined or specialized by the compiler?",
+                args.len(),
+                expected_args.len()
+            );
+        }
+        if args
+            .iter()
+            .map(|(_, target_offs, _)| target_offs)
+            .unique()
+            .count()
+            < args.len()
+        {
+            panic!(
+                "Placeholder address shared by multiple calls, each call needs its own address."
+            );
+        }
 
-                    debug!("sym @ {:08x} => patch @ {:08x}", offs, target_offs.unwrap());
-                    file.seek(std::io::SeekFrom::Start(target_offs.unwrap()))
-                        .expect(&*format!("Can't seek to 0x{:08x}", target_offs.unwrap()));
-                    file.write(&(offs + self.inner.data_section_addr()).to_le_bytes()[..4])
-                        .expect("Can't write bin");
-                }
+        let progress = Progress::new("Patching addresses", Some(expected_args.len()), verbosity);
+        for ((name, placeholder_addr, offs), (addr, target_offs, size)) in
+            expected_args.into_iter().zip(args)
+        {
+            progress.inc();
+            trace!("{} for {:08x} {:08x}", name, offs, placeholder_addr);
+            if addr != placeholder_addr {
+                panic!(
+                    "Expected placeholder address 0x{:08x} for {}, got 0x{:08x}.",
+                    placeholder_addr, name, addr
+                );
             }
+
+            trace!("sym @ {:08x} => patch @ {:08x}", offs, target_offs);
+            file.seek(std::io::SeekFrom::Start(target_offs))
+                .expect(&*format!("Can't seek to 0x{:08x}", target_offs));
+            file.write(&(offs + self.inner.data_section_addr()).to_le_bytes()[..size])
+                .expect("Can't write bin");
         }
     }
 
-    fn patch_build_id(&self, offs: u64, desc: Vec<u8>) {
+    fn patch_build_id(&self, path: &Path, offs: u64, desc: Vec<u8>) {
         let mut file = std::fs::OpenOptions::new()
             .read(true)
             .write(true)
-            .open("a2.out")
+            .open(path)
             .expect("Can't open bin");
 
         debug!("Patching build id @ 0x{:08x} = {:x?}.", offs, &desc);
@@ -660,137 +3358,196 @@ impl CustomFrameConverter<'_> {
 }
 
 impl FrameConverter for CustomFrameConverter<'_> {
+    fn data_section_addr(&self) -> u64 {
+        self.inner.data_section_addr()
+    }
+
+    fn text_section_addr(&self) -> u64 {
+        self.inner.text_section_addr()
+    }
+
+    /// Scripts are run with the binary embedding patched symbols.
+    fn vscode_launch(&self, _bin: &str) -> Option<serde_json::Value> {
+        self.inner.vscode_launch(&self.artifacts().name("2.out"))
+    }
+
+    /// The first binary is embedded in the second one, so it's only
+    /// needed while linking.
+    fn temp_names(&self) -> Vec<String> {
+        let artifacts = self.artifacts();
+        vec![
+            artifacts.name(".c"),
+            artifacts.name(".o"),
+            self.bin_name(),
+            artifacts.name(".ld"),
+            artifacts.name(&format!("2.0x{:04x}.ld", self.data_section_addr())),
+        ]
+    }
+
+    fn artifacts(&self) -> &Artifacts {
+        self.inner.artifacts()
+    }
+
+    fn bits(&self) -> u32 {
+        self.inner.bits()
+    }
+
+    fn script_name(&self) -> String {
+        self.inner.script_name()
+    }
+
     fn parser(&self) -> &dyn FrameParser {
         self.inner.parser()
     }
 
-    fn prepare_src(
+    fn shares_framelines(&self) -> bool {
+        self.inner.shares_framelines()
+    }
+
+    fn merges_frames(&self) -> bool {
+        self.inner.merges_frames()
+    }
+
+    fn write_src(
         &self,
-        frame_infos: &Vec<FrameInfo>,
+        out: &mut dyn Write,
+        frame_infos: &[FrameInfo],
         start_tmp_name: &str,
         has_debug_info: bool,
-    ) -> String {
-        let input_src = std::fs::read_to_string(self.file).unwrap();
-        let draw_line_calls = frame_infos
-            .iter()
-            .map(|_| {
-                let mut o = String::new();
-                for i in 0..self.height {
-                    let prefix_offset = if i == self.height - 1 {
-                        10 // \x1b[1;1H\x1b[2K
-                    } else {
-                        9 // \x1b[2K\x1b[99D
-                    };
-                    o = format!(
-                        r#"{}
-    draw_line((uint8_t*){}UL, {}, {});"#,
-                        o,
-                        format!("0x{:08x}", PLACEHOLDER_SYMTAB_ADDR),
-                        prefix_offset,
-                        self.height - 1 - i
-                    );
-                    if has_debug_info {
-                        o = format!(
-                            r#"{}
-    draw_line((uint8_t*){}UL, {}, {});"#,
-                            o,
-                            format!("0x{:08x}", PLACEHOLDER_DEBUGSTR_ADDR),
-                            prefix_offset,
-                            self.height - 1 - i
-                        );
-                    }
-                }
-                o
-            })
-            .collect::<Vec<String>>()
-            .join("\n");
-
-        let heads = frame_infos
-            .iter()
-            .map(|n| format!("{}();", n.first_name))
-            .collect::<Vec<String>>()
-            .join("\n    ");
-        let calls = frame_infos
-            .iter()
-            .map(|n| {
-                let mut o = String::new();
-                for (prev, next) in n.tmp_names.iter().tuple_windows() {
-                    o = format!(
-                        r#"
-void {}() {{
-    {}();
-}}
-{}"#,
-                        prev, next, o
-                    );
-                }
-                format!(
+    ) -> std::io::Result<()> {
+        let mut draw_line_calls = String::new();
+        for i in 0..self.height {
+            let prefix_offset = if i == self.height - 1 {
+                10 // \x1b[1;1H\x1b[2K
+            } else {
+                9 // \x1b[2K\x1b[99D
+            };
+            draw_line_calls += &format!(
+                r#"
+    draw_line((uint8_t*)0x{:08x}UL, {}, {});"#,
+                PLACEHOLDER_SYMTAB_ADDR,
+                prefix_offset,
+                self.height - 1 - i
+            );
+            if has_debug_info {
+                draw_line_calls += &format!(
                     r#"
-void {}() {{
-    return;
-}}
-{}"#,
-                    n.tmp_names.last().unwrap(),
-                    o
-                )
-            })
-            .collect::<Vec<String>>()
-            .join("\n");
+    draw_line((uint8_t*)0x{:08x}UL, {}, {});"#,
+                    PLACEHOLDER_DEBUGSTR_ADDR,
+                    prefix_offset,
+                    self.height - 1 - i
+                );
+            }
+        }
 
-        format!(
+        out.write_all(b"\n")?;
+        write_frameline_fns(out, frame_infos)?;
+        // Callbacks are declared before they are defined, so that
+        // definitions not matching the calls below fail to compile.
+        out.write_all(b"\n\n")?;
+        out.write_all(C_HEADER.as_bytes())?;
+        for include in self.includes {
+            writeln!(out, "#include \"{}\"", include.display())?;
+        }
+        std::io::copy(&mut File::open(self.file)?, out)?;
+        write!(
+            out,
             r#"
-{}
-
-{}
 
 void {}() {{
-    init(123, {}, {});
+    init({}, {}, {});
 loop:
     update_frame();
     {}
     {}
     goto loop;
 }}"#,
-            calls, input_src, start_tmp_name, self.width, self.height, draw_line_calls, heads
+            start_tmp_name,
+            self.seed,
+            self.width,
+            self.height,
+            // Each frame draws the same lines, from the symbols
+            // patched by `update_frame()`.
+            std::iter::repeat_n(&draw_line_calls, frame_infos.len()).format("\n"),
+            // Merged frames are drawn by the breakpoint of the
+            // previous frame, so their functions aren't called.
+            frame_infos
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !is_merged_frame(frame_infos, *i, self.merges_frames()))
+                .map(|(_, n)| format!("{}();", n.first_name))
+                .format("\n    ")
         )
     }
 
     fn compile(
         &self,
-        src: &str,
+        frame_infos: &[FrameInfo],
         compiler: &str,
+        cflags: &[&str],
         start_tmp_name: &str,
         include_debug_info: bool,
+        verbosity: Verbosity,
     ) -> Result<(), Box<dyn Error>> {
-        let name = std::path::Path::new("a.c");
-        let mut file = std::fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(name)?;
-        file.write_all(src.as_bytes())?;
-        spawn(
-            Command::new(compiler).args(
+        let artifacts = self.artifacts();
+        self.write_src_file(frame_infos, start_tmp_name, include_debug_info)?;
+        // The header is already part of the source, but input including
+        // it still needs to find it. It's written to a directory unique
+        // to this process, so that a header next to the output isn't
+        // replaced, then removed once compiled.
+        let header_dir =
+            std::env::temp_dir().join(format!("backgif-include-{}", std::process::id()));
+        std::fs::create_dir_all(&header_dir)?;
+        std::fs::write(header_dir.join(C_HEADER_NAME), C_HEADER)?;
+        // Included files may include other files next to them.
+        let include_flags = self
+            .includes
+            .iter()
+            .filter_map(|include| include.parent())
+            .unique()
+            .chain(std::iter::once(header_dir.as_path()))
+            .map(|dir| format!("-I{}", dir.display()))
+            .collect_vec();
+        let compiled = spawn_tool(
+            Command::new(compiler).current_dir(&artifacts.dir).args(
                 include_debug_info
                     .then_some(&["-g"])
                     .into_iter()
                     .flatten()
                     .chain(COMPILER_ARGS)
-                    .chain(&["-c", "-o", "a.o", name.to_str().unwrap()]),
+                    .chain(cflags)
+                    .chain(&include_flags.iter().map(String::as_str).collect_vec())
+                    .chain((self.bits() == 32).then_some(&"-m32"))
+                    .chain(&["-c", "-o", &artifacts.name(".o"), &artifacts.name(".c")]),
             ),
+            "--compiler-path",
+            verbosity,
+        );
+        std::fs::remove_dir_all(&header_dir).ok();
+        compiled?;
+
+        let linker_script = self.write_linker_script(None)?;
+        spawn_tool(
+            Command::new(self.ld)
+                .current_dir(&artifacts.dir)
+                .args(self.ld_emulation())
+                // Relocations locate names in `.debug_str`.
+                .args(include_debug_info.then_some("--emit-relocs"))
+                .args(&[
+                    "--build-id",
+                    "-e",
+                    start_tmp_name,
+                    "-o",
+                    &self.bin_name(),
+                    &artifacts.name(".o"),
+                    "-T",
+                    &linker_script,
+                ]),
+            "--ld-path",
+            verbosity,
         )?;
 
-        spawn(Command::new("ld").args(&[
-            "--build-id",
-            "-e",
-            &start_tmp_name,
-            "-o",
-            "a.out",
-            "a.o",
-            "-T",
-            "a.ld",
-        ]))
+        Ok(())
     }
 
     fn patch_bin(
@@ -800,12 +3557,22 @@ loop:
         start_tmp_name: &str,
         start_name: &str,
         build_id_desc_offs: u64,
-    ) {
+        verbosity: Verbosity,
+    ) -> Result<(), ConvError> {
         // Since CustomFrameConverters have the program code itself
         // patching symbols with binary-coded decimals, these
         // symbols have placeholder framelines with zeros on each
         // "r:g:b" component.
-        FrameConverter::patch_syms(self, name_to_info, frame_infos, start_tmp_name, start_name);
+        let artifacts = self.artifacts();
+        FrameConverter::patch_syms(
+            self,
+            &artifacts.dir.join(self.bin_name()),
+            name_to_info,
+            frame_infos,
+            start_tmp_name,
+            start_name,
+            verbosity,
+        );
 
         // We have to convince debuggers to reload these symbols.
         // However, sections such as `.symtab` are not loaded
@@ -815,17 +3582,29 @@ loop:
         // The trick we do here is to embed the previously compiled
         // binary into a custom section (`.data`) that we explicitly
         // load as writable memory.
-        spawn(Command::new("ld").args(&[
-            "--build-id",
-            "-e",
-            &start_tmp_name,
-            "-o",
-            "a2.out",
-            "a.o",
-            "-T",
-            format!("a2.0x{:04x}.ld", self.inner.data_section_addr()).as_str(),
-        ]))
-        .unwrap();
+        let linker_script = self
+            .write_linker_script(Some(&self.bin_name()))
+            .expect("Can't write linker script");
+        spawn_tool(
+            Command::new(self.ld)
+                .current_dir(&artifacts.dir)
+                .args(self.ld_emulation())
+                .args(&[
+                    "--build-id",
+                    "-e",
+                    start_tmp_name,
+                    "-o",
+                    &artifacts.name("2.out"),
+                    &artifacts.name(".o"),
+                    "-T",
+                    &linker_script,
+                ]),
+            "--ld-path",
+            verbosity,
+        )
+        .map_err(|e| ConvError::Build {
+            message: e.to_string(),
+        })?;
 
         // We now modify placeholder addresses in the compiled code
         // to instead reference the symbols in the `.symtab` section
@@ -841,19 +3620,26 @@ loop:
         //   either by CRC, or by Build ID descriptor in section
         //   `.note.gnu.build-id` (which is easier to lie about:
         //   we can just patch it with the second binary's Build ID);
-        let bin_info2 = FrameConverter::parse_bin(self, "a2.out");
+        let bin2 = artifacts.path("2.out");
+        let bin_info2 = FrameConverter::parse_bin(self, &bin2)?;
         CustomFrameConverter::patch_addrs(
             &self,
+            &bin2,
             &name_to_info,
             &frame_infos,
             bin_info2.section_offs.get(".text").unwrap(),
             bin_info2.name_to_info.get(start_tmp_name).unwrap().addr,
+            bin_info2.bits,
+            verbosity,
         );
         CustomFrameConverter::patch_build_id(
             &self,
+            &bin2,
             bin_info2.section_offs.get(".data").unwrap() + build_id_desc_offs,
             bin_info2.build_id_desc,
         );
+
+        Ok(())
     }
 
     fn write_dbg_script(
@@ -863,17 +3649,156 @@ loop:
         size: u64,
         _is_updated: bool,
         _bin: &str,
-    ) {
-        self.inner
-            .write_dbg_script(frame_infos, name_to_info, size, true, "a2.out")
+        loops: u32,
+    ) -> Result<Vec<RenderCommand>, ConvError> {
+        self.inner.write_dbg_script(
+            frame_infos,
+            name_to_info,
+            size,
+            true,
+            &self.artifacts().name("2.out"),
+            loops,
+        )
+    }
+}
+
+impl GdbFrameConverter<'_> {
+    /// Write a command file setting a software breakpoint for each
+    /// entry of `bp_info`, which prints the backtrace, then sleeps
+    /// in a shell for its delay. Sleeping in a subprocess is less
+    /// precise than the Python script, but doesn't require its API.
+    fn write_gdb_commands(&self, bp_info: &[(u64, u32)], loops: u32) -> Result<(), ConvError> {
+        let condition = self
+            .script
+            .frame_condition
+            .as_ref()
+            .map(|condition| format!(" if {}", condition))
+            .unwrap_or_default();
+        // Passes are counted on the last frame, then the inferior is
+        // killed outside of the breakpoint's commands.
+        let loop_count = if loops > 0 {
+            format!(
+                r#"
+  set $passes = $passes + 1
+  if $passes >= {}
+    kill
+    quit
+  end"#,
+                loops
+            )
+        } else {
+            String::new()
+        };
+        let breakpoints = bp_info
+            .iter()
+            .enumerate()
+            .map(|(i, (addr, delay))| {
+                format!(
+                    r#"break *0x{:08x}{}
+commands
+  silent
+  bt
+  shell sleep {}.{:02}{}
+  continue
+end
+"#,
+                    addr,
+                    condition,
+                    delay / 100,
+                    delay % 100,
+                    if i == bp_info.len() - 1 {
+                        loop_count.as_str()
+                    } else {
+                        ""
+                    }
+                )
+            })
+            .join("\n");
+
+        // \033[?25h => Show cursor (DECTCEM);
+        // \033[0m => Reset character attributes, revealing text hidden by the last frame line;
+        // \033[2J => Erase all in display;
+        // \033[H => Set cursor position to screen origin;
+        let o = format!(
+            r#"set pagination off
+set style enabled off
+set startup-with-shell off
+set confirm off
+
+define hook-quit
+  shell printf '\033[?25h\033[0m\033[2J\033[H'
+end
+
+starti
+set $passes = 0
+
+{}
+continue
+"#,
+            breakpoints
+        );
+        self.write_script(&o)
     }
 }
 
 impl FrameConverter for GdbFrameConverter<'_> {
+    fn data_section_addr(&self) -> u64 {
+        self.script.data_addr.unwrap_or(0)
+    }
+
+    fn text_section_addr(&self) -> u64 {
+        self.script.text_addr.unwrap_or(TEXT_SECTION_ADDR)
+    }
+
+    fn artifacts(&self) -> &Artifacts {
+        self.artifacts
+    }
+
+    fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    fn script_name(&self) -> String {
+        if self.script.gdb_commands {
+            self.artifacts.name("_gdb.gdb")
+        } else {
+            self.artifacts.name("_gdb.py")
+        }
+    }
+
     fn parser(&self) -> &dyn FrameParser {
         self.parser
     }
 
+    /// Only the breakpoint of the next frame is set by the Python
+    /// script, while gdb commands set all breakpoints at once.
+    fn shares_framelines(&self) -> bool {
+        !self.script.gdb_commands
+    }
+
+    fn merges_frames(&self) -> bool {
+        self.script.merge_frames
+    }
+
+    /// Configuration of the C/C++ extension (`cppdbg`), replacing its
+    /// launch commands with the script, which starts the inferior.
+    fn vscode_launch(&self, bin: &str) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "name": format!("backgif ({})", bin),
+            "type": "cppdbg",
+            "request": "launch",
+            "program": format!("${{workspaceFolder}}/{}", bin),
+            "cwd": "${workspaceFolder}",
+            "MIMode": "gdb",
+            "miDebuggerPath": self.script.debugger,
+            "customLaunchSetupCommands": [
+                {"text": format!("-file-exec-and-symbols ${{workspaceFolder}}/{}", bin)},
+                {"text": format!("source ${{workspaceFolder}}/{}", self.script_name())}
+            ],
+            "launchCompleteCommand": "None"
+        }))
+    }
+
     fn write_dbg_script(
         &self,
         frame_infos: &Vec<FrameInfo>,
@@ -881,128 +3806,588 @@ impl FrameConverter for GdbFrameConverter<'_> {
         _size: u64,
         is_updated: bool,
         bin: &str,
-    ) {
-        let bp_info = frame_infos
-            .iter()
-            .map(|n| (name_to_info.get(&n.last_name).unwrap().addr, n.delay))
-            .collect_vec();
-        println!(
-            "\n{}",
-            "Render automatically with debugger script:".purple().bold()
-        );
-        println!("{}", format!("gdb ./{bin} --command a_gdb.py").bold());
-        println!(
-            "\n{}",
-            "Render manually with software breakpoints:".purple().bold()
-        );
-        println!(
-            "{}",
-            format!(
-                r#"gdb ./{bin} \
-    -ex 'set pagination off' \
-    -ex 'set style enabled off' \
-    -ex 'set startup-with-shell off' \"#
-            )
-            .bold()
-        );
-        println!(
-            "{}",
-            [String::from("    -ex 'starti'")]
-                .into_iter()
-                .chain(
-                    bp_info
-                        .iter()
-                        .map(|(addr, _)| format!("    -ex 'b *0x{:08x}'", addr))
-                )
-                .join(" \\\n")
-                .bold()
-        );
+        loops: u32,
+    ) -> Result<Vec<RenderCommand>, ConvError> {
+        let debugger = &self.script.debugger;
+        let bp_info = to_bp_info(frame_infos, name_to_info, self.merges_frames());
+        let render_commands = vec![
+            RenderCommand::new(
+                "Render automatically with debugger script",
+                format!(
+                    "{debugger} ./{bin} {} {}",
+                    if self.script.gdb_commands {
+                        "-x"
+                    } else {
+                        "--command"
+                    },
+                    self.script_name()
+                ),
+            ),
+            RenderCommand::new(
+                "Render manually with software breakpoints",
+                [
+                    format!("{debugger} ./{bin}"),
+                    String::from("    -ex 'set pagination off'"),
+                    String::from("    -ex 'set style enabled off'"),
+                    String::from("    -ex 'set startup-with-shell off'"),
+                    String::from("    -ex 'starti'"),
+                ]
+                .into_iter()
+                .chain(
+                    bp_info
+                        .iter()
+                        .map(|(addr, _)| format!("    -ex 'b *0x{:08x}'", addr)),
+                )
+                .join(" \\\n"),
+            ),
+        ];
+        if self.script.gdb_commands {
+            self.write_gdb_commands(&bp_info, loops)?;
+            return Ok(render_commands);
+        }
+
+        let breakpoints = bp_info
+            .iter()
+            .circular_tuple_windows::<(_, _)>()
+            .map(|(prev, next)| {
+                format!(
+                    "{}[0x{:08x}, 0x{:08x}, {}],",
+                    " ".repeat(4),
+                    prev.0,
+                    next.0,
+                    prev.1 * 10
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let symbol_reload = is_updated
+            .then(|| {
+                format!(
+                    r#"
+        gdb.execute(f"symbol-file {bin}")
+        gdb.execute(f"symbol-file /proc/{{gdb.selected_inferior().pid}}/mem")"#
+                )
+            })
+            .unwrap_or_else(|| String::new());
+
+        let condition = self
+            .script
+            .frame_condition
+            .as_ref()
+            .map(|condition| {
+                format!(
+                    r#"
+        if int(gdb.parse_and_eval({})) == 0:
+            return False"#,
+                    serde_json::to_string(condition).unwrap()
+                )
+            })
+            .unwrap_or_default();
+
+        // Stops on the last frame of the last pass, so that
+        // the inferior can be killed outside of a breakpoint.
+        let (loop_count, loop_exit) = if loops > 0 {
+            (
+                format!(
+                    r#"
+
+        if bp_i == 0:
+            global passes
+            passes += 1
+            if passes >= {}:
+                return True"#,
+                    loops
+                ),
+                String::from(
+                    r#"gdb.execute("kill")
+gdb.execute("quit")
+"#,
+                ),
+            )
+        } else {
+            (String::new(), String::new())
+        };
+
+        // Ctrl-C stops the inferior and returns to the prompt, without
+        // passing SIGINT to the inferior, which would terminate it.
+        // Commands then continue to the next breakpoint, which only
+        // stops again when stepping. Since continuing returns on each
+        // stop, the last pass is checked after each command.
+        let (step_pause, commands, start, loop_exit) = if self.script.interactive {
+            (
+                String::from(
+                    r#"
+        if stepping:
+            return True"#,
+                ),
+                format!(
+                    r#"
+stepping = False
+
+def run():
+    try:
+        gdb.execute("c")
+    except (gdb.error, KeyboardInterrupt):
+        pass
+    if {} and passes >= {}:
+        gdb.execute("kill")
+        gdb.execute("quit")
+
+class Step(gdb.Command):
+    """Draw the next frame, then pause."""
+
+    def __init__(self):
+        super().__init__("backgif-step", gdb.COMMAND_RUNNING)
+
+    def invoke(self, arg, from_tty):
+        global stepping
+        stepping = True
+        run()
+
+class Resume(gdb.Command):
+    """Resume drawing frames, until paused with Ctrl-C."""
+
+    def __init__(self):
+        super().__init__("backgif-resume", gdb.COMMAND_RUNNING)
+
+    def invoke(self, arg, from_tty):
+        global stepping
+        stepping = False
+        run()
+
+Step()
+Resume()
+"#,
+                    if loops > 0 { "True" } else { "False" },
+                    loops,
+                ),
+                String::from(
+                    r#"gdb.execute("handle SIGINT stop print nopass")
+print("Pause with Ctrl-C, then run `backgif-step` or `backgif-resume`.")
+run()"#,
+                ),
+                String::new(),
+            )
+        } else {
+            (
+                String::new(),
+                String::new(),
+                String::from(r#"gdb.execute("c")"#),
+                loop_exit,
+            )
+        };
+
+        let o = format!(
+            r#"
+#!/usr/bin/env python3
+
+import atexit
+import gdb
+import sys
+import time
+
+{restore_terminal}
+class B(gdb.Breakpoint):
+    def __init__(self, offset, next_offset, delay):
+        self.delay = delay
+        gdb.Breakpoint.__init__(self, f"*{{offset}}", gdb.BP_HARDWARE_BREAKPOINT)
+
+    def stop(self):
+        {}
+
+        gdb.execute("delete breakpoints")
+        global bp_i
+        bp_i = (bp_i + 1) % {}
+        B(*bps[bp_i])
+{}
+        gdb.execute("bt")
+        {}{}{}
+        return False
+{}
+gdb.execute("set pagination off")
+gdb.execute("set style enabled off")
+gdb.execute("set startup-with-shell off")
+
+gdb.execute("starti")
+bp_i = 0
+passes = 0
+deadline = None
+bps = [
+{}
+]
+B(*bps[bp_i])
+{}
+{}"#,
+            symbol_reload,
+            bp_info.len(),
+            condition,
+            to_py_sleep("self.delay", "        ", self.script.precise_timing),
+            loop_count,
+            step_pause,
+            commands,
+            breakpoints,
+            start,
+            loop_exit,
+            restore_terminal = PY_RESTORE_TERMINAL,
+        );
+        self.write_script(&o)?;
+
+        Ok(render_commands)
+    }
+}
+
+impl FrameConverter for RrFrameConverter<'_> {
+    fn artifacts(&self) -> &Artifacts {
+        self.artifacts
+    }
+
+    fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    fn script_name(&self) -> String {
+        self.artifacts.name("_gdb.py")
+    }
+
+    fn parser(&self) -> &dyn FrameParser {
+        self.parser
+    }
+
+    /// Breakpoints are all set at once when replaying.
+    fn shares_framelines(&self) -> bool {
+        false
+    }
+
+    fn merges_frames(&self) -> bool {
+        self.script.merge_frames
+    }
+
+    fn write_dbg_script(
+        &self,
+        frame_infos: &Vec<FrameInfo>,
+        name_to_info: &HashMap<String, SymbolInfo>,
+        _size: u64,
+        _is_updated: bool,
+        bin: &str,
+        loops: u32,
+    ) -> Result<Vec<RenderCommand>, ConvError> {
+        let rr = &self.script.debugger;
+        let bp_info = to_bp_info(frame_infos, name_to_info, self.merges_frames());
+        let render_commands = vec![
+            RenderCommand::new(
+                "Record until interrupted with Ctrl-C",
+                format!("{rr} record ./{bin}"),
+            ),
+            RenderCommand::new(
+                "Render automatically with debugger script",
+                format!("{rr} replay -x {}", self.script_name()),
+            ),
+            RenderCommand::new(
+                "Render manually with software breakpoints",
+                [
+                    format!("{rr} replay --"),
+                    String::from("    -ex 'set pagination off'"),
+                    String::from("    -ex 'set style enabled off'"),
+                ]
+                .into_iter()
+                .chain(
+                    bp_info
+                        .iter()
+                        .map(|(addr, _)| format!("    -ex 'b *0x{:08x}'", addr)),
+                )
+                .join(" \\\n"),
+            ),
+        ];
+
+        let breakpoints = bp_info
+            .iter()
+            .enumerate()
+            .map(|(i, (addr, delay))| format!("B({}, 0x{:08x}, {})", i, addr, delay * 10))
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let condition = self
+            .script
+            .frame_condition
+            .as_ref()
+            .map(|condition| {
+                format!(
+                    r#"
+        if int(gdb.parse_and_eval({})) == 0:
+            return False"#,
+                    serde_json::to_string(condition).unwrap()
+                )
+            })
+            .unwrap_or_default();
+
+        // Stops on the first frame of the pass after the last one,
+        // so that the replay can be killed outside of a breakpoint.
+        let (loop_count, loop_exit) = if loops > 0 {
+            (
+                format!(
+                    r#"
+        if self.i == 0:
+            global passes
+            if passes >= {}:
+                return True
+            passes += 1"#,
+                    loops
+                ),
+                String::from(
+                    r#"gdb.execute("kill")
+gdb.execute("quit")
+"#,
+                ),
+            )
+        } else {
+            (String::new(), String::new())
+        };
+
+        // Replays are deterministic, so breakpoints stay set for
+        // the whole replay, hit in the same order as when recorded.
+        let o = format!(
+            r#"
+#!/usr/bin/env python3
+
+import gdb
+import time
+
+class B(gdb.Breakpoint):
+    def __init__(self, i, offset, delay):
+        self.i = i
+        self.delay = delay
+        gdb.Breakpoint.__init__(self, f"*{{offset}}")
+
+    def stop(self):{}{}
+        gdb.execute("bt")
+        time.sleep(self.delay / 1000)
+        return False
+
+gdb.execute("set pagination off")
+gdb.execute("set style enabled off")
+
+passes = 0
+{}
+gdb.execute("c")
+{}"#,
+            loop_count, condition, breakpoints, loop_exit
+        );
+        self.write_script(&o)?;
+
+        Ok(render_commands)
+    }
+}
+
+impl FrameConverter for R2FrameConverter<'_> {
+    fn artifacts(&self) -> &Artifacts {
+        self.artifacts
+    }
+
+    fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    fn script_name(&self) -> String {
+        self.artifacts.name("_r2.py")
+    }
+
+    fn parser(&self) -> &dyn FrameParser {
+        self.parser
+    }
+
+    /// Breakpoints are all set at once, and looked up by address.
+    fn shares_framelines(&self) -> bool {
+        false
+    }
+
+    fn merges_frames(&self) -> bool {
+        self.script.merge_frames
+    }
+
+    fn write_dbg_script(
+        &self,
+        frame_infos: &Vec<FrameInfo>,
+        name_to_info: &HashMap<String, SymbolInfo>,
+        _size: u64,
+        _is_updated: bool,
+        bin: &str,
+        loops: u32,
+    ) -> Result<Vec<RenderCommand>, ConvError> {
+        let debugger = &self.script.debugger;
+        let bp_info = to_bp_info(frame_infos, name_to_info, self.merges_frames());
+        let render_commands = vec![
+            RenderCommand::new(
+                "Render automatically with r2pipe script",
+                format!("python3 {}", self.script_name()),
+            ),
+            RenderCommand::new(
+                "Render manually with software breakpoints, then `dc` and `dbt`",
+                [format!("{debugger} -d")]
+                    .into_iter()
+                    .chain(
+                        bp_info
+                            .iter()
+                            .map(|(addr, _)| format!("    -c 'db 0x{:08x}'", addr)),
+                    )
+                    .chain([format!("    ./{bin}")])
+                    .join(" \\\n"),
+            ),
+        ];
+
+        let (pc, sp, fp, word_size) = if self.bits == 32 {
+            ("eip", "esp", "ebp", 4)
+        } else {
+            ("rip", "rsp", "rbp", 8)
+        };
 
         let breakpoints = bp_info
             .iter()
-            .circular_tuple_windows::<(_, _)>()
-            .map(|(prev, next)| {
-                format!(
-                    "{}[0x{:08x}, 0x{:08x}, {}],",
-                    " ".repeat(4),
-                    prev.0,
-                    next.0,
-                    prev.1 * 10
-                )
+            .enumerate()
+            .map(|(i, (addr, delay))| {
+                format!("{}0x{:08x}: ({}, {}),", " ".repeat(4), addr, i, delay * 10)
             })
             .collect::<Vec<String>>()
             .join("\n");
 
-        let symbol_reload = is_updated
-            .then(|| {
-                String::from(
+        let condition = self
+            .script
+            .frame_condition
+            .as_ref()
+            .map(|condition| {
+                format!(
                     r#"
-        gdb.execute(f"symbol-file a2.out")
-        gdb.execute(f"symbol-file /proc/{gdb.selected_inferior().pid}/mem")"#,
+    if int(r2.cmd("?v " + {}), 16) == 0:
+        continue"#,
+                    serde_json::to_string(condition).unwrap()
                 )
             })
-            .unwrap_or_else(|| String::new());
+            .unwrap_or_default();
+
+        let loop_count = if loops > 0 {
+            format!(
+                r#"
+    if bp_i == 0:
+        if passes >= {}:
+            break
+        passes += 1"#,
+                loops
+            )
+        } else {
+            String::new()
+        };
 
         let o = format!(
             r#"
 #!/usr/bin/env python3
 
-import gdb
+import r2pipe
 import time
 
-class B(gdb.Breakpoint):
-    def __init__(self, offset, next_offset, delay):
-        self.delay = delay
-        gdb.Breakpoint.__init__(self, f"*{{offset}}", gdb.BP_HARDWARE_BREAKPOINT)
+r2 = r2pipe.open("./{bin}", flags=["-d"])
 
-    def stop(self):
-        {}
+# Flag names are filtered by r2, so frame lines are taken from
+# the unfiltered names of function symbols instead.
+symbols = [
+    (s["vaddr"], s["vaddr"] + s["size"], s["realname"])
+    for s in r2.cmdj("isj")
+    if s["type"] == "FUNC"
+]
 
-        gdb.execute("delete breakpoints")
-        global bp_i
-        bp_i = (bp_i + 1) % {}
-        B(*bps[bp_i])
+def name_at(pc):
+    return next((name for start, end, name in symbols if start <= pc < end), None)
 
-        gdb.execute("bt")
-        time.sleep(self.delay / 1000)
-        return False
+def read_ptr(addr):
+    return int(r2.cmd(f"pv{word_size} @ {{addr}}"), 16)
 
-gdb.execute("set pagination off")
-gdb.execute("set style enabled off")
-gdb.execute("set startup-with-shell off")
+# Breakpoints are hit before the frame pointer is pushed, so the
+# return address to the caller is still on top of the stack.
+def backtrace():
+    regs = r2.cmdj("drj")
+    pcs = [regs["{pc}"], read_ptr(regs["{sp}"])]
+    fp = regs["{fp}"]
+    while fp != 0:
+        pcs.append(read_ptr(fp + {word_size}))
+        fp = read_ptr(fp)
+    return [name for name in map(name_at, pcs) if name is not None]
 
-gdb.execute("starti")
-bp_i = 0
-bps = [
-{}
-]
-B(*bps[bp_i])
-gdb.execute("c")
-"#,
-            symbol_reload,
-            bp_info.len(),
-            breakpoints
+bps = {{
+{breakpoints}
+}}
+for addr in bps:
+    r2.cmd(f"db {{addr}}")
+
+passes = 0
+while True:
+    r2.cmd("dc")
+    regs = r2.cmdj("drj")
+    if regs["{pc}"] not in bps:
+        break
+    bp_i, delay = bps[regs["{pc}"]]{loop_count}{condition}
+    print("\n".join(backtrace()), flush=True)
+    time.sleep(delay / 1000)
+
+r2.cmd("dk 9")
+r2.quit()
+"#
         );
-        let mut file = std::fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open("a_gdb.py")
-            .unwrap();
-        file.write(o.as_bytes()).expect("Can't write GDB script");
+        self.write_script(&o)?;
+
+        Ok(render_commands)
     }
 }
 
 impl FrameConverter for LldbFrameConverter<'_> {
+    fn artifacts(&self) -> &Artifacts {
+        self.artifacts
+    }
+
+    fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    fn script_name(&self) -> String {
+        self.artifacts.name("_lldb.py")
+    }
+
     fn data_section_addr(&self) -> u64 {
-        0x1000
+        self.script.data_addr.unwrap_or(0x1000)
+    }
+
+    fn text_section_addr(&self) -> u64 {
+        self.script.text_addr.unwrap_or(TEXT_SECTION_ADDR)
     }
 
     fn parser(&self) -> &dyn FrameParser {
         self.parser
     }
 
+    /// Breakpoints are all set at once, each auto-continuing after
+    /// drawing its own frame.
+    fn shares_framelines(&self) -> bool {
+        false
+    }
+
+    fn merges_frames(&self) -> bool {
+        self.script.merge_frames
+    }
+
+    /// Configuration of the CodeLLDB extension, creating the process
+    /// with the script, which launches the inferior.
+    fn vscode_launch(&self, bin: &str) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "name": format!("backgif ({})", bin),
+            "type": "lldb",
+            "request": "custom",
+            "targetCreateCommands": [
+                format!("target create ${{workspaceFolder}}/{}", bin)
+            ],
+            "processCreateCommands": [
+                format!("command script import ${{workspaceFolder}}/{}", self.script_name())
+            ]
+        }))
+    }
+
     fn write_dbg_script(
         &self,
         frame_infos: &Vec<FrameInfo>,
@@ -1010,41 +4395,37 @@ impl FrameConverter for LldbFrameConverter<'_> {
         size: u64,
         is_updated: bool,
         bin: &str,
-    ) {
-        let bp_info = frame_infos
-            .iter()
-            .map(|n| (name_to_info.get(&n.last_name).unwrap().addr, n.delay))
-            .collect_vec();
-        println!(
-            "\n{}",
-            "Render automatically with debugger script:".purple().bold()
-        );
-        println!(
-            "{}",
-            format!("lldb ./{bin} --one-line 'command script import a_lldb.py'").bold()
-        );
-        println!(
-            "\n{}",
-            "Render manually with software breakpoints:".purple().bold()
-        );
-        println!(
-            "{}",
-            format!(
-                r#"lldb ./{bin} \
-    --one-line 'settings set use-color false' \
-    --one-line 'settings set show-statusline false' \
-    --one-line 'process launch --disable-aslr true --no-stdio --stop-at-entry' \"#
-            )
-            .bold()
-        );
-        println!(
-            "{}",
-            &bp_info
-                .iter()
-                .map(|(addr, _)| format!("    --one-line 'b *0x{:08x}'", addr))
-                .join(" \\\n")
-                .bold()
-        );
+        loops: u32,
+    ) -> Result<Vec<RenderCommand>, ConvError> {
+        let debugger = &self.script.debugger;
+        let bp_info = to_bp_info(frame_infos, name_to_info, self.merges_frames());
+        let render_commands = vec![
+            RenderCommand::new(
+                "Render automatically with debugger script",
+                format!(
+                    "{debugger} ./{bin} --one-line 'command script import {}'",
+                    self.script_name()
+                ),
+            ),
+            RenderCommand::new(
+                "Render manually with software breakpoints",
+                [
+                    format!("{debugger} ./{bin}"),
+                    String::from("    --one-line 'settings set use-color false'"),
+                    String::from("    --one-line 'settings set show-statusline false'"),
+                    String::from(
+                        "    --one-line 'process launch --disable-aslr true --no-stdio --stop-at-entry'",
+                    ),
+                ]
+                .into_iter()
+                .chain(
+                    bp_info
+                        .iter()
+                        .map(|(addr, _)| format!("    --one-line 'b *0x{:08x}'", addr)),
+                )
+                .join(" \\\n"),
+            ),
+        ];
 
         let breakpoints = bp_info
             .iter()
@@ -1068,37 +4449,80 @@ impl FrameConverter for LldbFrameConverter<'_> {
         // from offset 0, and gets an EIO (Input/output error).
         //
         // As a workaround, this memory must be dumped to a
-        // temporary file on each displayed frame.
+        // temporary file on each displayed frame. Its path is unique
+        // to each debugger process, so that concurrent runs don't
+        // overwrite each other's dumps.
         let symbol_reload = is_updated
             .then(|| {
                 format!(
                     r#"
-    debugger.HandleCommand("target symbols add a2.out")
-    debugger.HandleCommand("memory read --binary --outfile /tmp/mem --count 0x{:08x} 0x{:08x}")
-    debugger.HandleCommand("target symbols add /tmp/mem")
+    debugger.HandleCommand("target symbols add {}")
+    debugger.HandleCommand(f'memory read --binary --outfile "{{mem_path}}" --count 0x{:08x} 0x{:08x}')
+    debugger.HandleCommand(f'target symbols add "{{mem_path}}"')
     "#,
+                    bin,
                     size,
                     self.data_section_addr()
                 )
             })
             .unwrap_or_else(|| String::new());
 
+        let condition = self
+            .script
+            .frame_condition
+            .as_ref()
+            .map(|condition| {
+                format!(
+                    r#"if frame.EvaluateExpression({}).GetValueAsUnsigned() == 0:
+        return False"#,
+                    serde_json::to_string(condition).unwrap()
+                )
+            })
+            .unwrap_or_default();
+
+        // Breakpoints auto-continue, so the process is
+        // killed on the last frame of the last pass.
+        let loop_count = if loops > 0 {
+            format!(
+                r#"
+
+    if extra_args.GetValueForKey("last").GetIntegerValue() == 1:
+        global passes
+        passes += 1
+        if passes >= {}:
+            process = frame.GetThread().GetProcess()
+            process.GetTarget().DeleteAllBreakpoints()
+            process.Kill()
+            restore_terminal()"#,
+                loops
+            )
+        } else {
+            String::new()
+        };
+
         let o = format!(
             r#"
 #!/usr/bin/env python3
 
+import atexit
 import lldb
 import os
 import sys
+import tempfile
 import time
 
+{restore_terminal}
+passes = 0
+deadline = None
+mem_path = {mem_path}
+
 def b(frame, bp_loc, extra_args, dict):
     debugger = frame.GetThread().GetProcess().GetTarget().GetDebugger()
-    {}
+    {}{}
     debugger.HandleCommand("bt")
 
     delay = extra_args.GetValueForKey("delay").GetIntegerValue()
-    time.sleep(delay / 1000)
+    {}{}
 
 def a(debugger, command, ctx, result, dict):
     # https://github.com/llvm/llvm-project/blob/6e3c7b8244e9067721ccd0d786755f2ae9c96a87/lldb/include/lldb/lldb-enumerations.h#L99
@@ -1110,17 +4534,18 @@ def a(debugger, command, ctx, result, dict):
         raise RuntimeError("Process not stopped.")
 
     target = process.GetTarget()
-    for addr, next_addr, delay in [
+    bps = [
 {}
-    ]:
+    ]
+    for i, (addr, next_addr, delay) in enumerate(bps):
         extra_args = lldb.SBStructuredData()
         stream = lldb.SBStream()
-        stream.Print(f'{{{{"delay" : {{delay}}}}}}')
+        stream.Print(f'{{{{"delay" : {{delay}}, "last" : {{int(i == len(bps) - 1)}}}}}}')
         extra_args.SetFromJSON(stream)
 
         bp = target.BreakpointCreateByAddress(addr)
         bp.SetAutoContinue(True)
-        bp.SetScriptCallbackFunction("a_lldb.b", extra_args)
+        bp.SetScriptCallbackFunction("{module}.b", extra_args)
         # FIXME: Unimplemented for Linux x86_64 targets
         # err = bp.SetIsHardware(True)
         # if not bp.IsHardware():
@@ -1133,39 +4558,418 @@ def a(debugger, command, ctx, result, dict):
 def __lldb_init_module(debugger, dict):
     debugger.HandleCommand("settings set use-color false")
     debugger.HandleCommand("settings set show-statusline false")
-    debugger.HandleCommand("command script add -f a_lldb.a a")
+    debugger.HandleCommand("command script add -f {module}.a a")
     debugger.HandleCommand("a")
     "#,
-            symbol_reload, breakpoints
+            symbol_reload,
+            condition,
+            to_py_sleep("delay", "    ", self.script.precise_timing),
+            loop_count,
+            breakpoints,
+            mem_path = {
+                let file_path = match &self.script.lldb_mem_path {
+                    Some(path) => serde_json::to_string(path).unwrap(),
+                    None => format!(
+                        r#"os.path.join(tempfile.gettempdir(), f"{}_{{os.getpid()}}.mem")"#,
+                        self.artifacts.name("_lldb")
+                    ),
+                };
+                // The memfd stays open for the lifetime of the debugger,
+                // which can open it by its path in procfs.
+                if self.script.lldb_memfd {
+                    format!(
+                        r#"f"/proc/{{os.getpid()}}/fd/{{os.memfd_create('backgif')}}" if hasattr(os, "memfd_create") else {}"#,
+                        file_path
+                    )
+                } else {
+                    file_path
+                }
+            },
+            // Script is imported as a module named after its file.
+            module = self.artifacts.name("_lldb"),
+            restore_terminal = PY_RESTORE_TERMINAL,
         );
+        self.write_script(&o)?;
+
+        Ok(render_commands)
+    }
+}
+
+impl FrameConverter for WinDbgFrameConverter<'_> {
+    fn artifacts(&self) -> &Artifacts {
+        self.artifacts
+    }
+
+    fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    fn bin_name(&self) -> String {
+        self.artifacts.name(".exe")
+    }
+
+    /// `clang-cl` names the object file after the source file.
+    fn temp_names(&self) -> Vec<String> {
+        vec![self.artifacts.name(".c"), self.artifacts.name(".obj")]
+    }
+
+    fn script_name(&self) -> String {
+        self.artifacts.name("_windbg.txt")
+    }
+
+    fn parser(&self) -> &dyn FrameParser {
+        self.parser
+    }
+
+    /// Breakpoints are all set at once, so each frame needs its own
+    /// innermost function to be told apart from other frames.
+    fn shares_framelines(&self) -> bool {
+        false
+    }
+
+    fn merges_frames(&self) -> bool {
+        self.script.merge_frames
+    }
+
+    /// Compile the generated C source code as a PE executable.
+    /// Debug info isn't supported, as debuggers would prefer the
+    /// CodeView names over the patched COFF symbol table.
+    fn compile(
+        &self,
+        frame_infos: &[FrameInfo],
+        compiler: &str,
+        cflags: &[&str],
+        start_tmp_name: &str,
+        include_debug_info: bool,
+        verbosity: Verbosity,
+    ) -> Result<(), Box<dyn Error>> {
+        self.write_src_file(frame_infos, start_tmp_name, include_debug_info)?;
+        spawn_tool(
+            Command::new(compiler)
+                .current_dir(&self.artifacts.dir)
+                .args(
+                    CL_COMPILER_ARGS
+                        .iter()
+                        .chain(cflags)
+                        .map(|arg| arg.to_string())
+                        .chain([
+                            format!("/Fe{}", self.bin_name()),
+                            self.artifacts.name(".c"),
+                            String::from("/link"),
+                            format!("/entry:{}", start_tmp_name),
+                        ])
+                        .chain(CL_LINKER_ARGS.iter().map(|arg| arg.to_string())),
+                ),
+            "--compiler-path",
+            verbosity,
+        )?;
+
+        Ok(())
+    }
+
+    fn parse_bin(&self, path: &Path) -> Result<BinInfo, ConvError> {
+        let io_error = |source| ConvError::Io {
+            path: path.to_owned(),
+            source,
+        };
         let mut file = std::fs::OpenOptions::new()
             .read(true)
             .write(true)
-            .create(true)
-            .truncate(true)
-            .open("a_lldb.py")
-            .unwrap();
-        file.write(o.as_bytes()).expect("Can't write LLDB script");
+            .open(path)
+            .map_err(io_error)?;
+        match lief::Binary::from(&mut file) {
+            Some(lief::Binary::PE(pe)) => {
+                let section_offs = [".data", ".text"]
+                    .iter()
+                    .map(|name| {
+                        (
+                            String::from(name.to_owned()),
+                            pe.section_by_name(name)
+                                .map_or(0, |section| section.pointerto_raw_data() as u64),
+                        )
+                    })
+                    .collect();
+                let section_addrs = pe
+                    .sections()
+                    .map(|section| section.virtual_address())
+                    .collect_vec();
+                let imagebase = pe.optional_header().imagebase();
+
+                let symtab_offs = pe.header().pointerto_symbol_table() as u64;
+                if symtab_offs == 0 {
+                    return Err(ConvError::MissingSection {
+                        path: path.to_owned(),
+                        section: "COFF symbol table (`/debug:symtab`)",
+                    });
+                }
+                let symtab_len = pe.header().numberof_symbols() as u64;
+
+                let mut contents = vec![];
+                file.seek(std::io::SeekFrom::Start(0))
+                    .and_then(|_| file.read_to_end(&mut contents))
+                    .map_err(io_error)?;
+                let name_to_info = parse_coff_symbols(
+                    &contents,
+                    symtab_offs,
+                    symtab_len,
+                    &section_addrs,
+                    imagebase,
+                )
+                .ok_or_else(|| ConvError::UnexpectedFormat {
+                    path: path.to_owned(),
+                    expected: "a PE",
+                })?;
+
+                Ok(BinInfo {
+                    build_id_desc_offs: 0,
+                    build_id_desc: vec![],
+                    name_to_info,
+                    section_offs,
+                    size: contents.len() as u64,
+                    bits: match pe.header().machine() {
+                        lief::pe::headers::MachineType::I386 => 32,
+                        _ => 64,
+                    },
+                })
+            }
+            _ => Err(ConvError::UnexpectedFormat {
+                path: path.to_owned(),
+                expected: "a PE",
+            }),
+        }
+    }
+
+    fn write_dbg_script(
+        &self,
+        frame_infos: &Vec<FrameInfo>,
+        name_to_info: &HashMap<String, SymbolInfo>,
+        _size: u64,
+        _is_updated: bool,
+        bin: &str,
+        _loops: u32,
+    ) -> Result<Vec<RenderCommand>, ConvError> {
+        let debugger = &self.script.debugger;
+        let bp_info = to_bp_info(frame_infos, name_to_info, self.merges_frames());
+        let render_commands = vec![
+            RenderCommand::new(
+                "Render automatically with debugger script",
+                format!("{debugger} -g -cf {} {bin}", self.script_name()),
+            ),
+            RenderCommand::new(
+                "Render manually with software breakpoints",
+                format!(
+                    "{debugger} -g -c \"{}; g\" {bin}",
+                    bp_info
+                        .iter()
+                        .map(|(addr, _)| format!("bp 0x{:08x}", addr))
+                        .join("; ")
+                ),
+            ),
+        ];
+
+        // Unlike GDB, breakpoint commands can't recreate the next
+        // hardware breakpoint without nesting quoted commands, so
+        // software breakpoints are used instead.
+        let breakpoints = bp_info
+            .iter()
+            .map(|(addr, delay)| {
+                let render = format!("k; .sleep {}", delay * 10);
+                let render = match &self.script.frame_condition {
+                    Some(condition) => format!(".if ({}) {{ {} }}", condition, render),
+                    None => render,
+                };
+                format!("bp 0x{:08x} \"{}; g\"", addr, render)
+            })
+            .join("\n");
+
+        let o = format!(
+            r#"
+.lines -d
+.prompt_allow -reg -ea -dis -src -sym
+{}
+g
+"#,
+            breakpoints
+        );
+        self.write_script(&o)?;
+
+        Ok(render_commands)
     }
 }
 
-fn spawn(cmd: &mut Command) -> Result<(), Box<dyn Error>> {
-    println!(
-        "Running `{} {}`.",
-        cmd.get_program().to_str().unwrap(),
-        cmd.get_args().map(|a| a.to_str().unwrap()).join(" ")
+/// Compile Python `script` without running it, to catch syntax errors
+/// before running it in the debugger. Frame lines are never embedded in
+/// scripts, only addresses, delays, and JSON-encoded string options.
+pub fn check_script(script: &Path, verbosity: Verbosity) -> Result<(), ConvError> {
+    // Syntax errors are reported on the last line of stderr,
+    // as `line:column:message`.
+    let checked = spawn(
+        Command::new("python3")
+            .args([
+                "-c",
+                r#"import sys
+try:
+    compile(open(sys.argv[1]).read(), sys.argv[1], "exec")
+except SyntaxError as e:
+    sys.exit(f"{e.lineno}:{e.offset}:{e.msg}")"#,
+            ])
+            .arg(script),
+        verbosity,
     );
+    let Err(e) = checked else {
+        return Ok(());
+    };
+    let message = e.to_string();
+    let syntax_error = message.lines().last().and_then(|last| {
+        let mut fields = last.splitn(3, ':');
+        let line = fields.next()?.parse().ok()?;
+        let column = fields.next()?.parse().ok()?;
+        Some(((line, column), fields.next()?.to_owned()))
+    });
+    let (location, message) = match syntax_error {
+        Some((location, message)) => (Some(location), message),
+        None => (None, message),
+    };
+
+    Err(ConvError::InvalidScript {
+        path: script.to_owned(),
+        location,
+        message,
+    })
+}
+
+/// Captured output of a command that exited successfully.
+#[derive(Debug)]
+pub struct Output {
+    pub status: ExitStatus,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Informational output printed while converting, such as commands
+/// being run. Errors are printed regardless.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+}
+
+/// Minimum time between progress reports.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Count of items processed by a long step, such as decoding frames,
+/// reported on stderr at most every `PROGRESS_INTERVAL`, unless
+/// `verbosity` is quiet or stderr isn't a terminal. Items can be
+/// counted from parallel iterators. The report line is cleared when
+/// dropped.
+pub struct Progress {
+    label: &'static str,
+    total: Option<usize>,
+    done: AtomicUsize,
+    /// Time of the last report, if any.
+    reported: Mutex<Option<Instant>>,
+    enabled: bool,
+}
+
+impl Progress {
+    pub fn new(label: &'static str, total: Option<usize>, verbosity: Verbosity) -> Self {
+        Progress {
+            label,
+            total,
+            done: AtomicUsize::new(0),
+            reported: Mutex::new(None),
+            enabled: verbosity != Verbosity::Quiet && std::io::stderr().is_terminal(),
+        }
+    }
+
+    pub fn inc(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if !self.enabled {
+            return;
+        }
+
+        // Items counted while another thread reports aren't waited on.
+        let Ok(mut reported) = self.reported.try_lock() else {
+            return;
+        };
+        if reported.is_some_and(|last| last.elapsed() < PROGRESS_INTERVAL) {
+            return;
+        }
+        *reported = Some(Instant::now());
+        match self.total {
+            Some(total) => eprint!("\r\x1b[2K{}: {}/{}", self.label, done, total),
+            None => eprint!("\r\x1b[2K{}: {}", self.label, done),
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.enabled
+            && self
+                .reported
+                .get_mut()
+                .is_ok_and(|reported| reported.is_some())
+        {
+            eprint!("\r\x1b[2K");
+        }
+    }
+}
+
+/// Run `cmd`, printing it along with its stdout, unless `verbosity`
+/// is quiet. If it fails, the error message includes its stderr, such
+/// as compiler diagnostics.
+fn spawn(cmd: &mut Command, verbosity: Verbosity) -> Result<Output, Box<dyn Error>> {
+    let program = cmd.get_program().to_str().unwrap().to_owned();
+    if verbosity != Verbosity::Quiet {
+        println!(
+            "Running `{} {}`.",
+            program,
+            cmd.get_args().map(|a| a.to_str().unwrap()).join(" ")
+        );
+    }
     let child = cmd.stderr(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
     let output = child.wait_with_output()?;
-    if output.status.success() {
-        let raw_output = String::from_utf8(output.stdout)?;
-        if !raw_output.is_empty() {
-            println!("{raw_output}");
-        }
-        Ok(())
-    } else {
-        let raw_err = String::from_utf8(output.stderr)?;
-        eprintln!("{raw_err}");
-        Err("Compile error".into())
+    let output = Output {
+        status: output.status,
+        stdout: String::from_utf8(output.stdout)?,
+        stderr: String::from_utf8(output.stderr)?,
+    };
+    if !output.status.success() {
+        return Err(format!(
+            "`{}` failed with {}:\n{}",
+            program,
+            output.status,
+            output.stderr.trim_end()
+        )
+        .into());
+    }
+    if verbosity != Verbosity::Quiet && !output.stdout.is_empty() {
+        println!("{}", output.stdout);
     }
+
+    Ok(output)
+}
+
+/// Spawn a build tool, like `spawn()`, but explaining how to provide
+/// the tool with `flag` if it isn't found.
+fn spawn_tool(
+    cmd: &mut Command,
+    flag: &str,
+    verbosity: Verbosity,
+) -> Result<Output, Box<dyn Error>> {
+    let program = cmd.get_program().to_str().unwrap().to_owned();
+    spawn(cmd, verbosity).map_err(|e| {
+        if let Some(io_err) = e.downcast_ref::<std::io::Error>()
+            && io_err.kind() == std::io::ErrorKind::NotFound
+        {
+            return format!(
+                "{} not found in PATH; install it or pass `{}`",
+                program, flag
+            )
+            .into();
+        }
+
+        e
+    })
 }
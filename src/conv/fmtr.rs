@@ -1,109 +1,839 @@
 //! Frame formatting types.
 
+use crate::conv::kdtree::KdTree;
+use base64::Engine;
+use clap::ValueEnum;
+use log::debug;
 use palette::color_difference::Ciede2000;
 use palette::convert::FromColorUnclamped;
 use palette::{Lab, Srgb};
 use serde_json::Value;
-use std::cell::RefCell;
 use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-pub trait FrameFormatter {
+/// Pre-computed color mappings of emoji codepoints, used
+/// unless another palette is passed.
+const EMOJI_PALETTE: &str = include_str!("../../bgr_to_emoji.json");
+
+/// Processed color mappings, to skip parsing and converting
+/// colors of the emoji palette on each run, in the cache directory.
+const EMOJI_PALETTE_CACHE_NAME: &str = "bgr_to_emoji.bin";
+
+/// Lookup cache format version, to be bumped when the layout of
+/// `LookupCache` or how colors are matched changes.
+const LOOKUP_CACHE_VERSION: u32 = 1;
+
+/// Format version, palette hash, and RGB hex values to the closest
+/// palette entry, along with their color difference.
+type LookupCache = (u32, u64, HashMap<String, (String, f32)>);
+
+/// Palette entries closest by euclidean distance in CIE L*a*b*,
+/// among which the closest by CIEDE2000 difference is picked.
+/// With CIE76 differences, the closest of them is already the best.
+const EMOJI_CANDIDATES: usize = 32;
+
+/// Lower bound of the CIEDE2000 difference between colors in the sRGB
+/// gamut, as a fraction of their euclidean distance in CIE L*a*b*.
+/// Chroma and hue differences are divided by at most 1 + 0.045 * 140
+/// (the largest mean chroma), and the rotation term removes at most
+/// sin(60°) / 2 of their squared sum, so that the difference is at
+/// least ~0.0501 times the distance, rounded down for precision.
+const CIEDE2000_DISTANCE_BOUND: f32 = 0.049;
+
+/// Binary palette cache format version, to be bumped when
+/// the layout of `PaletteCache` changes.
+const PALETTE_CACHE_VERSION: u32 = 1;
+
+/// RGB hex value, CIE L*a*b* components, and UTF-8 emoji codepoint.
+type PaletteCacheEntry = (String, [f32; 3], String);
+
+/// Format version, palette hash, and processed entries.
+type PaletteCache = (u32, u64, Vec<PaletteCacheEntry>);
+
+/// Color differences of matched frame dots, in units of the
+/// formatter's color metric.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MatchStats {
+    pub count: u64,
+    pub min: f32,
+    pub max: f32,
+    pub sum: f64,
+}
+
+impl MatchStats {
+    fn record(&mut self, diff: f32) {
+        if self.count == 0 || diff < self.min {
+            self.min = diff;
+        }
+        if self.count == 0 || diff > self.max {
+            self.max = diff;
+        }
+        self.count += 1;
+        self.sum += diff as f64;
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.sum / self.count.max(1) as f64
+    }
+}
+
+/// Difference between colors, used to match colors against a palette.
+#[derive(ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorMetric {
+    /// CIEDE2000 difference in CIE L*a*b*, accurate but slow
+    #[default]
+    Ciede2000,
+
+    /// Euclidean distance in CIE L*a*b*, faster but less accurate
+    /// for saturated colors
+    Cie76,
+
+    /// Euclidean distance in RGB weighted by the mean red component
+    /// ("redmean"), much faster and often good enough for pixel art
+    WeightedRgb,
+}
+
+impl ColorMetric {
+    /// Difference between colors of CIE L*a*b* `a` and `b`, for
+    /// metrics other than weighted RGB.
+    fn lab_difference(self, a: Lab, b: Lab) -> f32 {
+        match self {
+            ColorMetric::Ciede2000 => a.difference(b),
+            ColorMetric::Cie76 | ColorMetric::WeightedRgb => {
+                ((a.l - b.l).powi(2) + (a.a - b.a).powi(2) + (a.b - b.b).powi(2)).sqrt()
+            }
+        }
+    }
+}
+
+/// Frame dots and lines of a renderer, shared by threads that
+/// format frames in parallel.
+pub trait FrameFormatter: Sync {
     fn blank(&self) -> &str;
 
     fn placeholder(&self) -> &str;
 
     fn to_framedot(&self, rgba: Option<Vec<u8>>) -> String;
 
+    /// Frame dot replaced by up to `dot_width()` characters of
+    /// overlay `text`.
+    fn to_framedot_text(&self, text: &str) -> String;
+
+    /// Number of terminal columns taken by each frame dot.
+    fn dot_width(&self) -> usize {
+        2
+    }
+
     fn to_frameline_at_origin(&self, name: &String, clear_line: bool) -> String;
 
     fn to_frameline(&self, name: &String) -> String;
+
+    /// Width and height of the cell of pixels drawn by each frame dot.
+    fn cell_size(&self) -> (usize, usize) {
+        (1, 1)
+    }
+
+    /// Frame dot drawing the colors `rgbas` of a cell of pixels,
+    /// sized by `cell_size()`, in row-major order.
+    fn to_framedot_cell(&self, rgbas: &[[u8; 4]]) -> String {
+        self.to_framedot(Some(rgbas[0].to_vec()))
+    }
+
+    /// Number of pixel rows drawn by each frame line.
+    fn rows_per_line(&self) -> usize {
+        self.cell_size().1
+    }
+
+    /// Whether frame dots aren't drawn one per pixel, so they can't be
+    /// matched once per palette entry or replaced by overlay text.
+    fn groups_pixels(&self) -> bool {
+        self.cell_size() != (1, 1) || self.rows_per_line() != 1
+    }
+
+    /// Single frame dot drawing all `w`x`h` colors `frame_rgba` of a
+    /// frame, for formatters that don't split frames into lines.
+    fn whole_frame(&self, _frame_rgba: &[Vec<[u8; 4]>], _w: usize, _h: usize) -> Option<String> {
+        None
+    }
+
+    /// Frame dots of a frame line drawing up to `rows_per_line()`
+    /// rows of colors `lines`, grouped in cells of pixels. Cells past
+    /// the bottom or right edges are padded with transparent pixels.
+    fn to_framedots_band(&self, lines: &[Vec<[u8; 4]>]) -> Vec<String> {
+        let (cell_w, cell_h) = self.cell_size();
+        (0..lines[0].len())
+            .step_by(cell_w)
+            .map(|x| {
+                let cell = (0..cell_h)
+                    .flat_map(|dy| {
+                        (x..x + cell_w).map(move |x| {
+                            lines
+                                .get(dy)
+                                .and_then(|line| line.get(x))
+                                .map_or([0; 4], |rgba| *rgba)
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                self.to_framedot_cell(&cell)
+            })
+            .collect()
+    }
+
+    /// Differences of colors matched since the last call, for
+    /// formatters that approximate colors with a palette.
+    fn take_match_stats(&self) -> Option<MatchStats> {
+        None
+    }
+
+    /// RGB components of the palette color approximating `rgba`, for
+    /// formatters that approximate colors with a palette. Unlike
+    /// `to_framedot()`, the match isn't recorded in match stats.
+    fn quantize(&self, _rgba: &[u8; 4]) -> Option<[u8; 3]> {
+        None
+    }
+
+    /// Single frame line drawing all lines `names` of a frame.
+    fn to_frameline_rows(&self, names: &[String], clear_line: bool) -> String {
+        self.to_frameline_at_origin(&names.concat(), clear_line)
+    }
+
+    /// Whether frame lines are always drawn at their absolute row with
+    /// `to_frameline_at_row()`, regardless of row order.
+    fn positions_rows(&self) -> bool {
+        false
+    }
+
+    /// Frame line drawn at an absolute `row` of a frame with `height`
+    /// lines, for frames whose lines aren't printed top to bottom.
+    fn to_frameline_at_row(
+        &self,
+        name: &String,
+        _row: usize,
+        _height: usize,
+        at_origin: bool,
+        clear_line: bool,
+    ) -> String {
+        if at_origin {
+            self.to_frameline_at_origin(name, clear_line)
+        } else {
+            self.to_frameline(name)
+        }
+    }
 }
 
 pub struct EmojiFrameFormatter {
-    /// RGB hex values to closest UTF-8 emoji codepoint, based on
-    /// smallest color difference against pre-computed
-    /// color mappings in `bgr_to_emoji.json`
-    pub cache: RefCell<HashMap<String, String>>,
+    /// RGB hex values to RGB hex value of the closest palette entry,
+    /// based on smallest color difference against pre-computed
+    /// color mappings in the emoji palette, along with
+    /// their color difference
+    pub cache: Mutex<HashMap<String, (String, f32)>>,
+
+    /// Color differences of each lookup
+    pub stats: Mutex<MatchStats>,
+
+    /// Emoji drawn for transparent frame dots, instead of `🫥`
+    pub blank: Option<String>,
+
+    /// File of colors matched by previous runs, read on creation and
+    /// written back on drop, unless persisting caches is disabled
+    lookup_cache_path: Option<PathBuf>,
+
+    /// Number of colors read from the lookup cache, to skip writing
+    /// it back if no colors were matched since
+    loaded_lookups: usize,
+
+    /// Hash of the emoji palette, invalidating caches built from
+    /// another palette
+    palette_hash: u64,
+
+    /// Difference used to match colors against palette entries,
+    /// which matched colors in the lookup cache depend on
+    metric: ColorMetric,
 
     /// RGB hex values to CIE L*a*b*
     pub rgb_to_lab: HashMap<String, Lab>,
 
     /// RGB hex values to UTF-8 emoji codepoints
     pub rgb_to_emoji: HashMap<String, String>,
+
+    /// CIE L*a*b* of palette entries, indexing `tree_rgbs`
+    tree: KdTree,
+
+    /// RGB hex values of palette entries, in tree index order
+    tree_rgbs: Vec<String>,
+
+    /// RGB components of palette entries, in tree index order
+    tree_values: Vec<[u8; 3]>,
+}
+
+/// Fixed palette of terminal color codes, matched by smallest
+/// color difference.
+pub struct CodePalette {
+    /// RGB values to closest color code, along with their
+    /// color difference
+    pub cache: Mutex<HashMap<[u8; 3], (u8, f32)>>,
+
+    /// Color differences of each lookup
+    pub stats: Mutex<MatchStats>,
+
+    /// Color codes to CIE L*a*b*
+    pub code_to_lab: Vec<(u8, Lab)>,
+
+    /// Color codes to RGB components
+    pub code_to_rgb: HashMap<u8, [u8; 3]>,
+
+    /// Difference used to match colors against color codes
+    pub metric: ColorMetric,
+}
+
+pub struct Ansi256FrameFormatter {
+    /// Formatter of frame lines, which are drawn with the same
+    /// escape sequences regardless of how colors are encoded.
+    pub lines: TrueColorFrameFormatter,
+
+    /// xterm-256 palette indexes, skipping the 16 system colors,
+    /// which are redefined by most themes
+    pub palette: CodePalette,
+}
+
+pub struct Ansi16FrameFormatter {
+    /// Formatter of frame lines, which are drawn with the same
+    /// escape sequences regardless of how colors are encoded.
+    pub lines: TrueColorFrameFormatter,
+
+    /// SGR background codes of the 8 normal and 8 bright colors
+    pub palette: CodePalette,
+}
+
+pub struct HalfBlockFrameFormatter {
+    /// Formatter of frame lines, which are drawn with the same
+    /// escape sequences regardless of how colors are encoded.
+    pub lines: TrueColorFrameFormatter,
+}
+
+pub struct BrailleFrameFormatter {
+    /// Formatter of frame lines, which are drawn with the same
+    /// escape sequences regardless of how colors are encoded.
+    pub lines: TrueColorFrameFormatter,
+
+    /// Luminance above which a pixel raises its braille dot
+    pub threshold: u8,
+}
+
+pub struct AsciiFrameFormatter {
+    /// Formatter of frame lines, which are drawn with the same
+    /// escape sequences regardless of how colors are encoded.
+    pub lines: TrueColorFrameFormatter,
+
+    /// Characters from darkest to brightest, to pick by luminance
+    pub ramp: Vec<char>,
+}
+
+pub struct SixelFrameFormatter {
+    /// Formatter of frame lines, which are drawn with the same
+    /// escape sequences regardless of how colors are encoded.
+    pub lines: TrueColorFrameFormatter,
+}
+
+pub struct KittyFrameFormatter {
+    /// Formatter of frame lines, which are drawn with the same
+    /// escape sequences regardless of how colors are encoded.
+    pub lines: TrueColorFrameFormatter,
+}
+
+pub struct TrueColorFrameFormatter {
+    /// Only use absolute cursor positioning and SGR color sequences,
+    /// for terminals and multiplexers that mangle other sequences.
+    pub minimal_escapes: bool,
+
+    /// Draw frame dots as full blocks in the foreground color,
+    /// instead of filling the background, for terminals with
+    /// transparent or themed backgrounds.
+    pub fg_glyph: bool,
+
+    /// Double-width glyph drawn for transparent frame dots,
+    /// instead of spacing
+    pub blank: Option<String>,
+
+    /// Hide the cursor on each frame line, so that it doesn't
+    /// flicker over frames.
+    pub hide_cursor: bool,
 }
 
-pub struct TrueColorFrameFormatter;
+/// Double-width spacing filled with a background color as a frame dot.
+const DOT_SPACING: &str = "  ";
 
 impl EmojiFrameFormatter {
-    pub fn new() -> Self {
+    /// Formatter matching colors against the emoji palette at
+    /// `palette_path`, or against the embedded palette if `None`.
+    /// If `persist` is set, processed palette entries and matched
+    /// colors are cached on disk between runs, where matched colors
+    /// are kept apart for each `metric`. Errors name the palette and
+    /// the malformed entry, if any.
+    pub fn new(
+        palette_path: Option<&Path>,
+        persist: bool,
+        metric: ColorMetric,
+    ) -> Result<Self, String> {
         let mut this = Self {
-            cache: RefCell::new(HashMap::new()),
+            cache: Mutex::new(HashMap::new()),
+            stats: Mutex::new(MatchStats::default()),
+            blank: None,
+            lookup_cache_path: None,
+            loaded_lookups: 0,
+            palette_hash: 0,
+            metric,
             rgb_to_lab: HashMap::new(),
             rgb_to_emoji: HashMap::new(),
+            tree: KdTree::new(&[]),
+            tree_rgbs: vec![],
+            tree_values: vec![],
         };
 
-        let json: Value = serde_json::from_str(
-            std::fs::read_to_string("bgr_to_emoji.json")
-                .unwrap()
-                .as_str(),
-        )
-        .unwrap();
-        for v in json.as_array().unwrap() {
-            let rgb = format!(
-                "{:02x}{:02x}{:02x}",
-                v[2].as_u64().unwrap(),
-                v[1].as_u64().unwrap(),
-                v[0].as_u64().unwrap()
-            );
-            let lab: Lab = Lab::from_color_unclamped(Srgb::new(
-                v[2].as_u64().unwrap() as f32 / 255.0,
-                v[1].as_u64().unwrap() as f32 / 255.0,
-                v[0].as_u64().unwrap() as f32 / 255.0,
-            ));
-            this.rgb_to_lab.insert(rgb.to_owned(), lab);
-            this.rgb_to_emoji
-                .insert(rgb, String::from(v[3].as_str().unwrap()));
-        }
-
-        this
+        let palette_name =
+            palette_path.map_or(String::from("embedded"), |path| path.display().to_string());
+        let json_str = match palette_path {
+            Some(path) => std::fs::read_to_string(path)
+                .map_err(|e| format!("Can't read emoji palette {}: {}", palette_name, e))?,
+            None => String::from(EMOJI_PALETTE),
+        };
+        let mut hasher = DefaultHasher::new();
+        json_str.hash(&mut hasher);
+        let json_hash = hasher.finish();
+        this.palette_hash = json_hash;
+        if persist {
+            let metric = metric.to_possible_value().unwrap();
+            this.lookup_cache_path = lookup_cache_dir().map(|dir| {
+                dir.join(format!(
+                    "emoji_{:016x}_{}.bin",
+                    json_hash,
+                    metric.get_name()
+                ))
+            });
+            this.load_lookups();
+        }
+
+        if let Some(entries) = persist.then(|| Self::load_cache(json_hash)).flatten() {
+            for (rgb, [l, a, b], emoji) in entries {
+                this.rgb_to_lab.insert(rgb.to_owned(), Lab::new(l, a, b));
+                this.rgb_to_emoji.insert(rgb, emoji);
+            }
+            this.build_tree();
+
+            return Ok(this);
+        }
+
+        let json: Value = serde_json::from_str(json_str.as_str())
+            .map_err(|e| format!("Can't parse emoji palette {}: {}", palette_name, e))?;
+        let entries = json
+            .as_array()
+            .filter(|entries| !entries.is_empty())
+            .ok_or_else(|| {
+                format!(
+                    "Emoji palette {} must be a non-empty list of entries.",
+                    palette_name
+                )
+            })?;
+        for (i, v) in entries.iter().enumerate() {
+            // Each entry is `[b, g, r, "emoji"]`.
+            let component = |c: usize| v[c].as_u64().and_then(|c| u8::try_from(c).ok());
+            let (Some(b), Some(g), Some(r), Some(emoji)) =
+                (component(0), component(1), component(2), v[3].as_str())
+            else {
+                return Err(format!(
+                    "Invalid entry {} in emoji palette {}, expected `[b, g, r, \"emoji\"]`, got {}.",
+                    i, palette_name, v
+                ));
+            };
+            let rgb = format!("{:02x}{:02x}{:02x}", r, g, b);
+            this.rgb_to_lab.insert(rgb.to_owned(), to_lab(&[r, g, b]));
+            this.rgb_to_emoji.insert(rgb, String::from(emoji));
+        }
+        if persist {
+            this.save_cache(json_hash);
+        }
+        this.build_tree();
+
+        Ok(this)
+    }
+
+    fn build_tree(&mut self) {
+        let (rgbs, points): (Vec<_>, Vec<_>) = self
+            .rgb_to_lab
+            .iter()
+            .map(|(rgb, lab)| (rgb.to_owned(), [lab.l, lab.a, lab.b]))
+            .unzip();
+        self.tree = KdTree::new(&points);
+        self.tree_values = rgbs
+            .iter()
+            .map(|rgb| {
+                let rgb = u32::from_str_radix(rgb, 16).unwrap().to_be_bytes();
+                [rgb[1], rgb[2], rgb[3]]
+            })
+            .collect();
+        self.tree_rgbs = rgbs;
+    }
+
+    /// Read processed palette entries, unless the cache was written
+    /// by another format version or for another palette.
+    fn load_cache(json_hash: u64) -> Option<Vec<PaletteCacheEntry>> {
+        let bytes = std::fs::read(lookup_cache_dir()?.join(EMOJI_PALETTE_CACHE_NAME)).ok()?;
+        let (version, hash, entries): PaletteCache = bincode::deserialize(&bytes).ok()?;
+        if version != PALETTE_CACHE_VERSION || hash != json_hash {
+            debug!("Stale palette cache v{} hash {:016x}.", version, hash);
+            return None;
+        }
+
+        Some(entries)
+    }
+
+    fn save_cache(&self, json_hash: u64) {
+        let entries = self
+            .rgb_to_lab
+            .iter()
+            .map(|(rgb, lab)| {
+                (
+                    rgb.to_owned(),
+                    [lab.l, lab.a, lab.b],
+                    self.rgb_to_emoji.get(rgb).unwrap().to_owned(),
+                )
+            })
+            .collect();
+        let cache: PaletteCache = (PALETTE_CACHE_VERSION, json_hash, entries);
+        let Some(dir) = lookup_cache_dir() else {
+            return;
+        };
+        if let Err(e) = bincode::serialize(&cache)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| {
+                std::fs::create_dir_all(&dir)
+                    .and_then(|_| std::fs::write(dir.join(EMOJI_PALETTE_CACHE_NAME), bytes))
+                    .map_err(|e| e.to_string())
+            })
+        {
+            debug!("Can't write palette cache: {}", e);
+        }
+    }
+
+    /// Read colors matched by previous runs, unless the cache was
+    /// written by another format version or for another palette.
+    fn load_lookups(&mut self) {
+        let Some(path) = &self.lookup_cache_path else {
+            return;
+        };
+        let Some((version, hash, lookups)) = std::fs::read(path)
+            .ok()
+            .and_then(|bytes| bincode::deserialize::<LookupCache>(&bytes).ok())
+        else {
+            return;
+        };
+        if version != LOOKUP_CACHE_VERSION || hash != self.palette_hash {
+            debug!("Stale lookup cache v{} hash {:016x}.", version, hash);
+            return;
+        }
+
+        debug!("Read {} lookups from {}.", lookups.len(), path.display());
+        self.loaded_lookups = lookups.len();
+        self.cache = Mutex::new(lookups);
+    }
+
+    /// Check that `blank` is a single printable emoji.
+    pub fn validate_blank(blank: &str) -> Result<(), String> {
+        if blank.chars().any(char::is_control) {
+            return Err(String::from("contains control characters"));
+        }
+        match blank.graphemes(true).count() {
+            1 => Ok(()),
+            count => Err(format!("expected a single grapheme, got {}", count)),
+        }
     }
 
     pub fn lookup(&self, rgba: Vec<u8>) -> String {
+        let (best_rgb, diff) = self.closest_rgb(&rgba);
+        self.stats.lock().unwrap().record(diff);
+
+        self.rgb_to_emoji.get(&best_rgb).unwrap().to_owned()
+    }
+
+    /// RGB hex value of the palette entry closest to `rgba`, along
+    /// with their color difference.
+    fn closest_rgb(&self, rgba: &[u8]) -> (String, f32) {
         let candidate_rgb = format!("{:02x}{:02x}{:02x}", rgba[0], rgba[1], rgba[2]);
-        if self.cache.borrow().contains_key(&candidate_rgb) {
-            return self.cache.borrow().get(&candidate_rgb).unwrap().to_owned();
-        }
-
-        let candidate_lab: Lab = Lab::from_color_unclamped(Srgb::new(
-            rgba[0] as f32 / 255.0,
-            rgba[1] as f32 / 255.0,
-            rgba[2] as f32 / 255.0,
-        ));
-        let mut min_diff = f32::MAX;
-        let mut best_rgb = &candidate_rgb;
-        for (rgb, lab) in self.rgb_to_lab.iter() {
-            let diff = lab.difference(candidate_lab);
-            if min_diff > diff {
-                min_diff = diff;
-                best_rgb = rgb;
+        if let Some(cached) = self.cache.lock().unwrap().get(&candidate_rgb) {
+            return cached.to_owned();
+        }
+
+        let (best, min_diff) = match self.metric {
+            ColorMetric::WeightedRgb => nearest(0..self.tree_values.len(), |&i| {
+                weighted_rgb_difference(rgba, &self.tree_values[i])
+            }),
+            ColorMetric::Ciede2000 | ColorMetric::Cie76 => {
+                let lab = to_lab(rgba);
+                let k = match self.metric {
+                    ColorMetric::Ciede2000 => EMOJI_CANDIDATES,
+                    _ => 1,
+                };
+                let lab_difference = |metric: ColorMetric, i: usize| {
+                    let candidate_lab = self.rgb_to_lab.get(&self.tree_rgbs[i]).unwrap();
+                    metric.lab_difference(*candidate_lab, lab)
+                };
+                let candidates = self.tree.nearest([lab.l, lab.a, lab.b], k);
+                let best = nearest(candidates.iter().copied(), |&i| {
+                    lab_difference(self.metric, i)
+                });
+                // Other entries are at least as far as the farthest
+                // candidate, so they can only have a smaller CIEDE2000
+                // difference if that distance is within the bound.
+                match (best, candidates.last()) {
+                    (Some((_, min_diff)), Some(&farthest))
+                        if k > 1
+                            && candidates.len() == k
+                            && lab_difference(ColorMetric::Cie76, farthest)
+                                * CIEDE2000_DISTANCE_BOUND
+                                < min_diff =>
+                    {
+                        nearest(0..self.tree_rgbs.len(), |&i| lab_difference(self.metric, i))
+                    }
+                    (best, _) => best,
+                }
+            }
+        }
+        .unwrap();
+        let best_rgb = &self.tree_rgbs[best];
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(candidate_rgb, (best_rgb.to_owned(), min_diff));
+
+        (best_rgb.to_owned(), min_diff)
+    }
+}
+
+impl Drop for EmojiFrameFormatter {
+    /// Write back colors matched since the lookup cache was read.
+    fn drop(&mut self) {
+        let Some(path) = &self.lookup_cache_path else {
+            return;
+        };
+        let Ok(lookups) = self.cache.get_mut() else {
+            return;
+        };
+        if lookups.len() == self.loaded_lookups {
+            return;
+        }
+
+        let cache: LookupCache = (
+            LOOKUP_CACHE_VERSION,
+            self.palette_hash,
+            std::mem::take(lookups),
+        );
+        if let Err(e) = bincode::serialize(&cache)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| {
+                path.parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|_| std::fs::write(path, bytes))
+                    .map_err(|e| e.to_string())
+            })
+        {
+            debug!("Can't write lookup cache: {}", e);
+        }
+    }
+}
+
+/// Directory of caches kept between runs, under `XDG_CACHE_HOME`
+/// or else `~/.cache`.
+fn lookup_cache_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .map(|dir| dir.join("backgif"))
+}
+
+/// CIE L*a*b* of the RGB components of `rgba`.
+fn to_lab(rgba: &[u8]) -> Lab {
+    Lab::from_color_unclamped(Srgb::new(
+        rgba[0] as f32 / 255.0,
+        rgba[1] as f32 / 255.0,
+        rgba[2] as f32 / 255.0,
+    ))
+}
+
+/// Difference between the RGB components of `a` and `b`, weighted
+/// by their mean red component to approximate human perception.
+///
+/// See: <https://www.compuphase.com/cmetric.htm>
+fn weighted_rgb_difference(a: &[u8], b: &[u8]) -> f32 {
+    let r_mean = (a[0] as f32 + b[0] as f32) / 2.0;
+    let [dr, dg, db] = [0, 1, 2].map(|i| a[i] as f32 - b[i] as f32);
+    ((2.0 + r_mean / 256.0) * dr * dr + 4.0 * dg * dg + (2.0 + (255.0 - r_mean) / 256.0) * db * db)
+        .sqrt()
+}
+
+/// Key in `keys` of the closest color, by color difference `diff`,
+/// along with their color difference.
+fn nearest<K>(keys: impl Iterator<Item = K>, diff: impl Fn(&K) -> f32) -> Option<(K, f32)> {
+    let mut best = None;
+    for key in keys {
+        let diff = diff(&key);
+        if best.as_ref().is_none_or(|(_, min_diff)| *min_diff > diff) {
+            best = Some((key, diff));
+        }
+    }
+
+    best
+}
+
+/// RGB components of xterm-256 palette index `idx`, from either
+/// the 6x6x6 color cube or the grayscale ramp that follow the
+/// 16 system colors.
+fn xterm_rgb(idx: u8) -> [u8; 3] {
+    const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    match idx {
+        16..=231 => {
+            let i = idx - 16;
+            [
+                CUBE_LEVELS[(i / 36) as usize],
+                CUBE_LEVELS[(i / 6 % 6) as usize],
+                CUBE_LEVELS[(i % 6) as usize],
+            ]
+        }
+        232..=255 => [8 + 10 * (idx - 232); 3],
+        _ => panic!("System color {} has no fixed value", idx),
+    }
+}
+
+/// SGR background codes and RGB components of the 8 normal and
+/// 8 bright colors. Since each terminal defines its own values, normal
+/// colors are approximated by the 3-bit primaries at full intensity,
+/// and bright colors by their tints.
+const ANSI16_COLORS: [(u8, [u8; 3]); 16] = [
+    (40, [0, 0, 0]),
+    (41, [255, 0, 0]),
+    (42, [0, 255, 0]),
+    (43, [255, 255, 0]),
+    (44, [0, 0, 255]),
+    (45, [255, 0, 255]),
+    (46, [0, 255, 255]),
+    (47, [192, 192, 192]),
+    (100, [128, 128, 128]),
+    (101, [255, 128, 128]),
+    (102, [128, 255, 128]),
+    (103, [255, 255, 128]),
+    (104, [128, 128, 255]),
+    (105, [255, 128, 255]),
+    (106, [128, 255, 255]),
+    (107, [255, 255, 255]),
+];
+
+impl CodePalette {
+    pub fn new(code_to_rgb: impl Iterator<Item = (u8, [u8; 3])>, metric: ColorMetric) -> Self {
+        let code_to_rgb: Vec<(u8, [u8; 3])> = code_to_rgb.collect();
+        Self {
+            cache: Mutex::new(HashMap::new()),
+            stats: Mutex::new(MatchStats::default()),
+            code_to_lab: code_to_rgb
+                .iter()
+                .map(|(code, rgb)| (*code, to_lab(rgb)))
+                .collect(),
+            code_to_rgb: code_to_rgb.into_iter().collect(),
+            metric,
+        }
+    }
+
+    pub fn lookup(&self, rgba: &[u8]) -> u8 {
+        let (code, diff) = self.closest_code(rgba);
+        self.stats.lock().unwrap().record(diff);
+
+        code
+    }
+
+    /// RGB components of the palette color closest to `rgba`.
+    pub fn quantize(&self, rgba: &[u8]) -> [u8; 3] {
+        self.code_to_rgb[&self.closest_code(rgba).0]
+    }
+
+    /// Color code closest to `rgba`, along with their color difference.
+    fn closest_code(&self, rgba: &[u8]) -> (u8, f32) {
+        let candidate_rgb = [rgba[0], rgba[1], rgba[2]];
+        if let Some(cached) = self.cache.lock().unwrap().get(&candidate_rgb) {
+            return *cached;
+        }
+
+        let (best_code, min_diff) = match self.metric {
+            ColorMetric::WeightedRgb => {
+                nearest(self.code_to_lab.iter().map(|(code, _)| *code), |code| {
+                    weighted_rgb_difference(rgba, &self.code_to_rgb[code])
+                })
+            }
+            ColorMetric::Ciede2000 | ColorMetric::Cie76 => {
+                let lab = to_lab(rgba);
+                nearest(self.code_to_lab.iter(), |(_, code_lab)| {
+                    self.metric.lab_difference(*code_lab, lab)
+                })
+                .map(|((code, _), diff)| (*code, diff))
             }
         }
-        let best_emoji = self.rgb_to_emoji.get(best_rgb).unwrap();
+        .unwrap();
         self.cache
-            .borrow_mut()
-            .insert(candidate_rgb.to_owned(), best_emoji.to_owned());
+            .lock()
+            .unwrap()
+            .insert(candidate_rgb, (best_code, min_diff));
 
-        best_emoji.to_owned()
+        (best_code, min_diff)
+    }
+}
+
+impl Ansi256FrameFormatter {
+    pub fn new(
+        minimal_escapes: bool,
+        hide_cursor: bool,
+        blank: Option<String>,
+        metric: ColorMetric,
+    ) -> Self {
+        Self {
+            lines: TrueColorFrameFormatter {
+                minimal_escapes,
+                fg_glyph: false,
+                blank,
+                hide_cursor,
+            },
+            palette: CodePalette::new((16..=255).map(|idx| (idx, xterm_rgb(idx))), metric),
+        }
+    }
+}
+
+impl Ansi16FrameFormatter {
+    pub fn new(
+        minimal_escapes: bool,
+        hide_cursor: bool,
+        blank: Option<String>,
+        metric: ColorMetric,
+    ) -> Self {
+        Self {
+            lines: TrueColorFrameFormatter {
+                minimal_escapes,
+                fg_glyph: false,
+                blank,
+                hide_cursor,
+            },
+            palette: CodePalette::new(ANSI16_COLORS.into_iter(), metric),
+        }
     }
 }
 
 impl FrameFormatter for EmojiFrameFormatter {
     fn blank(&self) -> &str {
-        "🫥"
+        self.blank.as_deref().unwrap_or("🫥")
     }
 
     fn placeholder(&self) -> &str {
-        self.blank()
+        "🫥"
     }
 
     /// Convert color value to closest UTF-8 emoji codepoint.
@@ -114,6 +844,10 @@ impl FrameFormatter for EmojiFrameFormatter {
         })
     }
 
+    fn to_framedot_text(&self, text: &str) -> String {
+        format!("{:<2}", text)
+    }
+
     fn to_frameline_at_origin(&self, name: &String, _clear_line: bool) -> String {
         self.to_frameline(name)
     }
@@ -121,12 +855,42 @@ impl FrameFormatter for EmojiFrameFormatter {
     fn to_frameline(&self, name: &String) -> String {
         name.to_owned()
     }
+
+    fn take_match_stats(&self) -> Option<MatchStats> {
+        Some(std::mem::take(&mut self.stats.lock().unwrap()))
+    }
+
+    fn quantize(&self, rgba: &[u8; 4]) -> Option<[u8; 3]> {
+        let (best_rgb, _) = self.closest_rgb(rgba);
+        let rgb = u32::from_str_radix(&best_rgb, 16).unwrap().to_be_bytes();
+        Some([rgb[1], rgb[2], rgb[3]])
+    }
+}
+
+impl TrueColorFrameFormatter {
+    /// Escape sequence ending frame lines, hiding the cursor unless
+    /// it's kept visible.
+    fn hide_cursor_escape(&self) -> &str {
+        if self.hide_cursor { "\x1b[?25l" } else { "" }
+    }
+
+    /// Check that `blank` is printable and as wide as a frame dot.
+    pub fn validate_blank(blank: &str) -> Result<(), String> {
+        if blank.chars().any(char::is_control) {
+            return Err(String::from("contains control characters"));
+        }
+        match blank.width() {
+            2 => Ok(()),
+            width => Err(format!("expected 2 columns wide, got {}", width)),
+        }
+    }
 }
 
 impl FrameFormatter for TrueColorFrameFormatter {
-    /// Double-width spacing rendered as a square frame dot.
+    /// Double-width spacing rendered as a square frame dot,
+    /// unless overridden.
     fn blank(&self) -> &str {
-        "  "
+        self.blank.as_deref().unwrap_or(DOT_SPACING)
     }
 
     /// Black in 24-bit rgb color code.
@@ -139,6 +903,9 @@ impl FrameFormatter for TrueColorFrameFormatter {
     /// a.k.a. "888" colors a.k.a. 16 million colors.
     ///
     /// See: <https://tintin.mudhalla.net/info/truecolor/>
+    ///
+    /// With minimal escapes, components are separated by ';', which is
+    /// more widely supported than the ITU T.416 ':' separator.
     fn to_framedot(&self, rgba: Option<Vec<u8>>) -> String {
         let mut rgb = String::new();
         rgba.map_or(Some(self.placeholder()), |rgba| {
@@ -146,7 +913,7 @@ impl FrameFormatter for TrueColorFrameFormatter {
                 .iter()
                 .map(|n| n.to_string())
                 .collect::<Vec<String>>()
-                .join(":");
+                .join(if self.minimal_escapes { ";" } else { ":" });
             let a = rgba[3];
             match a {
                 0 => None,
@@ -154,31 +921,718 @@ impl FrameFormatter for TrueColorFrameFormatter {
             }
         })
         .map_or(String::from(self.blank()), |rgb| {
+            // \x1b[38:2::{}m => Foreground 24-bit rgb color code;
+            // \x1b[38;2;{}m => Foreground 24-bit rgb color code (legacy separator);
+            // \x1b[39m => Default foreground color;
+            if self.fg_glyph {
+                return if self.minimal_escapes {
+                    format!("\x1b[38;2;{}m██\x1b[39m", rgb)
+                } else {
+                    format!("\x1b[38:2::{}m██\x1b[39m", rgb)
+                };
+            }
+
             // \x1b[48:2::{}m => Background 24-bit rgb color code;
+            // \x1b[48;2;{}m => Background 24-bit rgb color code (legacy separator);
             // \x1b[49m => Default background color;
-            format!("\x1b[48:2::{}m{}\x1b[49m", rgb, self.blank())
+            if self.minimal_escapes {
+                format!("\x1b[48;2;{}m{}\x1b[49m", rgb, DOT_SPACING)
+            } else {
+                format!("\x1b[48:2::{}m{}\x1b[49m", rgb, DOT_SPACING)
+            }
         })
     }
 
+    fn to_framedot_text(&self, text: &str) -> String {
+        // \x1b[7m => Character attribute inverse: readable on both light and dark themes;
+        // \x1b[27m => Character attribute not inverse;
+        format!("\x1b[7m{:<2}\x1b[27m", text)
+    }
+
     fn to_frameline_at_origin(&self, name: &String, clear_line: bool) -> String {
+        if self.minimal_escapes {
+            // \x1b[1;1H => Set cursor position to screen origin [row=1;column=1];
+            return format!("\x1b[1;1H{}", name);
+        }
+
         // \x1b[1;1H => Set cursor position to screen origin [row=1;column=1];
         // \x1b[2K => Erase all in line;
         // \x1b[2J => Erase all in display;
         // \x1b[8m => Character attribute invisible: hides trailing argument parenthesis (gdb) / function offset (lldb);
         // \x1b[?25l => Hide cursor (DECTCEM);
         format!(
-            "\x1b[1;1H\x1b[2{}{}\x1b[8m\x1b[?25l",
+            "\x1b[1;1H\x1b[2{}{}\x1b[8m{}",
             if clear_line { "K" } else { "J" },
-            name
+            name,
+            self.hide_cursor_escape()
         )
     }
 
     fn to_frameline(&self, name: &String) -> String {
+        if self.minimal_escapes {
+            return name.to_owned();
+        }
+
         // \x1b[1K => Erase to left of cursor in line;
         // \x1b[99D => Cursor backward 99 times;
         // \x1b[3K => Erase to right of cursor in line;
         // \x1b[8m => Character attribute invisible: hides trailing argument parenthesis (gdb) / function offset (lldb);
         // \x1b[?25l => Hide cursor (DECTCEM);
-        format!("\x1b[1K\x1b[99D{}\x1b[3K\x1b[8m\x1b[?25l", name)
+        format!(
+            "\x1b[1K\x1b[99D{}\x1b[3K\x1b[8m{}",
+            name,
+            self.hide_cursor_escape()
+        )
+    }
+
+    fn to_frameline_rows(&self, names: &[String], clear_line: bool) -> String {
+        // \x1b[{row};1H => Set cursor position to frame line [row;column=1];
+        // \x1b[2K => Erase all in line;
+        // \x1b[{height + 1};1H => Set cursor position below frame: trailing text can't overwrite drawn lines;
+        let rows = names
+            .iter()
+            .enumerate()
+            .map(|(row, name)| {
+                if row == 0 {
+                    name.to_owned()
+                } else {
+                    format!(
+                        "\x1b[{};1H{}{}",
+                        row + 1,
+                        if clear_line && !self.minimal_escapes {
+                            "\x1b[2K"
+                        } else {
+                            ""
+                        },
+                        name
+                    )
+                }
+            })
+            .collect::<String>();
+        self.to_frameline_at_origin(&format!("{}\x1b[{};1H", rows, names.len() + 1), clear_line)
+    }
+
+    /// Minimal escapes can't erase leftover text of the previous
+    /// frame line, so lines are positioned instead.
+    fn positions_rows(&self) -> bool {
+        self.minimal_escapes
+    }
+
+    fn to_frameline_at_row(
+        &self,
+        name: &String,
+        row: usize,
+        height: usize,
+        at_origin: bool,
+        clear_line: bool,
+    ) -> String {
+        if self.minimal_escapes {
+            // \x1b[{row};1H => Set cursor position to frame line [row;column=1];
+            // \x1b[{height + 1};1H => Set cursor position below frame: trailing text and next frame prefix can't overwrite drawn lines;
+            return format!("\x1b[{};1H{}\x1b[{};1H", row + 1, name, height + 1);
+        }
+
+        // \x1b[1K => Erase to left of cursor in line;
+        // \x1b[2J => Erase all in display;
+        // \x1b[{row};1H => Set cursor position to frame line [row;column=1];
+        // \x1b[2K => Erase all in line;
+        // \x1b[{height + 1};1H => Set cursor position below frame: trailing text and next frame prefix can't overwrite drawn lines;
+        // \x1b[8m => Character attribute invisible: hides trailing argument parenthesis (gdb) / function offset (lldb);
+        // \x1b[?25l => Hide cursor (DECTCEM);
+        format!(
+            "\x1b[1K{}\x1b[{};1H\x1b[2K{}\x1b[{};1H\x1b[8m{}",
+            if at_origin && !clear_line {
+                "\x1b[2J"
+            } else {
+                ""
+            },
+            row + 1,
+            name,
+            height + 1,
+            self.hide_cursor_escape()
+        )
+    }
+}
+
+impl FrameFormatter for Ansi256FrameFormatter {
+    fn blank(&self) -> &str {
+        self.lines.blank()
+    }
+
+    /// Black in xterm-256 palette index.
+    fn placeholder(&self) -> &str {
+        "16"
+    }
+
+    /// Convert color value to a terminal sequence representing a
+    /// single frame dot, encoded with the closest xterm-256 palette
+    /// index a.k.a. "256 colors", for terminals without truecolor.
+    ///
+    /// See: <https://en.wikipedia.org/wiki/ANSI_escape_code#8-bit>
+    fn to_framedot(&self, rgba: Option<Vec<u8>>) -> String {
+        rgba.map_or(Some(String::from(self.placeholder())), |rgba| {
+            match rgba[3] {
+                0 => None,
+                _ => Some(self.palette.lookup(&rgba).to_string()),
+            }
+        })
+        .map_or(String::from(self.blank()), |idx| {
+            // \x1b[48;5;{}m => Background 256-color palette index;
+            // \x1b[49m => Default background color;
+            format!("\x1b[48;5;{}m{}\x1b[49m", idx, DOT_SPACING)
+        })
+    }
+
+    fn to_framedot_text(&self, text: &str) -> String {
+        self.lines.to_framedot_text(text)
+    }
+
+    fn to_frameline_at_origin(&self, name: &String, clear_line: bool) -> String {
+        self.lines.to_frameline_at_origin(name, clear_line)
+    }
+
+    fn to_frameline(&self, name: &String) -> String {
+        self.lines.to_frameline(name)
+    }
+
+    fn take_match_stats(&self) -> Option<MatchStats> {
+        Some(std::mem::take(&mut self.palette.stats.lock().unwrap()))
+    }
+
+    fn quantize(&self, rgba: &[u8; 4]) -> Option<[u8; 3]> {
+        Some(self.palette.quantize(rgba))
+    }
+
+    fn to_frameline_rows(&self, names: &[String], clear_line: bool) -> String {
+        self.lines.to_frameline_rows(names, clear_line)
+    }
+
+    fn positions_rows(&self) -> bool {
+        self.lines.positions_rows()
+    }
+
+    fn to_frameline_at_row(
+        &self,
+        name: &String,
+        row: usize,
+        height: usize,
+        at_origin: bool,
+        clear_line: bool,
+    ) -> String {
+        self.lines
+            .to_frameline_at_row(name, row, height, at_origin, clear_line)
+    }
+}
+
+impl FrameFormatter for Ansi16FrameFormatter {
+    fn blank(&self) -> &str {
+        self.lines.blank()
+    }
+
+    /// Black in SGR background code.
+    fn placeholder(&self) -> &str {
+        "40"
+    }
+
+    /// Convert color value to a terminal sequence representing a
+    /// single frame dot, encoded with the closest of the 16 colors
+    /// a.k.a. "4-bit" colors, for serial consoles and older emulators.
+    ///
+    /// See: <https://en.wikipedia.org/wiki/ANSI_escape_code#3-bit_and_4-bit>
+    fn to_framedot(&self, rgba: Option<Vec<u8>>) -> String {
+        rgba.map_or(Some(String::from(self.placeholder())), |rgba| {
+            match rgba[3] {
+                0 => None,
+                _ => Some(self.palette.lookup(&rgba).to_string()),
+            }
+        })
+        .map_or(String::from(self.blank()), |code| {
+            // \x1b[{}m => Background color (40-47 normal, 100-107 bright);
+            // \x1b[49m => Default background color;
+            format!("\x1b[{}m{}\x1b[49m", code, DOT_SPACING)
+        })
+    }
+
+    fn to_framedot_text(&self, text: &str) -> String {
+        self.lines.to_framedot_text(text)
+    }
+
+    fn to_frameline_at_origin(&self, name: &String, clear_line: bool) -> String {
+        self.lines.to_frameline_at_origin(name, clear_line)
+    }
+
+    fn to_frameline(&self, name: &String) -> String {
+        self.lines.to_frameline(name)
+    }
+
+    fn take_match_stats(&self) -> Option<MatchStats> {
+        Some(std::mem::take(&mut self.palette.stats.lock().unwrap()))
+    }
+
+    fn quantize(&self, rgba: &[u8; 4]) -> Option<[u8; 3]> {
+        Some(self.palette.quantize(rgba))
+    }
+
+    fn to_frameline_rows(&self, names: &[String], clear_line: bool) -> String {
+        self.lines.to_frameline_rows(names, clear_line)
+    }
+
+    fn positions_rows(&self) -> bool {
+        self.lines.positions_rows()
+    }
+
+    fn to_frameline_at_row(
+        &self,
+        name: &String,
+        row: usize,
+        height: usize,
+        at_origin: bool,
+        clear_line: bool,
+    ) -> String {
+        self.lines
+            .to_frameline_at_row(name, row, height, at_origin, clear_line)
+    }
+}
+
+impl FrameFormatter for HalfBlockFrameFormatter {
+    /// Single-width spacing, since each cell is split into two
+    /// square frame dots.
+    fn blank(&self) -> &str {
+        " "
+    }
+
+    fn placeholder(&self) -> &str {
+        self.blank()
+    }
+
+    fn to_framedot(&self, rgba: Option<Vec<u8>>) -> String {
+        rgba.map_or(String::from(self.placeholder()), |rgba| {
+            let rgba = [rgba[0], rgba[1], rgba[2], rgba[3]];
+            self.to_framedot_cell(&[rgba, rgba])
+        })
+    }
+
+    fn to_framedot_text(&self, text: &str) -> String {
+        self.lines.to_framedot_text(text)
+    }
+
+    fn dot_width(&self) -> usize {
+        1
+    }
+
+    fn to_frameline_at_origin(&self, name: &String, clear_line: bool) -> String {
+        self.lines.to_frameline_at_origin(name, clear_line)
+    }
+
+    fn to_frameline(&self, name: &String) -> String {
+        self.lines.to_frameline(name)
+    }
+
+    fn cell_size(&self) -> (usize, usize) {
+        (1, 2)
+    }
+
+    /// Convert colors of two vertically adjacent pixels to a terminal
+    /// sequence representing a single cell, with the top color drawn
+    /// by an upper half block, over the bottom color.
+    fn to_framedot_cell(&self, rgbas: &[[u8; 4]]) -> String {
+        let rgb =
+            |rgba: &[u8; 4]| (rgba[3] > 0).then(|| format!("{};{};{}", rgba[0], rgba[1], rgba[2]));
+        // \x1b[38;2;{}m => Foreground 24-bit rgb color code;
+        // \x1b[48;2;{}m => Background 24-bit rgb color code;
+        // \x1b[0m => Reset character attributes;
+        match (rgb(&rgbas[0]), rgb(&rgbas[1])) {
+            (Some(top), Some(bottom)) => {
+                format!("\x1b[38;2;{}m\x1b[48;2;{}m▀\x1b[0m", top, bottom)
+            }
+            (Some(top), None) => format!("\x1b[38;2;{}m▀\x1b[0m", top),
+            (None, Some(bottom)) => format!("\x1b[38;2;{}m▄\x1b[0m", bottom),
+            (None, None) => String::from(self.blank()),
+        }
+    }
+
+    fn to_frameline_rows(&self, names: &[String], clear_line: bool) -> String {
+        self.lines.to_frameline_rows(names, clear_line)
+    }
+
+    fn positions_rows(&self) -> bool {
+        self.lines.positions_rows()
+    }
+
+    fn to_frameline_at_row(
+        &self,
+        name: &String,
+        row: usize,
+        height: usize,
+        at_origin: bool,
+        clear_line: bool,
+    ) -> String {
+        self.lines
+            .to_frameline_at_row(name, row, height, at_origin, clear_line)
+    }
+}
+
+/// Bits of each braille dot in a 2x4 cell, indexed by row and column.
+///
+/// See: <https://en.wikipedia.org/wiki/Braille_Patterns#Block>
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+impl FrameFormatter for BrailleFrameFormatter {
+    /// Braille pattern without raised dots, which is as wide as
+    /// other patterns, unlike spacing in some fonts.
+    fn blank(&self) -> &str {
+        "\u{2800}"
+    }
+
+    fn placeholder(&self) -> &str {
+        self.blank()
+    }
+
+    fn to_framedot(&self, rgba: Option<Vec<u8>>) -> String {
+        rgba.map_or(String::from(self.placeholder()), |rgba| {
+            self.to_framedot_cell(&[[rgba[0], rgba[1], rgba[2], rgba[3]]; 8])
+        })
+    }
+
+    fn to_framedot_text(&self, text: &str) -> String {
+        self.lines.to_framedot_text(text)
+    }
+
+    fn dot_width(&self) -> usize {
+        1
+    }
+
+    fn to_frameline_at_origin(&self, name: &String, clear_line: bool) -> String {
+        self.lines.to_frameline_at_origin(name, clear_line)
+    }
+
+    fn to_frameline(&self, name: &String) -> String {
+        self.lines.to_frameline(name)
+    }
+
+    fn cell_size(&self) -> (usize, usize) {
+        (2, 4)
+    }
+
+    /// Convert colors of a 2x4 cell of pixels to a braille pattern,
+    /// raising the dots of opaque pixels brighter than the threshold,
+    /// by their ITU-R BT.601 luma.
+    fn to_framedot_cell(&self, rgbas: &[[u8; 4]]) -> String {
+        let mask = rgbas
+            .iter()
+            .enumerate()
+            .filter(|(_, rgba)| {
+                let luma = 0.299 * rgba[0] as f32 + 0.587 * rgba[1] as f32 + 0.114 * rgba[2] as f32;
+                rgba[3] > 0 && luma > self.threshold as f32
+            })
+            .map(|(i, _)| BRAILLE_DOTS[i / 2][i % 2])
+            .sum::<u32>();
+        char::from_u32(0x2800 + mask).unwrap().to_string()
+    }
+
+    fn to_frameline_rows(&self, names: &[String], clear_line: bool) -> String {
+        self.lines.to_frameline_rows(names, clear_line)
+    }
+
+    fn positions_rows(&self) -> bool {
+        self.lines.positions_rows()
+    }
+
+    fn to_frameline_at_row(
+        &self,
+        name: &String,
+        row: usize,
+        height: usize,
+        at_origin: bool,
+        clear_line: bool,
+    ) -> String {
+        self.lines
+            .to_frameline_at_row(name, row, height, at_origin, clear_line)
+    }
+}
+
+impl FrameFormatter for AsciiFrameFormatter {
+    /// Single-width spacing, which is also the darkest character
+    /// of the default ramp.
+    fn blank(&self) -> &str {
+        " "
+    }
+
+    fn placeholder(&self) -> &str {
+        self.blank()
+    }
+
+    /// Convert color value to the ramp character at the position of
+    /// its ITU-R BT.601 luma, for environments without color support.
+    fn to_framedot(&self, rgba: Option<Vec<u8>>) -> String {
+        rgba.map_or(String::from(self.placeholder()), |rgba| match rgba[3] {
+            0 => String::from(self.blank()),
+            _ => {
+                let luma = 0.299 * rgba[0] as f32 + 0.587 * rgba[1] as f32 + 0.114 * rgba[2] as f32;
+                let i = (luma / 256.0 * self.ramp.len() as f32) as usize;
+                self.ramp[i.min(self.ramp.len() - 1)].to_string()
+            }
+        })
+    }
+
+    fn to_framedot_text(&self, text: &str) -> String {
+        format!("{:<1}", text)
+    }
+
+    fn dot_width(&self) -> usize {
+        1
+    }
+
+    fn to_frameline_at_origin(&self, name: &String, clear_line: bool) -> String {
+        self.lines.to_frameline_at_origin(name, clear_line)
+    }
+
+    fn to_frameline(&self, name: &String) -> String {
+        self.lines.to_frameline(name)
+    }
+
+    fn to_frameline_rows(&self, names: &[String], clear_line: bool) -> String {
+        self.lines.to_frameline_rows(names, clear_line)
+    }
+
+    fn positions_rows(&self) -> bool {
+        self.lines.positions_rows()
+    }
+
+    fn to_frameline_at_row(
+        &self,
+        name: &String,
+        row: usize,
+        height: usize,
+        at_origin: bool,
+        clear_line: bool,
+    ) -> String {
+        self.lines
+            .to_frameline_at_row(name, row, height, at_origin, clear_line)
+    }
+}
+
+/// Pixel rows encoded by each sixel character.
+const SIXEL_BAND_HEIGHT: usize = 6;
+
+/// Screen pixels along each side of a frame dot drawn with sixels,
+/// so that a band of sixel rows roughly fills a line of text.
+const SIXEL_SCALE: usize = 3;
+
+/// Color register of `rgba` in a 6x6x6 color cube, shared by all
+/// sixel images.
+fn sixel_register(rgba: &[u8; 4]) -> usize {
+    rgba[..3].iter().fold(0, |register, c| {
+        register * 6 + (*c as usize * 5 + 127) / 255
+    })
+}
+
+/// Sixel image drawing color registers `pixels`, or `None` for
+/// transparent pixels, each one scaled to a square of screen pixels.
+///
+/// See: <https://vt100.net/docs/vt3xx-gp/chapter14.html>
+fn to_sixel(pixels: &[Vec<Option<usize>>]) -> String {
+    let w = pixels[0].len() * SIXEL_SCALE;
+    let h = pixels.len() * SIXEL_SCALE;
+    let rows = pixels
+        .iter()
+        .flat_map(|row| std::iter::repeat_n(row, SIXEL_SCALE))
+        .collect::<Vec<_>>();
+    let mut registers = pixels
+        .iter()
+        .flatten()
+        .flatten()
+        .copied()
+        .collect::<Vec<_>>();
+    registers.sort_unstable();
+    registers.dedup();
+
+    // \x1bP0;1q => Device control string with sixel data, leaving transparent pixels unchanged;
+    // "1;1;{w};{h} => Raster attributes: square pixels, image width and height;
+    let mut sixel = format!("\x1bP0;1q\"1;1;{};{}", w, h);
+    for register in &registers {
+        // #{register};2;{r};{g};{b} => Color register definition, with RGB percentages;
+        let [r, g, b] = [register / 36, register / 6 % 6, register % 6].map(|c| c * 20);
+        sixel += &format!("#{};2;{};{};{}", register, r, g, b);
+    }
+    for band in rows.chunks(SIXEL_BAND_HEIGHT) {
+        for register in &registers {
+            let columns = (0..band[0].len())
+                .map(|x| {
+                    band.iter()
+                        .enumerate()
+                        .filter(|(_, row)| row[x] == Some(*register))
+                        .map(|(dy, _)| 1 << dy)
+                        .sum::<u8>()
+                })
+                .collect::<Vec<_>>();
+            if columns.iter().all(|bits| *bits == 0) {
+                continue;
+            }
+
+            // #{register} => Select color register;
+            // !{n}{c} => Repeat sixel character `c` n times;
+            // $ => Return to start of band;
+            sixel += &format!("#{}", register);
+            for (bits, run) in columns
+                .chunk_by(|a, b| a == b)
+                .map(|run| (run[0], run.len()))
+            {
+                sixel += &format!("!{}{}", run * SIXEL_SCALE, (0x3f + bits) as char);
+            }
+            sixel += "$";
+        }
+        // - => Move to next band;
+        sixel += "-";
+    }
+    // \x1b\\ => String terminator;
+    sixel + "\x1b\\"
+}
+
+impl FrameFormatter for SixelFrameFormatter {
+    fn blank(&self) -> &str {
+        " "
+    }
+
+    fn placeholder(&self) -> &str {
+        self.blank()
+    }
+
+    fn to_framedot(&self, rgba: Option<Vec<u8>>) -> String {
+        rgba.map_or(String::from(self.placeholder()), |rgba| {
+            self.to_framedots_band(&[vec![[rgba[0], rgba[1], rgba[2], rgba[3]]]])
+                .concat()
+        })
+    }
+
+    fn to_framedot_text(&self, text: &str) -> String {
+        self.lines.to_framedot_text(text)
+    }
+
+    fn to_frameline_at_origin(&self, name: &String, clear_line: bool) -> String {
+        self.lines.to_frameline_at_origin(name, clear_line)
+    }
+
+    fn to_frameline(&self, name: &String) -> String {
+        self.lines.to_frameline(name)
+    }
+
+    fn rows_per_line(&self) -> usize {
+        SIXEL_BAND_HEIGHT
+    }
+
+    /// Convert a band of pixel rows into a single sixel image,
+    /// with colors approximated by a 6x6x6 color cube.
+    fn to_framedots_band(&self, lines: &[Vec<[u8; 4]>]) -> Vec<String> {
+        let pixels = lines
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .map(|rgba| (rgba[3] > 0).then(|| sixel_register(rgba)))
+                    .collect()
+            })
+            .collect::<Vec<_>>();
+        vec![to_sixel(&pixels)]
+    }
+
+    fn to_frameline_rows(&self, names: &[String], clear_line: bool) -> String {
+        self.lines.to_frameline_rows(names, clear_line)
+    }
+
+    fn positions_rows(&self) -> bool {
+        self.lines.positions_rows()
+    }
+
+    fn to_frameline_at_row(
+        &self,
+        name: &String,
+        row: usize,
+        height: usize,
+        at_origin: bool,
+        clear_line: bool,
+    ) -> String {
+        self.lines
+            .to_frameline_at_row(name, row, height, at_origin, clear_line)
+    }
+}
+
+/// Maximum size of base64 payload in each graphics escape sequence.
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+impl FrameFormatter for KittyFrameFormatter {
+    fn blank(&self) -> &str {
+        " "
+    }
+
+    fn placeholder(&self) -> &str {
+        self.blank()
+    }
+
+    fn to_framedot(&self, rgba: Option<Vec<u8>>) -> String {
+        rgba.map_or(String::from(self.placeholder()), |rgba| {
+            self.whole_frame(&[vec![[rgba[0], rgba[1], rgba[2], rgba[3]]]], 1, 1)
+                .unwrap()
+        })
+    }
+
+    fn to_framedot_text(&self, text: &str) -> String {
+        self.lines.to_framedot_text(text)
+    }
+
+    fn to_frameline_at_origin(&self, name: &String, clear_line: bool) -> String {
+        self.lines.to_frameline_at_origin(name, clear_line)
+    }
+
+    fn to_frameline(&self, name: &String) -> String {
+        self.lines.to_frameline(name)
+    }
+
+    fn groups_pixels(&self) -> bool {
+        true
+    }
+
+    /// Convert colors of a frame into kitty graphics escape sequences,
+    /// transmitting and displaying raw RGBA data, with the base64
+    /// payload split in chunks.
+    ///
+    /// See: <https://sw.kovidgoyal.net/kitty/graphics-protocol/>
+    fn whole_frame(&self, frame_rgba: &[Vec<[u8; 4]>], w: usize, h: usize) -> Option<String> {
+        let payload = base64::engine::general_purpose::STANDARD.encode(
+            frame_rgba
+                .iter()
+                .flatten()
+                .flatten()
+                .copied()
+                .collect::<Vec<_>>(),
+        );
+        let chunks = payload
+            .as_bytes()
+            .chunks(KITTY_CHUNK_SIZE)
+            .collect::<Vec<_>>();
+        let frame = chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                // \x1b_G{keys};{payload}\x1b\\ => Graphics command, where keys are:
+                // a=T => Transmit and display image;
+                // f=32 => RGBA pixel format;
+                // s={w},v={h} => Image width and height;
+                // q=2 => Suppress responses, which would be read as debugger input;
+                // m={0,1} => Whether more chunks follow;
+                let keys = if i == 0 {
+                    format!("a=T,f=32,s={},v={},q=2,", w, h)
+                } else {
+                    String::new()
+                };
+                format!(
+                    "\x1b_G{}m={};{}\x1b\\",
+                    keys,
+                    (i + 1 < chunks.len()) as u8,
+                    String::from_utf8_lossy(chunk)
+                )
+            })
+            .collect();
+
+        Some(frame)
     }
 }
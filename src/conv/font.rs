@@ -0,0 +1,72 @@
+//! Bitmap font for title cards.
+
+/// Glyph height in frame dots.
+pub const GLYPH_HEIGHT: usize = 5;
+
+/// Rows of each supported character, where '#' is a filled frame dot.
+const GLYPHS: &[(char, [&str; GLYPH_HEIGHT])] = &[
+    (' ', ["...", "...", "...", "...", "..."]),
+    ('0', ["###", "#.#", "#.#", "#.#", "###"]),
+    ('1', [".#.", "##.", ".#.", ".#.", "###"]),
+    ('2', ["##.", "..#", ".#.", "#..", "###"]),
+    ('3', ["##.", "..#", ".#.", "..#", "##."]),
+    ('4', ["#.#", "#.#", "###", "..#", "..#"]),
+    ('5', ["###", "#..", "##.", "..#", "##."]),
+    ('6', [".##", "#..", "###", "#.#", "###"]),
+    ('7', ["###", "..#", ".#.", ".#.", ".#."]),
+    ('8', ["###", "#.#", "###", "#.#", "###"]),
+    ('9', ["###", "#.#", "###", "..#", "##."]),
+    ('A', [".#.", "#.#", "###", "#.#", "#.#"]),
+    ('B', ["##.", "#.#", "##.", "#.#", "##."]),
+    ('C', [".##", "#..", "#..", "#..", ".##"]),
+    ('D', ["##.", "#.#", "#.#", "#.#", "##."]),
+    ('E', ["###", "#..", "##.", "#..", "###"]),
+    ('F', ["###", "#..", "##.", "#..", "#.."]),
+    ('G', [".##", "#..", "#.#", "#.#", ".##"]),
+    ('H', ["#.#", "#.#", "###", "#.#", "#.#"]),
+    ('I', ["###", ".#.", ".#.", ".#.", "###"]),
+    ('J', ["..#", "..#", "..#", "#.#", ".#."]),
+    ('K', ["#.#", "#.#", "##.", "#.#", "#.#"]),
+    ('L', ["#..", "#..", "#..", "#..", "###"]),
+    ('M', ["#.#", "###", "###", "#.#", "#.#"]),
+    ('N', ["##.", "#.#", "#.#", "#.#", "#.#"]),
+    ('O', [".#.", "#.#", "#.#", "#.#", ".#."]),
+    ('P', ["##.", "#.#", "##.", "#..", "#.."]),
+    ('Q', [".#.", "#.#", "#.#", "##.", ".##"]),
+    ('R', ["##.", "#.#", "##.", "#.#", "#.#"]),
+    ('S', [".##", "#..", ".#.", "..#", "##."]),
+    ('T', ["###", ".#.", ".#.", ".#.", ".#."]),
+    ('U', ["#.#", "#.#", "#.#", "#.#", "###"]),
+    ('V', ["#.#", "#.#", "#.#", "#.#", ".#."]),
+    ('W', ["#.#", "#.#", "###", "###", "#.#"]),
+    ('X', ["#.#", "#.#", ".#.", "#.#", "#.#"]),
+    ('Y', ["#.#", "#.#", ".#.", ".#.", ".#."]),
+    ('Z', ["###", "..#", ".#.", "#..", "###"]),
+];
+
+/// Rows of filled frame dots for character `c`, with lowercase
+/// letters drawn as uppercase.
+fn glyph(c: char) -> Option<[Vec<bool>; GLYPH_HEIGHT]> {
+    let c = c.to_ascii_uppercase();
+    GLYPHS
+        .iter()
+        .find(|(glyph_c, _)| *glyph_c == c)
+        .map(|(_, rows)| rows.map(|row| row.chars().map(|dot| dot == '#').collect()))
+}
+
+/// Rows of filled frame dots for `text`, with one blank column
+/// between characters, or the first unsupported character.
+pub fn render(text: &str) -> Result<Vec<Vec<bool>>, char> {
+    let mut rows = vec![vec![]; GLYPH_HEIGHT];
+    for (i, c) in text.chars().enumerate() {
+        let glyph = glyph(c).ok_or(c)?;
+        for (row, glyph_row) in rows.iter_mut().zip(glyph) {
+            if i > 0 {
+                row.push(false);
+            }
+            row.extend(glyph_row);
+        }
+    }
+
+    Ok(rows)
+}
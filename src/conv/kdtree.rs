@@ -0,0 +1,102 @@
+//! Nearest neighbour search over 3-D points.
+
+/// Point stored at a node, splitting its subtrees along `axis`.
+struct Node {
+    point: [f32; 3],
+    index: usize,
+    axis: usize,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+/// Static kd-tree, built once over all points.
+pub struct KdTree {
+    nodes: Vec<Node>,
+    root: Option<usize>,
+}
+
+impl KdTree {
+    /// Tree over `points`, where each point is identified by its index.
+    pub fn new(points: &[[f32; 3]]) -> Self {
+        let mut tree = KdTree {
+            nodes: Vec::with_capacity(points.len()),
+            root: None,
+        };
+        let mut indexes = (0..points.len()).collect::<Vec<_>>();
+        tree.root = tree.build(points, &mut indexes, 0);
+
+        tree
+    }
+
+    fn build(&mut self, points: &[[f32; 3]], indexes: &mut [usize], depth: usize) -> Option<usize> {
+        if indexes.is_empty() {
+            return None;
+        }
+
+        let axis = depth % 3;
+        let median = indexes.len() / 2;
+        indexes
+            .select_nth_unstable_by(median, |a, b| points[*a][axis].total_cmp(&points[*b][axis]));
+        let (left, rest) = indexes.split_at_mut(median);
+        let (index, right) = rest.split_first_mut().unwrap();
+        let left = self.build(points, left, depth + 1);
+        let right = self.build(points, right, depth + 1);
+        self.nodes.push(Node {
+            point: points[*index],
+            index: *index,
+            axis,
+            left,
+            right,
+        });
+
+        Some(self.nodes.len() - 1)
+    }
+
+    /// Indexes of up to `k` points closest to `target` by euclidean
+    /// distance, from closest to farthest.
+    pub fn nearest(&self, target: [f32; 3], k: usize) -> Vec<usize> {
+        let mut found = vec![];
+        self.search(self.root, target, k, &mut found);
+
+        found.into_iter().map(|(_, index)| index).collect()
+    }
+
+    /// Visit subtree at `node`, keeping the `k` closest points in
+    /// `found` as squared distances and indexes, sorted by distance.
+    fn search(
+        &self,
+        node: Option<usize>,
+        target: [f32; 3],
+        k: usize,
+        found: &mut Vec<(f32, usize)>,
+    ) {
+        let Some(node) = node.map(|node| &self.nodes[node]) else {
+            return;
+        };
+
+        let dist = distance_sq(node.point, target);
+        if found.len() < k || dist < found.last().unwrap().0 {
+            let i = found.partition_point(|(found_dist, _)| *found_dist <= dist);
+            found.insert(i, (dist, node.index));
+            found.truncate(k);
+        }
+
+        let delta = target[node.axis] - node.point[node.axis];
+        let (near, far) = if delta < 0.0 {
+            (node.left, node.right)
+        } else {
+            (node.right, node.left)
+        };
+        self.search(near, target, k, found);
+        // Points on the other side of the splitting plane can only be
+        // closer if the plane itself is closer than the farthest match.
+        if found.len() < k || delta * delta < found.last().unwrap().0 {
+            self.search(far, target, k, found);
+        }
+    }
+}
+
+/// Squared euclidean distance between points `a` and `b`.
+fn distance_sq(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
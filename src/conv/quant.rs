@@ -0,0 +1,113 @@
+//! Palette reduction by median cut.
+
+use crate::conv::kdtree::KdTree;
+use std::collections::HashMap;
+
+/// Colors in a box of the RGB cube, along with their counts.
+struct ColorBox {
+    colors: Vec<([u8; 3], usize)>,
+}
+
+impl ColorBox {
+    /// Channel with the widest range of colors, and that range.
+    fn widest_channel(&self) -> (usize, u8) {
+        (0..3)
+            .map(|c| {
+                let (min, max) = self
+                    .colors
+                    .iter()
+                    .fold((u8::MAX, u8::MIN), |(min, max), (rgb, _)| {
+                        (min.min(rgb[c]), max.max(rgb[c]))
+                    });
+                (c, max - min)
+            })
+            .max_by_key(|(_, range)| *range)
+            .unwrap()
+    }
+
+    /// Split at the median of the widest channel, weighted by counts,
+    /// so that both boxes have at least one color.
+    fn split(mut self) -> (ColorBox, ColorBox) {
+        let (c, _) = self.widest_channel();
+        self.colors.sort_unstable_by_key(|(rgb, _)| rgb[c]);
+        let total = self.colors.iter().map(|(_, count)| count).sum::<usize>();
+        let mut seen = 0;
+        let median = self
+            .colors
+            .iter()
+            .position(|(_, count)| {
+                seen += count;
+                seen * 2 >= total
+            })
+            .unwrap();
+        let at = (median + 1).clamp(1, self.colors.len() - 1);
+        let right = self.colors.split_off(at);
+
+        (self, ColorBox { colors: right })
+    }
+
+    /// Mean of the colors, weighted by counts.
+    fn mean(&self) -> [u8; 3] {
+        let total = self.colors.iter().map(|(_, count)| count).sum::<usize>() as f64;
+        [0, 1, 2].map(|c| {
+            let sum = self
+                .colors
+                .iter()
+                .map(|(rgb, count)| rgb[c] as f64 * *count as f64)
+                .sum::<f64>();
+            (sum / total).round() as u8
+        })
+    }
+}
+
+/// Palette of at most `max_colors` colors approximating the colors in
+/// `counts`, by repeatedly splitting the box with the widest range of
+/// a channel, until there are as many boxes or no box can be split.
+pub fn median_cut(counts: &HashMap<[u8; 3], usize>, max_colors: usize) -> Vec<[u8; 3]> {
+    if counts.is_empty() {
+        return vec![];
+    }
+
+    let mut boxes = vec![ColorBox {
+        colors: counts.iter().map(|(rgb, count)| (*rgb, *count)).collect(),
+    }];
+    while boxes.len() < max_colors {
+        let Some((i, _)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, color_box)| color_box.colors.len() > 1)
+            .max_by_key(|(_, color_box)| color_box.widest_channel().1)
+        else {
+            break;
+        };
+        let (left, right) = boxes.swap_remove(i).split();
+        boxes.push(left);
+        boxes.push(right);
+    }
+
+    boxes.iter().map(ColorBox::mean).collect()
+}
+
+/// Maps colors to the closest color of a palette.
+pub struct Quantizer {
+    palette: Vec<[u8; 3]>,
+    tree: KdTree,
+}
+
+impl Quantizer {
+    pub fn new(palette: Vec<[u8; 3]>) -> Self {
+        let points = palette
+            .iter()
+            .map(|rgb| rgb.map(|c| c as f32))
+            .collect::<Vec<_>>();
+        Quantizer {
+            tree: KdTree::new(&points),
+            palette,
+        }
+    }
+
+    /// Palette color closest to `rgb` by euclidean distance.
+    pub fn nearest(&self, rgb: [u8; 3]) -> [u8; 3] {
+        self.palette[self.tree.nearest(rgb.map(|c| c as f32), 1)[0]]
+    }
+}
@@ -0,0 +1,115 @@
+//! Environment self-check.
+
+use colored::Colorize;
+use std::process::{Command, Stdio};
+
+/// Tool, arguments to probe it, and what it is required for.
+const TOOLS: &[(&str, &[&str], &str)] = &[
+    (
+        "gcc",
+        &["--version"],
+        "compiling with `-d gdb`, `-d rr` or `-d r2`",
+    ),
+    ("clang", &["--version"], "compiling with `-d lldb`"),
+    ("clang-cl", &["--version"], "compiling with `-d windbg`"),
+    ("ld", &["--version"], "linking custom input (`-f c`)"),
+    ("gdb", &["--version"], "rendering with `-d gdb`"),
+    ("lldb", &["--version"], "rendering with `-d lldb`"),
+    ("rr", &["--version"], "recording with `-d rr`"),
+    ("r2", &["-v"], "rendering with `-d r2`"),
+];
+
+/// Debugger, arguments to run a Python statement, and what it is required for.
+const DEBUGGER_PYTHON: &[(&str, &[&str], &str)] = &[
+    (
+        "gdb",
+        &["-batch", "-ex", "python print('ok')"],
+        "running `a_gdb.py`",
+    ),
+    (
+        "lldb",
+        &["-b", "-o", "script print('ok')"],
+        "running `a_lldb.py`",
+    ),
+    (
+        "python3",
+        &["-c", "import r2pipe; print('ok')"],
+        "running `a_r2.py` with r2pipe",
+    ),
+];
+
+enum Status {
+    Ok(String),
+    Missing(String),
+}
+
+fn report(name: &str, status: Status) {
+    match status {
+        Status::Ok(detail) => println!("{} {}: {}", "[ok]".green().bold(), name, detail),
+        Status::Missing(hint) => println!("{} {}: {}", "[missing]".red().bold(), name, hint),
+    }
+}
+
+fn probe(program: &str, args: &[&str]) -> Option<String> {
+    Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Check the tools and files each option depends on, reporting what's
+/// missing along with a hint on what would be affected.
+pub fn doctor() {
+    println!("{}", "Tools:".purple().bold());
+    for (tool, args, required_for) in TOOLS {
+        report(
+            tool,
+            probe(tool, args)
+                .map(|output| Status::Ok(output.lines().next().unwrap_or_default().to_string()))
+                .unwrap_or_else(|| {
+                    Status::Missing(format!(
+                        "not found in PATH, install it for {}",
+                        required_for
+                    ))
+                }),
+        );
+    }
+
+    println!("\n{}", "Debugger Python support:".purple().bold());
+    for (debugger, args, required_for) in DEBUGGER_PYTHON {
+        report(
+            debugger,
+            probe(debugger, args)
+                .filter(|output| output.lines().any(|line| line.trim() == "ok"))
+                .map(|_| Status::Ok(String::from("Python scripting available")))
+                .unwrap_or_else(|| {
+                    Status::Missing(format!(
+                        "Python scripting unavailable, required for {}",
+                        required_for
+                    ))
+                }),
+        );
+    }
+
+    println!("\n{}", "System:".purple().bold());
+    report(
+        "vm.mmap_min_addr",
+        match std::fs::read_to_string("/proc/sys/vm/mmap_min_addr")
+            .ok()
+            .and_then(|min_addr| min_addr.trim().parse::<u64>().ok())
+        {
+            Some(0) => Status::Ok(String::from("0")),
+            Some(min_addr) => Status::Missing(format!(
+                "{}, custom input (`-f c`) requires `echo 0 | sudo tee /proc/sys/vm/mmap_min_addr`",
+                min_addr
+            )),
+            None => Status::Missing(String::from(
+                "can't read `/proc/sys/vm/mmap_min_addr`, required for custom input (`-f c`)",
+            )),
+        },
+    );
+}
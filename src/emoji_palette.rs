@@ -0,0 +1,101 @@
+//! Emoji palette generation.
+
+use backgif::conv::fmtr::EmojiFrameFormatter;
+use colored::Colorize;
+use itertools::Itertools;
+use palette::{LinSrgb, Srgb};
+use serde_json::Value;
+use std::path::Path;
+
+/// Average color of opaque pixels in `image`, weighted by alpha and
+/// averaged in linear light, or `None` if all pixels are transparent.
+fn average_color(image: &image::RgbaImage) -> Option<Srgb<u8>> {
+    let mut sum = LinSrgb::new(0.0, 0.0, 0.0);
+    let mut weight = 0.0;
+    for rgba in image.pixels() {
+        let alpha = rgba[3] as f32 / 255.0;
+        let linear = Srgb::new(rgba[0], rgba[1], rgba[2])
+            .into_format::<f32>()
+            .into_linear();
+        sum += linear * alpha;
+        weight += alpha;
+    }
+
+    (weight > 0.0).then(|| Srgb::<f32>::from_linear(sum / weight).into_format())
+}
+
+/// Print a warning about a skipped file.
+fn warn(message: &str) {
+    eprintln!("{}\n", message.red().bold());
+}
+
+/// Print an error, then exit.
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message.red().bold());
+    std::process::exit(1);
+}
+
+/// Write a palette of the images in `dir`, each named after the emoji
+/// it represents, with the same layout as the embedded palette: a list
+/// of blue, green and red components of each image's average color,
+/// followed by its emoji.
+pub fn build_palette(dir: &Path, out: &Path) {
+    let read_error = |e: std::io::Error| format!("Can't read directory {}: {}", dir.display(), e);
+    let paths = std::fs::read_dir(dir)
+        .unwrap_or_else(|e| fail(&read_error(e)))
+        .map(|entry| entry.unwrap_or_else(|e| fail(&read_error(e))).path())
+        .filter(|path| path.is_file())
+        .sorted()
+        .collect_vec();
+
+    let mut entries = vec![];
+    for path in paths {
+        let emoji = path.file_stem().unwrap().to_string_lossy().to_string();
+        // Entries are drawn as frame dots, so names that aren't
+        // a single emoji would break frame widths.
+        if let Err(e) = EmojiFrameFormatter::validate_blank(&emoji) {
+            warn(&format!(
+                "[!] Skipping {}, name isn't a single emoji: {}.",
+                path.display(),
+                e
+            ));
+            continue;
+        }
+        // Other files, such as a README, are skipped.
+        let image = match image::open(&path) {
+            Ok(image) => image.to_rgba8(),
+            Err(e) => {
+                warn(&format!(
+                    "[!] Skipping {}, can't decode image: {}",
+                    path.display(),
+                    e
+                ));
+                continue;
+            }
+        };
+        let Some(rgb) = average_color(&image) else {
+            warn(&format!(
+                "[!] Skipping {}, all pixels are transparent.",
+                path.display()
+            ));
+            continue;
+        };
+        entries.push(Value::from(vec![
+            Value::from(rgb.blue),
+            Value::from(rgb.green),
+            Value::from(rgb.red),
+            Value::from(emoji),
+        ]));
+    }
+
+    if entries.is_empty() {
+        fail(&format!(
+            "No images named after an emoji in {}, palette not written.",
+            dir.display()
+        ));
+    }
+
+    std::fs::write(out, serde_json::to_string_pretty(&entries).unwrap())
+        .unwrap_or_else(|e| fail(&format!("Can't write palette {}: {}", out.display(), e)));
+    println!("Wrote {} entries to {}", entries.len(), out.display());
+}
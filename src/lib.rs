@@ -0,0 +1,1732 @@
+//! `backgif` library, converting animations into binaries whose
+//! symbol names draw each frame in a debugger's backtrace.
+
+pub mod conv;
+
+use clap::{Parser, ValueEnum};
+use colored::Colorize;
+use conv::fmtr::{
+    Ansi16FrameFormatter, Ansi256FrameFormatter, AsciiFrameFormatter, BrailleFrameFormatter,
+    ColorMetric, EmojiFrameFormatter, FrameFormatter, HalfBlockFrameFormatter, KittyFrameFormatter,
+    SixelFrameFormatter, TrueColorFrameFormatter,
+};
+use conv::{
+    Animation, ApngFrameParser, Artifacts, CONFLICTING_CFLAGS, ColorAdjustments, ConvError,
+    CustomFrameConverter, CustomFrameParser, FrameConverter, FrameInfo, FrameParser,
+    GdbFrameConverter, GifFrameParser, LldbFrameConverter, QoiFrameParser, R2FrameConverter,
+    RenderCommand, RrFrameConverter, ScriptOptions, SequenceFrameParser, TempFiles,
+    TextFrameParser, Verbosity, VideoFrameParser, WinDbgFrameConverter, check_script,
+    estimate_symbols, preview, write_cast, write_player_script, write_snippets, write_verify_gif,
+};
+use std::cell::OnceCell;
+use std::path::PathBuf;
+use terminal_size::{Height, Width};
+
+/// Conversion options, parsed from command line arguments by the
+/// binary, or built with `Config::new()` and adjusted field by field.
+#[derive(Parser, Debug, Clone)]
+#[command(about = None, long_about = None)]
+pub struct Config {
+    /// Input file used to parse frames, or `-` to read GIF input
+    /// from standard input
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+
+    /// Input file format
+    #[arg(short, long, value_enum, default_value_t=InputFormat::GIF)]
+    pub format: InputFormat,
+
+    /// Frame renderer format
+    #[arg(short, long, value_enum, default_value_t=RenderFormat::TrueColor)]
+    pub renderer: RenderFormat,
+
+    /// Luminance cutoff from 0 to 255, above which pixels raise
+    /// their dot with the braille renderer
+    #[arg(long, default_value_t = 128)]
+    pub threshold: u8,
+
+    /// Characters from darkest to brightest, picked by luminance
+    /// with the ASCII renderer
+    #[arg(long, default_value = " .:-=+*#%@")]
+    pub ascii_ramp: String,
+
+    /// Line separating the text blocks of consecutive frames with
+    /// text input
+    #[arg(long, value_name = "LINE", default_value = "---")]
+    pub text_delimiter: String,
+
+    /// JSON file with color mappings of emoji codepoints, as a list
+    /// of blue, green, red and emoji entries, used instead of the
+    /// embedded palette with the emoji renderer
+    #[arg(long, value_name = "PATH")]
+    pub emoji_palette: Option<PathBuf>,
+
+    /// Difference used to match colors against the palette of the
+    /// emoji, ansi256 and ansi16 renderers, trading accuracy for speed
+    #[arg(long, value_enum, default_value_t=ColorMetric::Ciede2000)]
+    pub color_metric: ColorMetric,
+
+    /// Pass this argument to not read or write the emoji renderer's
+    /// caches, which keep processed palette entries and matched
+    /// colors in `$XDG_CACHE_HOME/backgif` (or `~/.cache/backgif`)
+    /// between runs
+    #[arg(long, action)]
+    pub no_cache: bool,
+
+    /// Glyph drawn for transparent dots, instead of the renderer's
+    /// default: a double-width sequence with the truecolor, ansi256
+    /// and ansi16 renderers, or a single emoji with the emoji renderer
+    #[arg(long, value_name = "GLYPH")]
+    pub blank: Option<String>,
+
+    /// Target debugger to generate commands and automation script
+    #[arg(short, long, value_enum, default_value_t=Debugger::GDB)]
+    pub debugger: Debugger,
+
+    /// Pass this argument to only clear each line being rendered,
+    /// but can leave artifacts on screen; Omit this argument to
+    /// clear all lines on the screen when rendering a new frame,
+    /// but can cause flickering with large frames (> 20x20)
+    #[arg(long, action)]
+    pub clear_line: bool,
+
+    /// Order in which frame lines are printed in the backtrace
+    #[arg(long, value_enum, default_value_t=RowOrder::TopDown)]
+    pub row_order: RowOrder,
+
+    /// Pass this argument to only use absolute cursor positioning
+    /// and color sequences, for terminals or SSH / multiplexer setups
+    /// that mangle other sequences, but can leave artifacts on screen
+    #[arg(long, action)]
+    pub minimal_escapes: bool,
+
+    /// Pass this argument to keep the cursor visible while frames
+    /// are drawn, instead of hiding it on each frame line (scripts
+    /// show it again when the debugger exits)
+    #[arg(long, action)]
+    pub no_hide_cursor: bool,
+
+    /// Pass this argument to draw truecolor dots as full blocks in
+    /// the foreground color, instead of filling the background, for
+    /// terminals with transparent or themed backgrounds
+    #[arg(long, action)]
+    pub fg_glyph: bool,
+
+    /// Pass this argument to draw all lines of each frame with
+    /// a single frame line, positioning each line on screen, for
+    /// environments that join or strip line breaks
+    #[arg(long, action)]
+    pub single_line: bool,
+
+    /// Pass this argument to draw each frame's index and start time
+    /// over its bottom right corner, useful for diagnosing timing
+    #[arg(long, action)]
+    pub overlay_index: bool,
+
+    /// Pass this argument to suppress advisory warnings about the
+    /// environment, while still stopping on fatal errors
+    #[arg(long, action)]
+    pub no_warnings: bool,
+
+    /// Pass this argument to not print commands run to build the
+    /// binary, nor their output, nor progress of long steps, only
+    /// printing the render commands (errors are still printed)
+    #[arg(short, long, action)]
+    pub quiet: bool,
+
+    /// Directory where the compiled binary, debugger script, and
+    /// intermediate files are written (default: current directory)
+    #[arg(long, value_name = "DIR")]
+    pub out_dir: Option<PathBuf>,
+
+    /// Prefix of the names of written files, such as `<PREFIX>.out`
+    /// and `<PREFIX>_gdb.py`
+    #[arg(long, value_name = "PREFIX", default_value = "a")]
+    pub name_prefix: String,
+
+    /// Pass this argument to include debug info when compiling
+    #[arg(long, action)]
+    pub debug_info: bool,
+
+    /// Pass this argument to write `.vscode/launch.json` to the output
+    /// directory, overwriting it, for watching frames in VSCode's debug
+    /// console (requires `--debug-info`, and gdb with the C/C++
+    /// extension or lldb with the CodeLLDB extension)
+    #[arg(long, action)]
+    pub emit_vscode: bool,
+
+    /// Debugger expression evaluated when each frame is hit, only
+    /// rendering the frame if non-zero (e.g. a global exported by
+    /// custom input, cast if compiled without debug info, such as
+    /// `*(char *)&render_frame`)
+    #[arg(long)]
+    pub frame_condition: Option<String>,
+
+    /// Pass this argument to keep a breakpoint for each frame,
+    /// instead of merging consecutive frames with the same lines
+    /// into a single breakpoint holding their summed delay
+    #[arg(long, action)]
+    pub no_frame_merge: bool,
+
+    /// Pass this argument to pause the gdb script with Ctrl-C, then
+    /// draw one frame at a time with the `backgif-step` command, or
+    /// resume with the `backgif-resume` command
+    #[arg(long, action)]
+    pub interactive: bool,
+
+    /// Pass this argument to keep the gdb and lldb scripts on tempo,
+    /// waiting for each frame only until its scheduled time, instead
+    /// of its whole delay after printing the backtrace and reloading
+    /// symbols (frames are drawn without waiting when behind)
+    #[arg(long, action)]
+    pub precise_timing: bool,
+
+    /// File the lldb script dumps memory to on each frame, for
+    /// reloading symbols with custom input, e.g. on a tmpfs (default:
+    /// a file in `$TMPDIR` named after the script and lldb's pid)
+    #[arg(long, value_name = "PATH")]
+    pub lldb_mem_path: Option<PathBuf>,
+
+    /// Pass this argument to have the lldb script dump memory to an
+    /// in-memory file instead, with `memfd_create` (Linux only, falls
+    /// back to `--lldb-mem-path` elsewhere)
+    #[arg(long, action)]
+    pub lldb_memfd: bool,
+
+    /// Pass this argument to write a gdb command file (`-x`) instead
+    /// of a Python script, for gdb builds without Python support
+    /// (sleeps in a shell between frames, less precise)
+    #[arg(long, action)]
+    pub gdb_commands: bool,
+
+    /// Address of `.text` in binaries compiled from custom input,
+    /// in hex with a `0x` prefix or in decimal, aligned to a page
+    /// (default: 0x401000)
+    #[arg(long, value_name = "ADDR", value_parser = parse_addr)]
+    pub text_addr: Option<u64>,
+
+    /// Address of `.data` embedding the binary in binaries compiled
+    /// from custom input, for reloading symbols, aligned to a page
+    /// (default: 0 with gdb, 0x1000 with lldb)
+    #[arg(long, value_name = "ADDR", value_parser = parse_addr)]
+    pub data_addr: Option<u64>,
+
+    /// Word size of the compiled binary, where 32-bit binaries are
+    /// compiled with `-m32` (requires 32-bit toolchain support)
+    #[arg(long, value_enum, default_value_t=Bits::X64)]
+    pub bits: Bits,
+
+    /// Path to the gdb executable, in printed render commands
+    #[arg(long, value_name = "PATH", default_value = "gdb")]
+    pub gdb_path: String,
+
+    /// Path to the lldb executable, in printed render commands
+    #[arg(long, value_name = "PATH", default_value = "lldb")]
+    pub lldb_path: String,
+
+    /// Path to the rr executable, in printed record and replay commands
+    #[arg(long, value_name = "PATH", default_value = "rr")]
+    pub rr_path: String,
+
+    /// Path to the compiler executable, defaulting to gcc (gdb),
+    /// clang (lldb), or clang-cl (windbg)
+    #[arg(long, value_name = "PATH")]
+    pub compiler_path: Option<String>,
+
+    /// Extra compiler flags, separated by whitespace and appended to
+    /// the fixed arguments (e.g. `"-I include -O1"`)
+    #[arg(long, value_name = "FLAGS", allow_hyphen_values = true)]
+    pub cflags: Option<String>,
+
+    /// C source or header included before custom input, e.g. to
+    /// split animation logic across files (can be repeated). Its
+    /// directory is searched for the files it includes
+    #[arg(long, value_name = "PATH")]
+    pub include: Vec<PathBuf>,
+
+    /// Path to the linker executable, used with custom input
+    #[arg(long, value_name = "PATH", default_value = "ld")]
+    pub ld_path: String,
+
+    /// Pass this argument to check the Python syntax of the
+    /// generated debugger script, requires `python3`
+    #[arg(long, action)]
+    pub check_script: bool,
+
+    /// Pass this argument to print the distribution of color
+    /// differences between frame dots and their closest palette
+    /// colors, where a large mean signals poor palette coverage
+    #[arg(long, action)]
+    pub palette_stats: bool,
+
+    /// Index of the first frame to convert, for converting part
+    /// of the input (out of range values are clamped)
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        allow_negative_numbers = true
+    )]
+    pub start_frame: i64,
+
+    /// Maximum number of frames to convert, starting from
+    /// `--start-frame` (default: all remaining frames)
+    #[arg(long, value_name = "N", allow_negative_numbers = true)]
+    pub max_frames: Option<i64>,
+
+    /// Fail if exactly this number of frames isn't selected from input,
+    /// after `--start-frame` and `--max-frames`, checked before
+    /// compiling (e.g. to validate inputs in CI)
+    #[arg(long, value_name = "N")]
+    pub expect_frames: Option<usize>,
+
+    /// Warn before compiling binaries defining more than this number
+    /// of symbols, one per frame line drawn, which take long to build
+    /// and load in debuggers
+    #[arg(long, value_name = "N", default_value_t = 100000)]
+    pub symbol_warning: usize,
+
+    /// Pass this argument to play frames in the terminal, without
+    /// compiling, until interrupted with Ctrl-C or after `--loops`
+    /// plays, for quickly trying out renderers and sizes
+    #[arg(long, action)]
+    pub preview: bool,
+
+    /// Pass this argument to print the generated C source to stdout,
+    /// without compiling or writing any files (e.g. to compile
+    /// manually with `--emit-source > anim.c`)
+    #[arg(long, action)]
+    pub emit_source: bool,
+
+    /// Pass this argument to keep intermediate files (e.g. generated
+    /// C source, object files, linker scripts) after building, which
+    /// are otherwise removed, leaving the binary and debugger script
+    #[arg(long, action)]
+    pub keep_temps: bool,
+
+    /// Directory to write each frame's lines to, as files that
+    /// can be printed on their own (e.g. `cat frame_01.txt`)
+    #[arg(long, value_name = "DIR")]
+    pub emit_snippets: Option<PathBuf>,
+
+    /// GIF file to write frames to as rendered, with each frame dot
+    /// drawn as a square in the color approximated by the renderer's
+    /// palette, for checking the result without a debugger (requires
+    /// GIF input, and doesn't draw overlay text)
+    #[arg(long, value_name = "PATH")]
+    pub verify_gif: Option<PathBuf>,
+
+    /// asciinema v2 recording to write frames to, as printed by the
+    /// debugger, for playing with `asciinema play` without a debugger
+    /// (played once if frames loop forever)
+    #[arg(long, value_name = "PATH")]
+    pub cast: Option<PathBuf>,
+
+    /// Bash script to write frames to, printing them with `printf`
+    /// and waiting for their delay with `sleep`, for sharing the
+    /// animation without a debugger (e.g. `bash player.sh`)
+    #[arg(long, value_name = "PATH")]
+    pub player_script: Option<PathBuf>,
+
+    /// Only render this color channel
+    #[arg(long, value_enum)]
+    pub channel: Option<Channel>,
+
+    /// Crop each GIF frame to the region with its top left corner
+    /// at column X and row Y, W dots wide and H dots high, before
+    /// resizing (regions out of bounds are clamped)
+    #[arg(long, value_name = "X,Y,W,H", value_parser = parse_crop)]
+    pub crop: Option<[u16; 4]>,
+
+    /// Width and height proportions of terminal cells, so that GIF
+    /// frames are stretched vertically to keep their aspect ratio
+    /// unless both `--width` and `--height` are given (the default
+    /// keeps frames unchanged with renderers drawing square pixels,
+    /// e.g. truecolor dots taking 2 columns, or half-blocks taking
+    /// half of a cell, but halves the height of ascii frames)
+    #[arg(long, value_name = "W:H", default_value = "1:2", value_parser = parse_cell_aspect)]
+    pub cell_aspect: [u16; 2],
+
+    /// Pass this argument to mirror GIF frames left to right
+    #[arg(long, action)]
+    pub flip_horizontal: bool,
+
+    /// Pass this argument to mirror GIF frames top to bottom
+    /// (e.g. for GIFs exported upside-down)
+    #[arg(long, action)]
+    pub flip_vertical: bool,
+
+    /// Pass this argument to invert the colors of GIF frames
+    #[arg(long, action)]
+    pub invert: bool,
+
+    /// Factor scaling the brightness of GIF frames in linear light,
+    /// e.g. 1.5 for frames that render too dark
+    #[arg(long, default_value_t = 1.0)]
+    pub brightness: f32,
+
+    /// Factor scaling the contrast of GIF frames in linear light,
+    /// around mid gray
+    #[arg(long, default_value_t = 1.0)]
+    pub contrast: f32,
+
+    /// Blend each frame with this fraction of the previous frame,
+    /// smoothing fast motion at low frame rates (0 disables blending)
+    #[arg(long, value_name = "ALPHA", default_value_t = 0.0)]
+    pub motion_blur: f32,
+
+    /// Pass this argument to dither GIF frames with Floyd-Steinberg
+    /// error diffusion, reducing banding of colors approximated by
+    /// the renderer's palette (ignored by renderers without a palette)
+    #[arg(long, action)]
+    pub dither: bool,
+
+    /// Reduce the colors of all GIF frames to at most this many, by
+    /// median cut, so that similar frame dots are drawn by the same
+    /// frame lines, shrinking the binary
+    #[arg(long, value_name = "N")]
+    pub max_colors: Option<usize>,
+
+    /// Text shown in a title card before the animation, drawn
+    /// in block characters (letters, digits, and spaces)
+    #[arg(long)]
+    pub caption: Option<String>,
+
+    /// Title card delay in units of 10 ms
+    #[arg(long, default_value_t = 200)]
+    pub caption_delay: u16,
+
+    /// Number of times frames are played before the debugger
+    /// quits, or 0 to loop forever (default: GIF loop count)
+    #[arg(long, value_name = "N")]
+    pub loops: Option<u32>,
+
+    /// Number of threads formatting GIF frames in parallel
+    /// (default: number of cores)
+    #[arg(long, value_name = "N")]
+    pub jobs: Option<usize>,
+
+    /// Custom frame delay in units of 10 ms, overriding the delay
+    /// of every frame
+    #[arg(long)]
+    pub delay: Option<u16>,
+
+    /// Custom frame rate in frames per second, converted to
+    /// the nearest frame delay (video input is also sampled at it)
+    #[arg(long, conflicts_with = "delay")]
+    pub fps: Option<f32>,
+
+    /// Minimum GIF frame delay in units of 10 ms, raising shorter
+    /// delays as browsers do (ignored with `--delay` or `--fps`)
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    pub min_delay: u16,
+
+    /// Custom frame height in number of dots, or height each frame
+    /// is resized to with GIF or directory input (GIF frames keep
+    /// their aspect ratio if only one dimension is given)
+    #[arg(long)]
+    pub height: Option<u16>,
+
+    /// Custom frame width in number of dots, or width each frame
+    /// is resized to with GIF or directory input
+    #[arg(long)]
+    pub width: Option<u16>,
+
+    /// Pass this argument to downscale GIF frames that don't fit the
+    /// terminal, keeping their aspect ratio, if neither `--width` nor
+    /// `--height` is given (otherwise frames are only warned about)
+    #[arg(long, action, conflicts_with_all = ["width", "height"])]
+    pub fit: bool,
+
+    /// Seed passed to `init()` of custom input, for reproducing or
+    /// varying procedural animations
+    #[arg(long, default_value_t = 123)]
+    pub seed: u64,
+}
+
+/// Parse a crop region from comma-separated `X,Y,W,H`.
+fn parse_crop(value: &str) -> Result<[u16; 4], String> {
+    let crop = value
+        .split(',')
+        .map(|n| n.trim().parse::<u16>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let crop = <[u16; 4]>::try_from(crop)
+        .map_err(|crop| format!("expected 4 values X,Y,W,H, got {}", crop.len()))?;
+    if crop[2] == 0 || crop[3] == 0 {
+        return Err(String::from("width and height must be greater than 0"));
+    }
+
+    Ok(crop)
+}
+
+/// Parse cell proportions from colon-separated `W:H`.
+fn parse_cell_aspect(value: &str) -> Result<[u16; 2], String> {
+    let aspect = value
+        .split(':')
+        .map(|n| n.trim().parse::<u16>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let aspect = <[u16; 2]>::try_from(aspect)
+        .map_err(|aspect| format!("expected 2 values W:H, got {}", aspect.len()))?;
+    if aspect.contains(&0) {
+        return Err(String::from("width and height must be greater than 0"));
+    }
+
+    Ok(aspect)
+}
+
+/// Parse a page-aligned section address, in hex with a `0x` prefix
+/// or in decimal.
+fn parse_addr(value: &str) -> Result<u64, String> {
+    let addr = match value.strip_prefix("0x").or(value.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse::<u64>(),
+    }
+    .map_err(|e| e.to_string())?;
+    if addr % 0x1000 != 0 {
+        return Err(format!(
+            "address 0x{:x} not aligned to a page (0x1000)",
+            addr
+        ));
+    }
+
+    Ok(addr)
+}
+
+impl Config {
+    /// Options converting `file`, with the default value of other options.
+    pub fn new(file: impl Into<PathBuf>) -> Self {
+        // Placeholder for the required argument, replaced below.
+        let mut config = Self::parse_from(["backgif", "FILE"]);
+        config.file = file.into();
+        config
+    }
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum Debugger {
+    GDB,
+    LLDB,
+
+    /// Console debugger `cdb`, reading symbols from a PE executable
+    #[value(name = "windbg")]
+    WinDbg,
+
+    /// Record and replay with `rr`, rendering the replay in gdb
+    #[value(name = "rr")]
+    RR,
+
+    /// radare2, rendering with an r2pipe script
+    #[value(name = "r2")]
+    R2,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum Bits {
+    /// i386 binaries
+    #[value(name = "32")]
+    X86,
+
+    /// x86-64 binaries
+    #[value(name = "64")]
+    X64,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum InputFormat {
+    /// C source file with functions for building custom frames,
+    /// declared in `backgif.h` (see `--emit-header`)
+    ///
+    /// ```c
+    /// // Called at the beginning of start function, supplying a
+    /// // `seed` (see `--seed`) for the initial state of PRNGs, along with the
+    /// // configured frame width `w` and height `h`.
+    /// void init(uint64_t seed, uint16_t w, uint16_t h);
+    ///
+    /// // Called at the beginning of each frame.
+    /// void update_frame();
+    ///
+    /// // Renders frame line `n` containing up to
+    /// // `width` dots, updating the corresponding symbol at `addr`.
+    /// // First dot is after frame line prefix `offs`.
+    /// void draw_line(uint8_t *addr, uint8_t offs, uint16_t n);
+    /// ```
+    C,
+
+    /// GIF binary file
+    GIF,
+
+    /// PNG or APNG binary file
+    APNG,
+
+    /// Still image file (PNG, JPEG, or BMP), drawn as a single
+    /// frame (e.g. a logo shown as a splash screen)
+    Image,
+
+    /// Directory of image files (including QOI files), or glob
+    /// pattern matching them (e.g. `"frames/*.png"`), one per frame,
+    /// in natural order of file names (e.g. `frame_2.png` before
+    /// `frame_10.png`)
+    Dir,
+
+    /// UTF-8 text file with a block of lines per frame, separated by
+    /// `--text-delimiter` lines, each drawn as a frame line as-is, so
+    /// that they can include escape sequences (e.g. ANSI art)
+    Text,
+
+    /// QOI image file, drawn as a single frame, or directory of QOI
+    /// files ordered as with directory input
+    QOI,
+
+    /// Video file (e.g. MP4), with frames and their timestamps
+    /// extracted by `ffmpeg` (requires ffmpeg), sampled at `--fps`
+    /// if given
+    Video,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum RowOrder {
+    /// Top line printed first, relying on the debugger's line breaks
+    TopDown,
+
+    /// Bottom line printed first, positioning each line on screen
+    BottomUp,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum Channel {
+    /// Red channel, with other channels zeroed
+    R,
+
+    /// Green channel, with other channels zeroed
+    G,
+
+    /// Blue channel, with other channels zeroed
+    B,
+
+    /// Luma, mapped to grayscale
+    Luma,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum RenderFormat {
+    /// UTF-8 emoji codepoints
+    Emoji,
+
+    /// 24-bit truecolor for virtual terminal emulators
+    TrueColor,
+
+    /// 8-bit xterm-256 colors, for terminals without truecolor
+    Ansi256,
+
+    /// 4-bit 16 colors, for serial consoles and older emulators
+    Ansi16,
+
+    /// 24-bit truecolor with two frame dots per cell, halving
+    /// the number of frame lines
+    HalfBlock,
+
+    /// Monochrome braille patterns with 2x4 frame dots per cell
+    Braille,
+
+    /// Characters picked from a ramp by luminance, for environments
+    /// without color support
+    Ascii,
+
+    /// Sixel images drawing six rows of frame dots per frame line,
+    /// for terminals with sixel graphics support
+    Sixel,
+
+    /// Kitty graphics protocol images drawing each frame with a
+    /// single frame line, for terminals supporting it
+    Kitty,
+}
+
+/// Emoji formatter, or the error building it, along with the palette
+/// it was built from, whether it persists caches on disk, and its
+/// color metric.
+type SharedEmojiFormatter = (
+    Option<PathBuf>,
+    bool,
+    ColorMetric,
+    Result<EmojiFrameFormatter, String>,
+);
+
+/// State shared between runs, which is expensive to build.
+#[derive(Default)]
+pub struct Cache {
+    emoji_formatter: OnceCell<SharedEmojiFormatter>,
+}
+
+impl Cache {
+    /// Emoji formatter for `palette`, or the error building it, unless
+    /// the shared formatter was built from another palette, another
+    /// `persist` value, or another `metric`.
+    pub fn emoji_formatter_for(
+        &self,
+        palette: &Option<PathBuf>,
+        persist: bool,
+        metric: ColorMetric,
+    ) -> Option<Result<&EmojiFrameFormatter, &String>> {
+        let (cached_palette, cached_persist, cached_metric, formatter) =
+            self.emoji_formatter.get_or_init(|| {
+                (
+                    palette.to_owned(),
+                    persist,
+                    metric,
+                    EmojiFrameFormatter::new(palette.as_deref(), persist, metric),
+                )
+            });
+
+        (cached_palette == palette && *cached_persist == persist && *cached_metric == metric)
+            .then_some(formatter.as_ref())
+    }
+}
+
+/// Columns and rows of the terminal attached to standard output,
+/// or else of `COLUMNS` and `LINES`.
+fn terminal_size() -> Option<(u16, u16)> {
+    terminal_size::terminal_size()
+        .map(|(Width(columns), Height(rows))| (columns, rows))
+        .or_else(|| {
+            let var = |name| std::env::var(name).ok()?.parse().ok();
+            Some((var("COLUMNS")?, var("LINES")?))
+        })
+}
+
+/// Print an advisory warning, unless suppressed with `--no-warnings`.
+fn warn(config: &Config, message: &str) {
+    if !config.no_warnings {
+        eprintln!("{}\n", message.red().bold());
+    }
+}
+
+/// Frames selected by `config.start_frame` and `config.max_frames`,
+/// which are clamped to keep at least one frame.
+fn select_frames(config: &Config, mut frame_infos: Vec<FrameInfo>) -> Vec<FrameInfo> {
+    let last = frame_infos.len() as i64 - 1;
+    let start = config.start_frame.clamp(0, last);
+    if start != config.start_frame {
+        warn(
+            config,
+            &format!(
+                "[!] Start frame {} out of range 0..={}, clamped to {}.",
+                config.start_frame, last, start
+            ),
+        );
+    }
+    let count = config.max_frames.map_or(last - start + 1, |max_frames| {
+        if max_frames < 1 {
+            warn(
+                config,
+                &format!("[!] Max frames {} out of range, clamped to 1.", max_frames),
+            );
+        }
+        max_frames.clamp(1, last - start + 1)
+    });
+    frame_infos.drain(..start as usize);
+    frame_infos.truncate(count as usize);
+
+    frame_infos
+}
+
+/// Error for an option that can't be used, or can't be combined
+/// with other options.
+fn invalid_option(message: impl Into<String>) -> ConvError {
+    ConvError::InvalidOption {
+        message: message.into(),
+    }
+}
+
+/// Error for a file at `path` that can't be opened, read, or written.
+fn io_error(path: impl Into<PathBuf>) -> impl FnOnce(std::io::Error) -> ConvError {
+    let path = path.into();
+    move |source| ConvError::Io { path, source }
+}
+
+/// Convert the input of `config`, writing artifacts named after
+/// `config.name_prefix` to `config.out_dir`, along with the commands
+/// that render them. Invalid combinations of options are returned as
+/// `ConvError::InvalidOption`.
+pub fn convert(config: &Config) -> Result<Artifacts, ConvError> {
+    convert_with_cache(config, &Cache::default())
+}
+
+/// Convert the input of `config`, reusing state from previous
+/// conversions in `cache`.
+pub fn convert_with_cache(config: &Config, cache: &Cache) -> Result<Artifacts, ConvError> {
+    let mut uncached_emoji_formatter;
+    let formatter: &dyn FrameFormatter = match config.renderer {
+        // Shared formatter is only used with the default blank.
+        RenderFormat::Emoji => match config
+            .blank
+            .is_none()
+            .then(|| {
+                cache.emoji_formatter_for(
+                    &config.emoji_palette,
+                    !config.no_cache,
+                    config.color_metric,
+                )
+            })
+            .flatten()
+        {
+            Some(formatter) => formatter.map_err(invalid_option)?,
+            None => {
+                uncached_emoji_formatter = EmojiFrameFormatter::new(
+                    config.emoji_palette.as_deref(),
+                    !config.no_cache,
+                    config.color_metric,
+                )
+                .map_err(invalid_option)?;
+                uncached_emoji_formatter.blank = config.blank.clone();
+                &uncached_emoji_formatter
+            }
+        },
+        RenderFormat::TrueColor => &TrueColorFrameFormatter {
+            minimal_escapes: config.minimal_escapes,
+            fg_glyph: config.fg_glyph,
+            blank: config.blank.clone(),
+            hide_cursor: !config.no_hide_cursor,
+        },
+        RenderFormat::Ansi256 => &Ansi256FrameFormatter::new(
+            config.minimal_escapes,
+            !config.no_hide_cursor,
+            config.blank.clone(),
+            config.color_metric,
+        ),
+        RenderFormat::Ansi16 => &Ansi16FrameFormatter::new(
+            config.minimal_escapes,
+            !config.no_hide_cursor,
+            config.blank.clone(),
+            config.color_metric,
+        ),
+        RenderFormat::HalfBlock => &HalfBlockFrameFormatter {
+            lines: TrueColorFrameFormatter {
+                minimal_escapes: config.minimal_escapes,
+                fg_glyph: false,
+                blank: None,
+                hide_cursor: !config.no_hide_cursor,
+            },
+        },
+        RenderFormat::Braille => &BrailleFrameFormatter {
+            lines: TrueColorFrameFormatter {
+                minimal_escapes: config.minimal_escapes,
+                fg_glyph: false,
+                blank: None,
+                hide_cursor: !config.no_hide_cursor,
+            },
+            threshold: config.threshold,
+        },
+        RenderFormat::Ascii => &AsciiFrameFormatter {
+            lines: TrueColorFrameFormatter {
+                minimal_escapes: config.minimal_escapes,
+                fg_glyph: false,
+                blank: None,
+                hide_cursor: !config.no_hide_cursor,
+            },
+            ramp: config.ascii_ramp.chars().collect(),
+        },
+        RenderFormat::Sixel => &SixelFrameFormatter {
+            lines: TrueColorFrameFormatter {
+                minimal_escapes: config.minimal_escapes,
+                fg_glyph: false,
+                blank: None,
+                hide_cursor: !config.no_hide_cursor,
+            },
+        },
+        RenderFormat::Kitty => &KittyFrameFormatter {
+            lines: TrueColorFrameFormatter {
+                minimal_escapes: config.minimal_escapes,
+                fg_glyph: false,
+                blank: None,
+                hide_cursor: !config.no_hide_cursor,
+            },
+        },
+    };
+    if config.ascii_ramp.is_empty() {
+        return Err(invalid_option(
+            "ASCII ramp must have at least one character.",
+        ));
+    }
+    if config.overlay_index && formatter.groups_pixels() {
+        return Err(invalid_option(format!(
+            "Frame index overlay not supported with {} formatter, overlay text needs a cell per frame dot.",
+            config.renderer.to_possible_value().unwrap().get_name()
+        )));
+    }
+    if config.single_line && matches!(config.renderer, RenderFormat::Emoji) {
+        return Err(invalid_option(
+            "Single frame line not supported with emoji formatter, lines can't be positioned.",
+        ));
+    }
+    if config.single_line && matches!(config.row_order, RowOrder::BottomUp) {
+        return Err(invalid_option(
+            "Single frame line doesn't have a row order, it draws all lines at once.",
+        ));
+    }
+    if config.fg_glyph && !matches!(config.renderer, RenderFormat::TrueColor) {
+        return Err(invalid_option(
+            "Foreground glyphs only supported with truecolor formatter.",
+        ));
+    }
+    if let Some(blank) = &config.blank {
+        let renderer = config.renderer.to_possible_value().unwrap();
+        let result = match config.renderer {
+            RenderFormat::Emoji => EmojiFrameFormatter::validate_blank(blank),
+            RenderFormat::TrueColor | RenderFormat::Ansi256 | RenderFormat::Ansi16 => {
+                TrueColorFrameFormatter::validate_blank(blank)
+            }
+            _ => {
+                return Err(invalid_option(format!(
+                    "Custom blank not supported with {} formatter.",
+                    renderer.get_name()
+                )));
+            }
+        };
+        if let Err(e) = result {
+            return Err(invalid_option(format!(
+                "Invalid blank {:?} for {} formatter: {}.",
+                blank,
+                renderer.get_name(),
+                e
+            )));
+        }
+    }
+    if config.minimal_escapes && matches!(config.renderer, RenderFormat::Emoji) {
+        return Err(invalid_option(
+            "Minimal escapes not supported with emoji formatter, lines can't be positioned.",
+        ));
+    }
+    if !(0.0..=1.0).contains(&config.motion_blur) {
+        return Err(invalid_option(format!(
+            "Motion blur must be between 0 and 1, got {}.",
+            config.motion_blur
+        )));
+    }
+    for (name, factor) in [
+        ("Brightness", config.brightness),
+        ("Contrast", config.contrast),
+    ] {
+        if !(factor.is_finite() && factor >= 0.0) {
+            return Err(invalid_option(format!(
+                "{} must be a non-negative number, got {}.",
+                name, factor
+            )));
+        }
+    }
+    if config.preview && config.emit_source {
+        return Err(invalid_option(
+            "Preview can't be combined with source emission, neither compiles frames.",
+        ));
+    }
+    if config.jobs == Some(0) {
+        return Err(invalid_option("Number of jobs must be at least 1."));
+    }
+    if config.height == Some(0) || config.width == Some(0) {
+        return Err(invalid_option("Frame height and width must be at least 1."));
+    }
+    if let Some(caption) = &config.caption
+        && let Err(c) = conv::font::render(caption)
+    {
+        return Err(invalid_option(format!(
+            "Unsupported character {:?} in caption, only letters, digits, and spaces can be drawn.",
+            c
+        )));
+    }
+    if matches!(config.format, InputFormat::C)
+        && (config.height.is_none() || config.width.is_none())
+    {
+        return Err(invalid_option(
+            "Frame height and width required for custom input, pass `--height` and `--width` with the dimensions drawn by the program.",
+        ));
+    }
+    let cflags: Vec<&str> = config
+        .cflags
+        .as_deref()
+        .map_or(vec![], |cflags| cflags.split_whitespace().collect());
+    if let Some(flag) = cflags.iter().find(|flag| CONFLICTING_CFLAGS.contains(flag)) {
+        return Err(invalid_option(format!(
+            "Compiler flag `{}` not supported, binaries are always built with `-nostdlib -static` to a fixed output path.",
+            flag
+        )));
+    }
+    let bottom_up = matches!(config.row_order, RowOrder::BottomUp);
+    if bottom_up && matches!(config.renderer, RenderFormat::Emoji) {
+        return Err(invalid_option(
+            "Bottom-up row order not supported with emoji formatter, lines can't be positioned.",
+        ));
+    }
+    if config.file.as_os_str() == "-" && !matches!(config.format, InputFormat::GIF) {
+        return Err(invalid_option(
+            "Standard input only supported with GIF input.",
+        ));
+    }
+    if config.verify_gif.is_some()
+        && !matches!(config.format, InputFormat::GIF | InputFormat::Image)
+    {
+        return Err(invalid_option(
+            "Verification GIF only supported with GIF or image input.",
+        ));
+    }
+    if config.max_colors == Some(0) {
+        return Err(invalid_option("Color reduction needs at least 1 color."));
+    }
+    let image_input = match config.format {
+        InputFormat::APNG => Some("APNG"),
+        InputFormat::Dir => Some("directory"),
+        InputFormat::Text => Some("text"),
+        InputFormat::QOI => Some("QOI"),
+        InputFormat::Video => Some("video"),
+        InputFormat::C | InputFormat::GIF | InputFormat::Image => None,
+    };
+    if let Some(image_input) = image_input {
+        if config.overlay_index {
+            return Err(invalid_option(format!(
+                "Frame index overlay not supported with {} input.",
+                image_input
+            )));
+        }
+
+        if config.motion_blur > 0.0 {
+            return Err(invalid_option(format!(
+                "Motion blur not supported with {} input.",
+                image_input
+            )));
+        }
+
+        if config.dither {
+            return Err(invalid_option(format!(
+                "Dithering not supported with {} input.",
+                image_input
+            )));
+        }
+
+        if config.max_colors.is_some() {
+            return Err(invalid_option(format!(
+                "Color reduction not supported with {} input.",
+                image_input
+            )));
+        }
+
+        if config.crop.is_some() {
+            return Err(invalid_option(format!(
+                "Crop not supported with {} input.",
+                image_input
+            )));
+        }
+
+        if config.flip_horizontal || config.flip_vertical {
+            return Err(invalid_option(format!(
+                "Flip not supported with {} input.",
+                image_input
+            )));
+        }
+
+        if config.fit {
+            return Err(invalid_option(format!(
+                "Fit not supported with {} input.",
+                image_input
+            )));
+        }
+
+        if config.cell_aspect != [1, 2] {
+            return Err(invalid_option(format!(
+                "Cell aspect not supported with {} input.",
+                image_input
+            )));
+        }
+
+        if config.caption.is_some() {
+            return Err(invalid_option(format!(
+                "Caption not supported with {} input.",
+                image_input
+            )));
+        }
+
+        if config.invert || config.brightness != 1.0 || config.contrast != 1.0 {
+            return Err(invalid_option(format!(
+                "Color adjustments not supported with {} input.",
+                image_input
+            )));
+        }
+
+        if config.channel.is_some() {
+            return Err(invalid_option(format!(
+                "Channel isolation not supported with {} input.",
+                image_input
+            )));
+        }
+    }
+    if matches!(config.format, InputFormat::Text)
+        && (config.height.is_some() || config.width.is_some())
+    {
+        return Err(invalid_option(
+            "Frame height and width not supported with text input, frames are drawn as written.",
+        ));
+    }
+    let terminal_size = (config.height.is_none() && config.width.is_none())
+        .then(terminal_size)
+        .flatten();
+    if config.fit && terminal_size.is_none() {
+        warn(
+            config,
+            "[!] Can't detect terminal size, frames aren't fit to it.",
+        );
+    }
+    let verbosity = if config.quiet {
+        Verbosity::Quiet
+    } else {
+        Verbosity::Normal
+    };
+    let parser: &dyn FrameParser = match config.format {
+        InputFormat::C => &CustomFrameParser {
+            formatter,
+            bottom_up,
+            height: config.height.unwrap(),
+            width: config.width.unwrap(),
+        },
+        InputFormat::GIF | InputFormat::Image => &GifFrameParser {
+            formatter,
+            bottom_up,
+            overlay_index: config.overlay_index,
+            motion_blur: config.motion_blur,
+            caption: config.caption.to_owned(),
+            caption_delay: config.caption_delay,
+            color_matrix: config.channel.as_ref().map(|channel| match channel {
+                Channel::R => [[1.0, 0.0, 0.0], [0.0; 3], [0.0; 3]],
+                Channel::G => [[0.0; 3], [0.0, 1.0, 0.0], [0.0; 3]],
+                Channel::B => [[0.0; 3], [0.0; 3], [0.0, 0.0, 1.0]],
+                // ITU-R BT.601 luma coefficients
+                Channel::Luma => [[0.299, 0.587, 0.114]; 3],
+            }),
+            adjustments: ColorAdjustments {
+                invert: config.invert,
+                brightness: config.brightness,
+                contrast: config.contrast,
+            },
+            single_line: config.single_line,
+            match_per_dot: config.palette_stats,
+            dither: config.dither,
+            max_colors: config.max_colors,
+            keep_colors: config.verify_gif.is_some(),
+            jobs: config.jobs.unwrap_or(0),
+            height: config.height,
+            width: config.width,
+            min_delay: config.min_delay,
+            crop: config.crop,
+            flip_horizontal: config.flip_horizontal,
+            flip_vertical: config.flip_vertical,
+            no_warnings: config.no_warnings,
+            terminal_size,
+            fit: config.fit,
+            cell_aspect: config.cell_aspect,
+            still_image: matches!(config.format, InputFormat::Image),
+            verbosity,
+        },
+        InputFormat::APNG => &ApngFrameParser {
+            formatter,
+            bottom_up,
+            single_line: config.single_line,
+        },
+        InputFormat::Dir => &SequenceFrameParser {
+            formatter,
+            bottom_up,
+            single_line: config.single_line,
+            height: config.height,
+            width: config.width,
+        },
+        InputFormat::Text => &TextFrameParser {
+            formatter,
+            bottom_up,
+            single_line: config.single_line,
+            delimiter: config.text_delimiter.to_owned(),
+        },
+        InputFormat::QOI => &QoiFrameParser {
+            sequence: SequenceFrameParser {
+                formatter,
+                bottom_up,
+                single_line: config.single_line,
+                height: config.height,
+                width: config.width,
+            },
+        },
+        InputFormat::Video => &VideoFrameParser {
+            sequence: SequenceFrameParser {
+                formatter,
+                bottom_up,
+                single_line: config.single_line,
+                height: config.height,
+                width: config.width,
+            },
+            fps: config.fps,
+        },
+    };
+    let compiler: &str = config
+        .compiler_path
+        .as_deref()
+        .unwrap_or(match config.debugger {
+            Debugger::GDB | Debugger::RR | Debugger::R2 => "gcc",
+            Debugger::LLDB => "clang",
+            Debugger::WinDbg => "clang-cl",
+        });
+    let script = &ScriptOptions {
+        debugger: match config.debugger {
+            Debugger::GDB => config.gdb_path.to_owned(),
+            Debugger::LLDB => config.lldb_path.to_owned(),
+            Debugger::WinDbg => String::from("cdb"),
+            Debugger::RR => config.rr_path.to_owned(),
+            Debugger::R2 => String::from("r2"),
+        },
+        frame_condition: config.frame_condition.to_owned(),
+        merge_frames: !config.no_frame_merge,
+        interactive: config.interactive,
+        precise_timing: config.precise_timing,
+        lldb_mem_path: config.lldb_mem_path.to_owned(),
+        lldb_memfd: config.lldb_memfd,
+        gdb_commands: config.gdb_commands,
+        text_addr: config.text_addr,
+        data_addr: config.data_addr,
+    };
+    if config.interactive && !matches!(config.debugger, Debugger::GDB) {
+        return Err(invalid_option(
+            "Interactive controls only supported with gdb, other debugger scripts don't define commands.",
+        ));
+    }
+    if config.precise_timing && !matches!(config.debugger, Debugger::GDB | Debugger::LLDB) {
+        return Err(invalid_option(
+            "Precise timing only supported with gdb and lldb.",
+        ));
+    }
+    if (config.lldb_mem_path.is_some() || config.lldb_memfd)
+        && !(matches!(config.debugger, Debugger::LLDB) && matches!(config.format, InputFormat::C))
+    {
+        return Err(invalid_option(
+            "LLDB memory dumps only used with lldb and custom input.",
+        ));
+    }
+    if config.gdb_commands {
+        if !matches!(config.debugger, Debugger::GDB) {
+            return Err(invalid_option("Command files only written for gdb."));
+        }
+        if matches!(config.format, InputFormat::C) {
+            return Err(invalid_option(
+                "Command files not supported with custom input, symbols are reloaded from the Python script.",
+            ));
+        }
+        if config.interactive || config.precise_timing {
+            return Err(invalid_option(
+                "Command files not supported with interactive controls or precise timing, which require the Python script.",
+            ));
+        }
+    }
+    if !config.include.is_empty() && !matches!(config.format, InputFormat::C) {
+        return Err(invalid_option(
+            "Included files only used with custom input.",
+        ));
+    }
+    if (config.text_addr.is_some() || config.data_addr.is_some())
+        && !matches!(config.format, InputFormat::C)
+    {
+        return Err(invalid_option(
+            "Section addresses only used with custom input, other binaries aren't linked with a linker script.",
+        ));
+    }
+    if config.text_addr == Some(0) {
+        return Err(invalid_option(
+            "Text address must be at least 0x1000, headers are mapped on the page before it.",
+        ));
+    }
+    if !config
+        .name_prefix
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(invalid_option(format!(
+            "Name prefix must only contain ASCII letters, digits, and underscores, got {:?}.",
+            config.name_prefix
+        )));
+    }
+    let artifacts = Artifacts {
+        dir: config.out_dir.to_owned().unwrap_or(PathBuf::from(".")),
+        prefix: config.name_prefix.to_owned(),
+        commands: vec![],
+    };
+    let bits = match config.bits {
+        Bits::X86 => 32,
+        Bits::X64 => 64,
+    };
+    if bits == 32 && matches!(config.debugger, Debugger::WinDbg) {
+        return Err(invalid_option(
+            "32-bit binaries not supported with WinDbg, only ELF binaries can be compiled for i386.",
+        ));
+    }
+    if matches!(config.debugger, Debugger::WinDbg)
+        && let Some(condition) = &config.frame_condition
+        && condition.contains(['"', ';'])
+    {
+        return Err(invalid_option(format!(
+            "Frame condition can't contain `\"` or `;` with WinDbg, since breakpoint commands are quoted, got {:?}.",
+            condition
+        )));
+    }
+    let inner: &dyn FrameConverter = match config.debugger {
+        Debugger::GDB => &GdbFrameConverter {
+            parser,
+            script,
+            artifacts: &artifacts,
+            bits,
+        },
+        Debugger::LLDB => &LldbFrameConverter {
+            parser,
+            script,
+            artifacts: &artifacts,
+            bits,
+        },
+        Debugger::WinDbg => &WinDbgFrameConverter {
+            parser,
+            script,
+            artifacts: &artifacts,
+            bits,
+        },
+        Debugger::RR => &RrFrameConverter {
+            parser,
+            script,
+            artifacts: &artifacts,
+            bits,
+        },
+        Debugger::R2 => &R2FrameConverter {
+            parser,
+            script,
+            artifacts: &artifacts,
+            bits,
+        },
+    };
+    if config.emit_vscode {
+        if !config.debug_info {
+            return Err(invalid_option(
+                "VSCode launch configuration requires `--debug-info`, VSCode reads symbols from debug info.",
+            ));
+        }
+
+        if !matches!(config.debugger, Debugger::GDB | Debugger::LLDB) {
+            return Err(invalid_option(format!(
+                "VSCode launch configuration not supported with {}, only gdb and lldb have VSCode extensions.",
+                config.debugger.to_possible_value().unwrap().get_name()
+            )));
+        }
+    }
+    if matches!(config.debugger, Debugger::WinDbg) && config.debug_info {
+        warn(
+            config,
+            "[!] WinDbg reads patched names from the COFF symbol table, ignoring `--debug-info`.",
+        );
+    }
+    let includes = config
+        .include
+        .iter()
+        .map(|include| {
+            std::fs::canonicalize(include).map_err(|e| {
+                invalid_option(format!(
+                    "Can't find included file {}: {}.",
+                    include.display(),
+                    e
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let converter: &dyn FrameConverter = match config.format {
+        InputFormat::C => {
+            // Only Linux exposes the lowest mappable address in procfs.
+            if cfg!(target_os = "linux") {
+                match std::fs::read_to_string("/proc/sys/vm/mmap_min_addr")
+                    .ok()
+                    .and_then(|min_addr| min_addr.trim().parse::<u64>().ok())
+                {
+                    Some(0) => {}
+                    Some(min_addr) => warn(
+                        config,
+                        &format!(
+                            "[!] Custom input expects `/proc/sys/vm/mmap_min_addr = 0`, got `{}`.",
+                            min_addr
+                        ),
+                    ),
+                    None => warn(
+                        config,
+                        "[!] Can't read `/proc/sys/vm/mmap_min_addr`, custom input expects it to be 0.",
+                    ),
+                }
+            }
+
+            if matches!(config.debugger, Debugger::LLDB) {
+                warn(
+                    config,
+                    "[!] Workaround for llvm-project issue #153772: each frame dumps memory to a temporary file, mind your SSD lifespan (or pass `--lldb-mem-path` on a tmpfs)!",
+                );
+                if !config.debug_info {
+                    warn(
+                        config,
+                        "[!] LLDB does not reload .symtab symbols, consider passing `--debug-info` to instead use .debug_str entries.",
+                    );
+                }
+            }
+
+            if matches!(config.renderer, RenderFormat::Emoji) {
+                return Err(invalid_option(
+                    "Custom input not supported with emoji formatter 😞.",
+                ));
+            }
+
+            if formatter.groups_pixels() {
+                return Err(invalid_option(format!(
+                    "Custom input not supported with {} formatter, frame dots are drawn one per cell.",
+                    config.renderer.to_possible_value().unwrap().get_name()
+                )));
+            }
+
+            if matches!(
+                config.renderer,
+                RenderFormat::Ansi256 | RenderFormat::Ansi16 | RenderFormat::Ascii
+            ) {
+                return Err(invalid_option(format!(
+                    "Custom input not supported with {} formatter, frame dots are drawn with 24-bit colors.",
+                    config.renderer.to_possible_value().unwrap().get_name()
+                )));
+            }
+
+            if bottom_up {
+                return Err(invalid_option(
+                    "Bottom-up row order not supported with custom input.",
+                ));
+            }
+
+            if config.overlay_index {
+                return Err(invalid_option(
+                    "Frame index overlay not supported with custom input.",
+                ));
+            }
+
+            if config.motion_blur > 0.0 {
+                return Err(invalid_option(
+                    "Motion blur not supported with custom input.",
+                ));
+            }
+
+            if config.dither {
+                return Err(invalid_option("Dithering not supported with custom input."));
+            }
+
+            if config.max_colors.is_some() {
+                return Err(invalid_option(
+                    "Color reduction not supported with custom input.",
+                ));
+            }
+
+            if config.crop.is_some() {
+                return Err(invalid_option("Crop not supported with custom input."));
+            }
+
+            if config.flip_horizontal || config.flip_vertical {
+                return Err(invalid_option("Flip not supported with custom input."));
+            }
+
+            if config.fit {
+                return Err(invalid_option("Fit not supported with custom input."));
+            }
+
+            if config.minimal_escapes {
+                return Err(invalid_option(
+                    "Minimal escapes not supported with custom input.",
+                ));
+            }
+
+            if config.fg_glyph {
+                return Err(invalid_option(
+                    "Foreground glyphs not supported with custom input.",
+                ));
+            }
+
+            if config.blank.is_some() {
+                return Err(invalid_option(
+                    "Custom blank not supported with custom input.",
+                ));
+            }
+
+            if config.caption.is_some() {
+                return Err(invalid_option("Caption not supported with custom input."));
+            }
+
+            if config.channel.is_some() {
+                return Err(invalid_option(
+                    "Channel isolation not supported with custom input.",
+                ));
+            }
+
+            if config.invert || config.brightness != 1.0 || config.contrast != 1.0 {
+                return Err(invalid_option(
+                    "Color adjustments not supported with custom input.",
+                ));
+            }
+
+            if config.single_line {
+                return Err(invalid_option(
+                    "Single frame line not supported with custom input.",
+                ));
+            }
+
+            if config.preview {
+                return Err(invalid_option(
+                    "Preview not supported with custom input, frames are drawn when running.",
+                ));
+            }
+
+            if config.cast.is_some() {
+                return Err(invalid_option(
+                    "Cast not supported with custom input, frames are drawn when running.",
+                ));
+            }
+
+            if config.player_script.is_some() {
+                return Err(invalid_option(
+                    "Player script not supported with custom input, frames are drawn when running.",
+                ));
+            }
+
+            if matches!(config.debugger, Debugger::WinDbg) {
+                return Err(invalid_option(
+                    "Custom input not supported with WinDbg, symbols can only be reloaded from ELF files.",
+                ));
+            }
+
+            if matches!(config.debugger, Debugger::RR) {
+                return Err(invalid_option(
+                    "Custom input not supported with rr, replayed processes don't expose memory to reload symbols from.",
+                ));
+            }
+
+            if matches!(config.debugger, Debugger::R2) {
+                return Err(invalid_option(
+                    "Custom input not supported with radare2, symbols are only read when loading the binary.",
+                ));
+            }
+
+            // Sections of the converter are the ones of the inner converter.
+            if inner.sections_overlap() {
+                return Err(invalid_option(format!(
+                    "Text section at 0x{:x} overlaps data section at 0x{:x}, pass addresses further apart.",
+                    inner.text_section_addr(),
+                    inner.data_section_addr()
+                )));
+            }
+
+            &CustomFrameConverter {
+                inner,
+                file: &config.file,
+                ld: &config.ld_path,
+                height: config.height.unwrap(),
+                width: config.width.unwrap(),
+                seed: config.seed,
+                includes: &includes,
+            }
+        }
+        InputFormat::GIF
+        | InputFormat::Image
+        | InputFormat::APNG
+        | InputFormat::Dir
+        | InputFormat::Text
+        | InputFormat::QOI
+        | InputFormat::Video => inner,
+    };
+
+    if config.dither && formatter.quantize(&[0, 0, 0, 255]).is_none() {
+        warn(
+            config,
+            &format!(
+                "[!] Dithering ignored with {} formatter, it doesn't approximate colors with a palette.",
+                config.renderer.to_possible_value().unwrap().get_name()
+            ),
+        );
+    }
+    if config.palette_stats && matches!(config.renderer, RenderFormat::TrueColor) {
+        warn(
+            config,
+            "[!] Palette stats aren't collected with truecolor formatter, it doesn't match colors.",
+        );
+    }
+    // Formatter may be shared by batch entries, so matches
+    // of previous entries are discarded.
+    formatter.take_match_stats();
+    if let Some(fps) = config.fps
+        && !(fps.is_finite() && fps > 0.0)
+    {
+        return Err(invalid_option(format!(
+            "Frame rate must be a positive number, got {}.",
+            fps
+        )));
+    }
+    let delay = config.fps.map_or(config.delay, |fps| {
+        Some((100.0 / fps).round().min(u16::MAX as f32) as u16)
+    });
+    let Animation { frame_infos, loops } =
+        converter.parse_input(&config.file, config.clear_line, delay)?;
+    let loops = config.loops.unwrap_or(loops);
+    if loops > 0 && matches!(config.debugger, Debugger::WinDbg) {
+        warn(
+            config,
+            "[!] Loop count not supported with WinDbg, frames are looped forever.",
+        );
+    }
+    let frame_infos = select_frames(config, frame_infos);
+    if let Some(expected) = config.expect_frames
+        && frame_infos.len() != expected
+    {
+        return Err(ConvError::UnexpectedFrameCount {
+            expected,
+            actual: frame_infos.len(),
+        });
+    }
+    if let Some(dir) = &config.emit_snippets {
+        write_snippets(&frame_infos, dir).map_err(io_error(dir))?;
+    }
+    if let Some(path) = &config.verify_gif {
+        write_verify_gif(&frame_infos, path, loops).map_err(io_error(path))?;
+    }
+    if let Some(path) = &config.cast {
+        // Recordings are sized to the current terminal, since frames
+        // are drawn for it.
+        let size = terminal_size().unwrap_or((80, 24));
+        write_cast(&frame_infos, path, loops, size).map_err(io_error(path))?;
+    }
+    if let Some(path) = &config.player_script {
+        write_player_script(&frame_infos, path, loops).map_err(io_error(path))?;
+    }
+    if config.preview {
+        preview(&frame_infos, loops).map_err(io_error("standard output"))?;
+        return Ok(artifacts);
+    }
+    let (symbol_count, size) = estimate_symbols(&frame_infos, bits);
+    if symbol_count > config.symbol_warning {
+        warn(
+            config,
+            &format!(
+                "[!] Frames define {} symbols (above {}), for a binary of about {} MB, reduce `--width`, `--height`, or `--max-frames` to build faster.",
+                symbol_count,
+                config.symbol_warning,
+                size.div_ceil(1_000_000)
+            ),
+        );
+    }
+    let (start_name, start_tmp_name) = parser.to_frameline_names(
+        formatter,
+        // Entrypoint symbol (overrides default symbol `_start`)
+        // is not used as frame line, so it can be filled with
+        // "Zero Width No-Break Space" (ZWNBSP).
+        &String::from_utf8(b"\xef\xbb\xbf".repeat(4)).unwrap(),
+        0,
+        false,
+        config.clear_line,
+    );
+
+    if config.emit_source {
+        converter
+            .write_src(
+                &mut std::io::stdout().lock(),
+                &frame_infos,
+                &start_tmp_name,
+                config.debug_info,
+            )
+            .map_err(io_error("standard output"))?;
+        return Ok(artifacts);
+    }
+
+    std::fs::create_dir_all(&artifacts.dir).map_err(io_error(&artifacts.dir))?;
+    let _temps = TempFiles {
+        paths: converter
+            .temp_names()
+            .iter()
+            .map(|name| artifacts.dir.join(name))
+            .collect(),
+        keep: config.keep_temps,
+    };
+    converter
+        .compile(
+            &frame_infos,
+            &compiler,
+            &cflags,
+            &start_tmp_name,
+            config.debug_info,
+            verbosity,
+        )
+        .map_err(|e| ConvError::Build {
+            message: e.to_string(),
+        })?;
+
+    let bin_info = converter.parse_bin(&artifacts.dir.join(converter.bin_name()))?;
+    converter.patch_bin(
+        &frame_infos,
+        &bin_info.name_to_info,
+        &start_tmp_name,
+        &start_name,
+        bin_info.build_id_desc_offs,
+        verbosity,
+    )?;
+
+    let mut commands = converter.write_dbg_script(
+        &frame_infos,
+        &bin_info.name_to_info,
+        bin_info.size,
+        false,
+        &converter.bin_name(),
+        loops,
+    )?;
+    if config.emit_vscode
+        && let Some(launch) = converter.vscode_launch(&converter.bin_name())
+    {
+        let dir = artifacts.dir.join(".vscode");
+        std::fs::create_dir_all(&dir).map_err(io_error(&dir))?;
+        let launch = serde_json::json!({"version": "0.2.0", "configurations": [launch]});
+        let path = dir.join("launch.json");
+        std::fs::write(&path, serde_json::to_string_pretty(&launch).unwrap())
+            .map_err(io_error(&path))?;
+        commands.push(RenderCommand {
+            title: String::from("Render in VSCode with launch configuration"),
+            command: String::from("code . # then Run > Start Debugging"),
+        });
+    }
+
+    if config.check_script {
+        if converter.script_name().ends_with(".py") {
+            check_script(&artifacts.dir.join(converter.script_name()), verbosity)?;
+        } else {
+            warn(
+                config,
+                &format!(
+                    "[!] Skipping check of {}, not a Python script.",
+                    converter.script_name()
+                ),
+            );
+        }
+    }
+
+    if config.palette_stats
+        && let Some(stats) = formatter.take_match_stats()
+    {
+        let metric = config.color_metric.to_possible_value().unwrap();
+        eprintln!(
+            "\n{}",
+            format!("Palette color differences ({}):", metric.get_name())
+                .purple()
+                .bold()
+        );
+        eprintln!(
+            "{} frame dots matched, min {:.2}, mean {:.2}, max {:.2}",
+            stats.count,
+            stats.min,
+            stats.mean(),
+            stats.max
+        );
+    }
+
+    Ok(Artifacts {
+        commands,
+        ..artifacts
+    })
+}
@@ -1,186 +1,276 @@
 //! `backgif` command line binary.
 
-mod conv;
+mod doctor;
+mod emoji_palette;
 
-use clap::{Parser, ValueEnum};
+use backgif::conv::{Artifacts, C_HEADER, RenderCommand};
+use backgif::{Cache, Config, InputFormat, RenderFormat, convert_with_cache};
+use clap::{Parser, Subcommand};
 use colored::Colorize;
-use conv::fmtr::{EmojiFrameFormatter, FrameFormatter, TrueColorFrameFormatter};
-use conv::{
-    CustomFrameConverter, CustomFrameParser, FrameConverter, FrameParser, GdbFrameConverter,
-    GifFrameParser, LldbFrameConverter,
-};
+use serde_json::Value;
+use std::io::IsTerminal;
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
-#[command(about, long_about = None)]
-struct Args {
-    /// Input file used to parse frames
-    #[arg(value_name = "FILE")]
-    file: PathBuf,
-
-    /// Input file format
-    #[arg(short, long, value_enum, default_value_t=InputFormat::GIF)]
-    format: InputFormat,
-
-    /// Frame renderer format
-    #[arg(short, long, value_enum, default_value_t=RenderFormat::TrueColor)]
-    renderer: RenderFormat,
-
-    /// Target debugger to generate commands and automation script
-    #[arg(short, long, value_enum, default_value_t=Debugger::GDB)]
-    debugger: Debugger,
-
-    /// Pass this argument to only clear each line being rendered,
-    /// but can leave artifacts on screen; Omit this argument to
-    /// clear all lines on the screen when rendering a new frame,
-    /// but can cause flickering with large frames (> 20x20)
-    #[arg(long, action)]
-    clear_line: bool,
+#[command(
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    arg_required_else_help = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    /// Pass this argument to include debug info when compiling
-    #[arg(long, action)]
-    debug_info: bool,
+    #[command(flatten)]
+    args: Option<Config>,
 
-    /// Custom frame delay in units of 10 ms
-    #[arg(long)]
-    delay: Option<u16>,
+    /// Log conversion details, such as parsed symbols and patched
+    /// addresses (repeat for more details, e.g. `-vv`)
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
 
-    /// Custom frame height in number of dots
-    #[arg(long)]
-    height: Option<u16>,
+    /// Pass this argument to print messages without colors, which
+    /// is also the case if `NO_COLOR` is set (frame lines keep theirs)
+    #[arg(long, action, global = true)]
+    no_color: bool,
 
-    /// Custom frame width in number of dots
-    #[arg(long)]
-    width: Option<u16>,
-}
+    /// Pass this argument to keep the truecolor renderer on terminals
+    /// that don't advertise truecolor support with `COLORTERM`,
+    /// instead of falling back to the ansi256 renderer
+    #[arg(long, action, global = true)]
+    force_renderer: bool,
 
-#[derive(ValueEnum, Clone, Debug)]
-enum Debugger {
-    GDB,
-    LLDB,
+    /// Pass this argument to print `backgif.h`, declaring the functions
+    /// of custom input and helpers for writing frame dots, which custom
+    /// input can include (e.g. `--emit-header > backgif.h`)
+    #[arg(long, action)]
+    emit_header: bool,
 }
 
-#[derive(ValueEnum, Clone, Debug)]
-enum InputFormat {
-    /// C source file with functions for building custom frames
-    ///
-    /// ```c
-    /// // Called at the beginning of start function, supplying a
-    /// // `seed` for the initial state of PRNGs, along with the
-    /// // configured frame width `w` and height `h`.
-    /// void init(uint64_t seed, uint16_t w, uint16_t h);
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Convert multiple inputs listed in a manifest
     ///
-    /// // Called at the beginning of each frame.
-    /// void update_frame();
+    /// The manifest is a JSON list of entries, each mapping option
+    /// names to values, along with the input `file` and an optional
+    /// `out-dir` where artifacts are written (relative paths are
+    /// resolved against the current directory):
     ///
-    /// // Renders frame line `n` containing up to
-    /// // `width` dots, updating the corresponding symbol at `addr`.
-    /// // First dot is after frame line prefix `offs`.
-    /// void draw_line(uint8_t *addr, uint8_t offs, uint16_t n);
+    /// ```json
+    /// [
+    ///   { "file": "example/bunnyhop.gif", "out-dir": "out/bunnyhop", "renderer": "emoji" },
+    ///   { "file": "example/snake.gif", "out-dir": "out/snake", "clear-line": true }
+    /// ]
     /// ```
-    C,
+    Batch {
+        /// Manifest file listing inputs and their options
+        #[arg(value_name = "MANIFEST")]
+        manifest: PathBuf,
+    },
 
-    /// GIF binary file
-    GIF,
-}
+    /// Check which tools and files are available for each option
+    Doctor,
 
-#[derive(ValueEnum, Clone, Debug)]
-enum RenderFormat {
-    /// UTF-8 emoji codepoints
-    Emoji,
+    /// Build an emoji palette for `--emoji-palette` from a directory
+    /// of images, each named after the emoji it represents (e.g. `🟥.png`)
+    BuildPalette {
+        /// Directory of images
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
 
-    /// 24-bit truecolor for virtual terminal emulators
-    TrueColor,
+        /// Palette file to write
+        #[arg(long, value_name = "PATH", default_value = "palette.json")]
+        out: PathBuf,
+    },
 }
 
-fn main() {
-    let args = Args::parse();
+/// Manifest entry parsed into options.
+struct BatchEntry {
+    file: String,
+    args: Result<Config, String>,
+}
 
-    let formatter: &dyn FrameFormatter = match args.renderer {
-        RenderFormat::Emoji => &EmojiFrameFormatter::new(),
-        RenderFormat::TrueColor => &TrueColorFrameFormatter,
-    };
-    let parser: &dyn FrameParser = match args.format {
-        InputFormat::C => &CustomFrameParser {
-            formatter,
-            height: args.height.expect("Custom parser requires passing height"),
-            width: args.width.expect("Custom parser requires passing width"),
-        },
-        InputFormat::GIF => &GifFrameParser { formatter },
-    };
-    let compiler: &str = match args.debugger {
-        Debugger::GDB => "gcc",
-        Debugger::LLDB => "clang",
-    };
-    let inner: &dyn FrameConverter = match args.debugger {
-        Debugger::GDB => &GdbFrameConverter { parser },
-        Debugger::LLDB => &LldbFrameConverter { parser },
-    };
-    let converter: &dyn FrameConverter = match args.format {
-        InputFormat::C => {
-            let min_addr = std::fs::read_to_string("/proc/sys/vm/mmap_min_addr")
-                .unwrap()
-                .trim()
-                .parse::<u64>()
-                .unwrap();
-            if min_addr > 0 {
-                eprintln!(
-                    "{}\n",
-                    format!(
-                        "[!] Custom input expects `/proc/sys/vm/mmap_min_addr = 0`, got `{}`.",
-                        min_addr
-                    )
-                    .red()
-                    .bold()
-                );
+fn main() {
+    let cli = Cli::parse();
+    init_logger(cli.verbose);
+    if cli.no_color || std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        colored::control::set_override(false);
+    }
+    let cache = Cache::default();
+    match cli.command {
+        Some(Command::Batch { manifest }) => batch(&manifest, &cache, cli.force_renderer),
+        Some(Command::Doctor) => doctor::doctor(),
+        Some(Command::BuildPalette { dir, out }) => emoji_palette::build_palette(&dir, &out),
+        None if cli.emit_header => print!("{}", C_HEADER),
+        None => {
+            let mut args = cli.args.unwrap();
+            if !cli.force_renderer {
+                fallback_renderer(&mut args);
             }
-
-            if matches!(args.debugger, Debugger::LLDB) {
-                eprintln!("{}\n","[!] Workaround for llvm-project issue #153772: each frame dumps memory to a temporary file, mind your SSD lifespan!".red().bold());
-                if !args.debug_info {
-                    eprintln!("{}\n","[!] LLDB does not reload .symtab symbols, consider passing `--debug-info` to instead use .debug_str entries.".red().bold());
+            match convert_with_cache(&args, &cache) {
+                Ok(artifacts) => print_commands(&args, &artifacts),
+                Err(e) => {
+                    eprintln!("{}", e.to_string().red().bold());
+                    std::process::exit(1);
                 }
             }
+        }
+    }
+}
 
-            if matches!(args.renderer, RenderFormat::Emoji) {
-                panic!("Custom input not supported with emoji formatter 😞.");
-            }
+/// Log messages up to the level of `verbose` flags, unless overridden
+/// by `RUST_LOG`. `DEBUG=1` is deprecated, and read as a single flag.
+fn init_logger(verbose: u8) {
+    let debug_env = std::env::var("DEBUG").is_ok_and(|value| value == "1");
+    let level = match verbose.max(debug_env as u8) {
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .format_timestamp(None)
+        .parse_default_env()
+        .init();
+    if debug_env {
+        log::warn!("DEBUG=1 is deprecated, pass --verbose instead.");
+    }
+}
 
-            &CustomFrameConverter {
-                inner,
-                file: &args.file,
-                height: args.height.expect("Custom input requires passing height"),
-                width: args.width.expect("Custom input requires passing width"),
-            }
+/// Print the commands rendering `artifacts`, which are run from the
+/// output directory of `args`.
+fn print_commands(args: &Config, artifacts: &Artifacts) {
+    if artifacts.commands.is_empty() {
+        return;
+    }
+
+    if let Some(out_dir) = &args.out_dir {
+        println!(
+            "\n{}\n{}",
+            "Run the commands below from the output directory:"
+                .purple()
+                .bold(),
+            format!("cd {}", out_dir.display()).bold()
+        );
+    }
+    for RenderCommand { title, command } in &artifacts.commands {
+        println!("\n{}", format!("{}:", title).purple().bold());
+        println!("{}", command.bold());
+    }
+}
+
+/// Whether `COLORTERM` advertises 24-bit colors.
+fn supports_truecolor() -> bool {
+    std::env::var("COLORTERM").is_ok_and(|value| matches!(value.as_str(), "truecolor" | "24bit"))
+}
+
+/// Switch the truecolor renderer of `args` to ansi256, when printing
+/// to a terminal that doesn't advertise truecolor support. Options
+/// that require truecolor keep it.
+fn fallback_renderer(args: &mut Config) {
+    if !matches!(args.renderer, RenderFormat::TrueColor)
+        || matches!(args.format, InputFormat::C)
+        || args.fg_glyph
+        || !std::io::stdout().is_terminal()
+        || supports_truecolor()
+    {
+        return;
+    }
+
+    if !args.no_warnings {
+        eprintln!(
+            "{}\n",
+            "[!] Terminal doesn't advertise truecolor support in `COLORTERM`, falling back to ansi256 renderer; pass `--force-renderer` to keep truecolor."
+                .red()
+                .bold()
+        );
+    }
+    args.renderer = RenderFormat::Ansi256;
+}
+
+fn parse_batch_entry(entry: &Value) -> BatchEntry {
+    let file = entry["file"].as_str().unwrap_or_default().to_string();
+    let mut argv = vec![String::from("backgif"), file.to_owned()];
+    for (key, value) in entry.as_object().into_iter().flatten() {
+        match (key.as_str(), value) {
+            ("file", _) | (_, Value::Bool(false)) => {}
+            (_, Value::Bool(true)) => argv.push(format!("--{}", key)),
+            (_, Value::String(value)) => argv.extend([format!("--{}", key), value.to_owned()]),
+            (_, value) => argv.extend([format!("--{}", key), value.to_string()]),
         }
-        InputFormat::GIF => inner,
+    }
+    let args = if file.is_empty() {
+        Err(String::from("Missing `file` in manifest entry"))
+    } else {
+        Config::try_parse_from(argv)
+            .map_err(|e| e.to_string().lines().next().unwrap_or_default().to_string())
     };
 
-    let frame_infos = converter.parse_input(&args.file, args.clear_line, args.delay);
-    let (start_name, start_tmp_name) = parser.to_frameline_names(
-        formatter,
-        // Entrypoint symbol (overrides default symbol `_start`)
-        // is not used as frame line, so it can be filled with
-        // "Zero Width No-Break Space" (ZWNBSP).
-        &String::from_utf8(b"\xef\xbb\xbf".repeat(4)).unwrap(),
-        0,
-        false,
-        args.clear_line,
-    );
-
-    let src = converter.prepare_src(&frame_infos, &start_tmp_name, args.debug_info);
-    converter
-        .compile(&src, &compiler, &start_tmp_name, args.debug_info)
-        .unwrap();
-
-    let bin_info = converter.parse_bin("a.out");
-    converter.patch_bin(
-        &frame_infos,
-        &bin_info.name_to_info,
-        &start_tmp_name,
-        &start_name,
-        bin_info.build_id_desc_offs,
-    );
-
-    converter.write_dbg_script(&frame_infos, &bin_info.name_to_info, bin_info.size, false, "a.out");
+    BatchEntry { file, args }
+}
+
+/// Convert each manifest entry, reporting which ones failed at the end.
+fn batch(manifest: &PathBuf, cache: &Cache, force_renderer: bool) {
+    let manifest_entries = std::fs::read_to_string(manifest)
+        .map_err(|e| format!("Can't read manifest {}: {}", manifest.display(), e))
+        .and_then(|contents| {
+            serde_json::from_str::<Value>(&contents)
+                .map_err(|e| format!("Can't parse manifest {}: {}", manifest.display(), e))
+        })
+        .and_then(|json| match json {
+            Value::Array(entries) => Ok(entries),
+            _ => Err(format!(
+                "Manifest {} must be a list of entries.",
+                manifest.display()
+            )),
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e.red().bold());
+            std::process::exit(1);
+        });
+    let mut entries = manifest_entries
+        .iter()
+        .map(parse_batch_entry)
+        .collect::<Vec<_>>();
+    if !force_renderer {
+        for args in entries
+            .iter_mut()
+            .filter_map(|entry| entry.args.as_mut().ok())
+        {
+            fallback_renderer(args);
+        }
+    }
+
+    // Palette cache is shared by all entries, so it's read once.
+    if let Some(args) = entries.iter().find_map(|entry| {
+        entry
+            .args
+            .as_ref()
+            .ok()
+            .filter(|args| matches!(args.renderer, RenderFormat::Emoji))
+    }) {
+        cache.emoji_formatter_for(&args.emoji_palette, !args.no_cache, args.color_metric);
+    }
+
+    let results = entries
+        .iter()
+        .map(|entry| {
+            let args = entry.args.as_ref()?;
+            convert_with_cache(args, cache)
+                .map(|artifacts| print_commands(args, &artifacts))
+                .map_err(|e| e.to_string())
+        })
+        .collect::<Vec<_>>();
+
+    println!("\n{}", "Batch results:".purple().bold());
+    for (entry, result) in entries.iter().zip(&results) {
+        match result {
+            Ok(_) => println!("{} {}", "[ok]".green().bold(), entry.file),
+            Err(e) => println!("{} {}: {}", "[failed]".red().bold(), entry.file, e.trim()),
+        }
+    }
+    if results.iter().any(|result| result.is_err()) {
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,3093 @@
+//! End-to-end conversion of GIF input, rendered by a headless debugger.
+
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+fn has_tool(name: &str) -> bool {
+    Command::new(name)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Temporary directory of a test, removed when dropped, even if an assertion fails.
+struct WorkDir(PathBuf);
+
+impl std::ops::Deref for WorkDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for WorkDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn work_dir(name: &str) -> WorkDir {
+    let dir = std::env::temp_dir().join(format!("backgif-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    WorkDir(dir)
+}
+
+/// Run `backgif` with `args` in `dir`, capturing its output.
+fn run_backgif<I, S>(dir: &Path, args: I) -> Output
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Whether any of `tools` is missing, in which case the test is skipped.
+fn lacks_tools(tools: &[&str]) -> bool {
+    if tools.iter().all(|tool| has_tool(tool)) {
+        return false;
+    }
+    eprintln!("Skipping, requires {}.", tools.join(" and "));
+    true
+}
+
+/// Write a 2x`height` GIF with a red frame followed by a blue frame.
+fn write_fixture(path: &Path, height: u16) {
+    let file = std::fs::File::create(path).unwrap();
+    let mut encoder = gif::Encoder::new(file, 2, height, &[]).unwrap();
+    for rgba in [RED, BLUE] {
+        let mut buffer = rgba.repeat(2 * height as usize);
+        encoder
+            .write_frame(&gif::Frame::from_rgba(2, height, &mut buffer))
+            .unwrap();
+    }
+}
+
+/// Write a 2x1 GIF with a frame filled by each color of `colors`.
+fn write_frames_fixture(path: &Path, colors: &[[u8; 4]]) {
+    let file = std::fs::File::create(path).unwrap();
+    let mut encoder = gif::Encoder::new(file, 2, 1, &[]).unwrap();
+    for rgba in colors {
+        let mut buffer = rgba.repeat(2);
+        encoder
+            .write_frame(&gif::Frame::from_rgba(2, 1, &mut buffer))
+            .unwrap();
+    }
+}
+
+/// Write a 3x1 GIF with a red background frame, followed by frames
+/// moving a 1x1 blue sprite, each restoring the background when disposed.
+fn write_sprite_fixture(path: &Path) {
+    let file = std::fs::File::create(path).unwrap();
+    let mut encoder = gif::Encoder::new(file, 3, 1, &[255, 0, 0, 0, 0, 255]).unwrap();
+    encoder
+        .write_frame(&gif::Frame {
+            width: 3,
+            height: 1,
+            buffer: vec![0; 3].into(),
+            dispose: gif::DisposalMethod::Keep,
+            ..Default::default()
+        })
+        .unwrap();
+    for left in 0..3 {
+        encoder
+            .write_frame(&gif::Frame {
+                left,
+                width: 1,
+                height: 1,
+                buffer: vec![1].into(),
+                dispose: gif::DisposalMethod::Previous,
+                ..Default::default()
+            })
+            .unwrap();
+    }
+}
+
+/// Write a 3x1 GIF without a global palette, where each frame has a
+/// local palette, and later frames make a different index transparent.
+fn write_local_palettes_fixture(path: &Path) {
+    let file = std::fs::File::create(path).unwrap();
+    let mut encoder = gif::Encoder::new(file, 3, 1, &[]).unwrap();
+    for (palette, transparent, buffer) in [
+        // Red, blue
+        (vec![255, 0, 0, 0, 0, 255], None, vec![0, 0, 0]),
+        // Green (transparent), blue
+        (vec![0, 255, 0, 0, 0, 255], Some(0), vec![0, 1, 0]),
+        // Blue, green (transparent)
+        (vec![0, 0, 255, 0, 255, 0], Some(1), vec![0, 1, 1]),
+    ] {
+        encoder
+            .write_frame(&gif::Frame {
+                width: 3,
+                height: 1,
+                buffer: buffer.into(),
+                palette: Some(palette),
+                transparent,
+                dispose: gif::DisposalMethod::Keep,
+                ..Default::default()
+            })
+            .unwrap();
+    }
+}
+
+fn frameline_at_origin(rgba: [u8; 4]) -> String {
+    format!(
+        "\x1b[1;1H\x1b[2J{}\x1b[8m\x1b[?25l",
+        format!("\x1b[48:2::{}:{}:{}m  \x1b[49m", rgba[0], rgba[1], rgba[2]).repeat(2)
+    )
+}
+
+#[test]
+fn gif_to_gdb() {
+    if lacks_tools(&["gcc"]) {
+        return;
+    }
+
+    let dir = work_dir("gif-to-gdb");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    assert!(
+        run_backgif(&dir, ["tiny.gif", "--delay", "1", "--loops", "1"])
+            .status
+            .success()
+    );
+    assert!(dir.join("a.out").exists());
+    assert!(dir.join("a_gdb.py").exists());
+
+    if !has_tool("gdb") {
+        eprintln!("Skipping playback, requires gdb.");
+        return;
+    }
+
+    // The script quits after a single pass, but it's still
+    // stopped if it hangs.
+    let output = Command::new("timeout")
+        .current_dir(&dir)
+        .args([
+            "30",
+            "gdb",
+            "--batch",
+            "-q",
+            "./a.out",
+            "--command",
+            "a_gdb.py",
+        ])
+        .stderr(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "gdb didn't quit after a single pass"
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let red_i = stdout
+        .find(&frameline_at_origin(RED))
+        .expect("Missing first frame in backtrace");
+    stdout[red_i..]
+        .find(&frameline_at_origin(BLUE))
+        .expect("Missing second frame after first frame in backtrace");
+}
+
+#[test]
+fn gif_to_rr() {
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    let dir = work_dir("gif-to-rr");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["tiny.gif", "-d", "rr", "--loops", "1"])
+        .args(has_tool("python3").then_some("--check-script"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("rr record ./a.out"));
+    assert!(stdout.contains("rr replay -x a_gdb.py"));
+
+    // Breakpoints are set once, not recreated on each stop.
+    let script = std::fs::read_to_string(dir.join("a_gdb.py")).unwrap();
+    assert!(!script.contains("delete breakpoints"));
+    assert!(!script.contains("starti"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn gif_to_r2() {
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    let dir = work_dir("gif-to-r2");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["tiny.gif", "-d", "r2", "--loops", "1"])
+        .args(has_tool("python3").then_some("--check-script"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("python3 a_r2.py"));
+    assert_eq!(stdout.matches("-c 'db 0x").count(), 2);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn library_convert() {
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    let dir = work_dir("library");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let mut config = backgif::Config::new(dir.join("tiny.gif"));
+    config.out_dir = Some(dir.join("out"));
+    config.name_prefix = String::from("tiny");
+    let artifacts = backgif::convert(&config).unwrap();
+    assert!(artifacts.path(".out").exists());
+    assert!(artifacts.path("_gdb.py").exists());
+    assert!(
+        artifacts
+            .commands
+            .iter()
+            .any(|c| c.command == "gdb ./tiny.out --command tiny_gdb.py")
+    );
+
+    // Outputs that can't be written are returned as errors.
+    std::fs::write(dir.join("taken"), "").unwrap();
+    config.emit_snippets = Some(dir.join("taken"));
+    let e = backgif::convert(&config).unwrap_err();
+    assert!(matches!(e, backgif::conv::ConvError::Io { .. }));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn library_invalid_option() {
+    // Invalid options are returned as errors, before reading input.
+    let mut config = backgif::Config::new("missing.gif");
+    config.jobs = Some(0);
+    let e = backgif::convert(&config).unwrap_err();
+    assert!(matches!(e, backgif::conv::ConvError::InvalidOption { .. }));
+    assert_eq!(e.to_string(), "Number of jobs must be at least 1.");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .args(["missing.gif", "--jobs", "0"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Number of jobs must be at least 1."));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn check_script_syntax_error() {
+    use backgif::conv::{ConvError, Verbosity, check_script};
+
+    if lacks_tools(&["python3"]) {
+        return;
+    }
+
+    let dir = work_dir("check-script");
+    let script = dir.join("broken.py");
+    std::fs::write(&script, "x = 1\nprint((x)\n").unwrap();
+    let e = check_script(&script, Verbosity::Quiet).unwrap_err();
+    assert!(
+        matches!(e, ConvError::InvalidScript { location: Some(_), .. }),
+        "{}",
+        e
+    );
+    assert!(e.to_string().starts_with("Invalid Python syntax in "));
+
+    std::fs::write(&script, "x = 1\n").unwrap();
+    assert!(check_script(&script, Verbosity::Quiet).is_ok());
+}
+
+#[test]
+fn coff_symbols() {
+    // `IMAGE_SYMBOL` entry named by an offset in the string table, or
+    // inline, followed by `aux` auxiliary entries.
+    let symbol = |name: [u8; 8], value: u32, section: i16, symbol_type: u16, aux: u8| {
+        let mut entry = name.to_vec();
+        entry.extend(value.to_le_bytes());
+        entry.extend(section.to_le_bytes());
+        entry.extend(symbol_type.to_le_bytes());
+        entry.extend([2, aux]);
+        entry
+    };
+    let strtab_name = |offs: u32| {
+        let mut name = [0; 8];
+        name[4..].copy_from_slice(&offs.to_le_bytes());
+        name
+    };
+    // Symbol table at offset 4.
+    let mut contents = vec![0; 4];
+    contents.extend(symbol(strtab_name(4), 0x10, 2, 0x20, 1));
+    contents.extend([0xff; 18]);
+    contents.extend(symbol(*b"main\0\0\0\0", 0x20, 1, 0x20, 0));
+    contents.extend(symbol(strtab_name(24), 0x30, 1, 0, 0));
+    let strtab_offs = contents.len() as u64;
+    contents.extend(38u32.to_le_bytes());
+    contents.extend(b"AAAAAAAAAA_function\0AAAAAAAA_data\0");
+
+    let parse = |contents: &[u8], symtab_len: u64, section_addrs: &[u64]| {
+        backgif::conv::parse_coff_symbols(contents, 4, symtab_len, section_addrs, 0x140000000)
+    };
+    let name_to_info = parse(&contents, 4, &[0x1000, 0x2000]).unwrap();
+    // Only functions named in the string table are parsed.
+    assert_eq!(name_to_info.len(), 1);
+    let info = &name_to_info["AAAAAAAAAA_function"];
+    assert_eq!(info.addr, 0x140002010);
+    assert_eq!(info.offs, [strtab_offs + 4]);
+
+    // Truncated tables and unknown sections are malformed.
+    assert!(parse(&contents[..40], 4, &[0x1000, 0x2000]).is_none());
+    assert!(parse(&contents, 4, &[0x1000]).is_none());
+    assert!(parse(&contents, u64::MAX, &[0x1000, 0x2000]).is_none());
+}
+
+#[test]
+fn windbg_frame_condition() {
+    // Breakpoint commands are quoted, so conditions can't end them.
+    let mut config = backgif::Config::new("missing.gif");
+    config.debugger = backgif::Debugger::WinDbg;
+    config.frame_condition = Some(String::from("render\"; q"));
+    let e = backgif::convert(&config).unwrap_err();
+    assert!(matches!(e, backgif::conv::ConvError::InvalidOption { .. }));
+    assert!(e.to_string().contains("Frame condition can't contain"));
+}
+
+#[test]
+fn emoji_lookup_matches_scan() {
+    use palette::color_difference::Ciede2000;
+    use palette::convert::FromColorUnclamped;
+    use palette::{Lab, Srgb};
+
+    let to_lab = |rgb: [u64; 3]| -> Lab {
+        Lab::from_color_unclamped(Srgb::new(
+            rgb[0] as f32 / 255.0,
+            rgb[1] as f32 / 255.0,
+            rgb[2] as f32 / 255.0,
+        ))
+    };
+    let json: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("bgr_to_emoji.json"))
+            .unwrap(),
+    )
+    .unwrap();
+    let entries = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| {
+            let rgb = [&v[2], &v[1], &v[0]].map(|c| c.as_u64().unwrap());
+            (to_lab(rgb), v[3].as_str().unwrap().to_owned())
+        })
+        .collect::<Vec<_>>();
+
+    // Colors matched among the nearest palette entries are the same
+    // as the ones matched by scanning the whole palette.
+    let formatter = backgif::conv::fmtr::EmojiFrameFormatter::new(
+        None,
+        false,
+        backgif::conv::fmtr::ColorMetric::Ciede2000,
+    )
+    .unwrap();
+    let levels = (0..=255).step_by(17);
+    for rgb in levels.clone().flat_map(|r| {
+        levels
+            .clone()
+            .flat_map(move |g| (0..=255).step_by(17).map(move |b| [r, g, b]))
+    }) {
+        let lab = to_lab(rgb);
+        let min_diff = entries
+            .iter()
+            .map(|(entry_lab, _)| entry_lab.difference(lab))
+            .fold(f32::MAX, f32::min);
+        let emoji = formatter.lookup(vec![rgb[0] as u8, rgb[1] as u8, rgb[2] as u8, 255]);
+        // Entries with the same difference may have other emoji.
+        assert!(
+            entries.iter().any(|(entry_lab, entry_emoji)| {
+                *entry_emoji == emoji && entry_lab.difference(lab) == min_diff
+            }),
+            "{:?} matched {}",
+            rgb,
+            emoji
+        );
+    }
+}
+
+#[test]
+fn shared_framelines() {
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    let dir = work_dir("shared-framelines");
+    write_frames_fixture(&dir.join("repeat.gif"), &[RED, BLUE, RED]);
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["repeat.gif", "--delay", "1", "--keep-temps"])
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // Last frame reuses the function of the first frame, so only
+    // the entrypoint and one function per distinct frame are defined.
+    let src = std::fs::read_to_string(dir.join("a.c")).unwrap();
+    assert_eq!(src.matches("void ").count(), 3);
+
+    // Debuggers setting all breakpoints at once keep a breakpoint
+    // address for each frame.
+    let assert_unique_addrs = |args: &[&str], script: &str, prefix: &str| {
+        assert!(
+            run_backgif(&dir, ["repeat.gif", "--delay", "1"].iter().chain(args))
+                .status
+                .success()
+        );
+        let script = std::fs::read_to_string(dir.join(script)).unwrap();
+        let addrs = script
+            .lines()
+            .filter_map(|line| line.strip_prefix(prefix))
+            .map(|line| line[..8].to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(addrs.len(), 3);
+        assert_eq!(addrs.iter().collect::<HashSet<_>>().len(), 3);
+    };
+    assert_unique_addrs(&["-d", "r2"], "a_r2.py", "    0x");
+    if has_tool("clang") {
+        assert_unique_addrs(&["-d", "lldb"], "a_lldb.py", "    [0x");
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn interactive_gdb_script() {
+    let dir = work_dir("interactive");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["tiny.gif", "--interactive", "--loops", "1"])
+            .args(args)
+            .args(has_tool("python3").then_some("--check-script"))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+    };
+    assert!(!run(&["-d", "lldb"]).success(), "accepted with lldb");
+
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        std::fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+
+    assert!(run(&[]).success());
+    let script = std::fs::read_to_string(dir.join("a_gdb.py")).unwrap();
+    assert!(script.contains("handle SIGINT stop print nopass"));
+    assert!(script.contains(r#"super().__init__("backgif-step""#));
+    assert!(script.contains(r#"super().__init__("backgif-resume""#));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn precise_timing() {
+    let dir = work_dir("precise-timing");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    for (debugger, compiler, script) in [("gdb", "gcc", "a_gdb.py"), ("lldb", "clang", "a_lldb.py")]
+    {
+        if !has_tool(compiler) {
+            eprintln!("Skipping {}, requires {}.", debugger, compiler);
+            continue;
+        }
+
+        let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["tiny.gif", "--precise-timing", "-d", debugger])
+            .args(has_tool("python3").then_some("--check-script"))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "{}", debugger);
+        let script = std::fs::read_to_string(dir.join(script)).unwrap();
+        assert!(script.contains("deadline = None"), "{}", debugger);
+        assert!(
+            script.contains("time.sleep(deadline - now)"),
+            "{}",
+            debugger
+        );
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn out_dir_and_name_prefix() {
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    let dir = work_dir("out-dir");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["tiny.gif", "--out-dir", "out", "--name-prefix", "tiny"])
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    for file in ["tiny.c", "tiny.out", "tiny_gdb.py"] {
+        assert!(dir.join("out").join(file).exists(), "{}", file);
+    }
+    assert!(!dir.join("a.out").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn emit_vscode() {
+    let dir = work_dir("emit-vscode");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["tiny.gif", "--emit-vscode"])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+    };
+    assert!(!run(&[]).success(), "accepted without debug info");
+
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        std::fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+
+    assert!(run(&["--debug-info"]).success());
+    let launch: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(dir.join(".vscode").join("launch.json")).unwrap(),
+    )
+    .unwrap();
+    let configuration = &launch["configurations"][0];
+    assert_eq!(configuration["type"], "cppdbg");
+    assert_eq!(configuration["program"], "${workspaceFolder}/a.out");
+    assert_eq!(
+        configuration["customLaunchSetupCommands"][1]["text"],
+        "source ${workspaceFolder}/a_gdb.py"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stripped_binary() {
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    let dir = work_dir("stripped");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    std::fs::write(dir.join("stripcc"), "#!/bin/sh\nexec gcc -s \"$@\"\n").unwrap();
+    Command::new("chmod")
+        .args(["+x", "stripcc"])
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["tiny.gif", "--compiler-path", "./stripcc"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Missing section `.symtab`"));
+    assert!(!stderr.contains("panicked"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cflags() {
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    let dir = work_dir("cflags");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["tiny.gif", "--cflags", "-Wl,-Map=a.map -Wextra"])
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(dir.join("a.map").exists());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["tiny.gif", "--cflags", "-shared"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Compiler flag `-shared`"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn lldb_mem_path() {
+    let dir = work_dir("lldb-mem-path");
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["-f", "c", "--height", "2", "--width", "2"])
+            .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("example/fire.c"))
+            .args(["--lldb-mem-path", "/dev/shm/frame.mem"])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+    };
+    assert!(!run(&[]).success(), "accepted with gdb");
+
+    if !has_tool("clang") || !has_tool("ld") {
+        eprintln!("Skipping, requires clang and ld.");
+        std::fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+
+    assert!(run(&["-d", "lldb", "--debug-info"]).success());
+    let script = std::fs::read_to_string(dir.join("a_lldb.py")).unwrap();
+    assert!(script.contains(r#"mem_path = "/dev/shm/frame.mem""#));
+    assert!(!script.contains("/tmp/mem"));
+
+    // Dump path is the fallback of memfds.
+    assert!(run(&["-d", "lldb", "--debug-info", "--lldb-memfd"]).success());
+    let script = std::fs::read_to_string(dir.join("a_lldb.py")).unwrap();
+    assert!(script.contains("os.memfd_create('backgif')"));
+    assert!(script.contains(r#"else "/dev/shm/frame.mem""#));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn written_source() {
+    if !has_tool("gcc") || !has_tool("ld") {
+        eprintln!("Skipping, requires gcc and ld.");
+        return;
+    }
+
+    // Source printed to stdout is the same as the compiled source.
+    let dir = work_dir("written-source");
+    write_frames_fixture(&dir.join("repeat.gif"), &[RED, BLUE, RED]);
+    let fire = Path::new(env!("CARGO_MANIFEST_DIR")).join("example/fire.c");
+    let fire = fire.to_str().unwrap();
+    for args in [
+        &["repeat.gif"][..],
+        &[
+            "-f",
+            "c",
+            "--height",
+            "2",
+            "--width",
+            "2",
+            "--debug-info",
+            fire,
+        ],
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(args)
+            .arg("--emit-source")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(args)
+            .arg("--keep-temps")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            std::fs::read_to_string(dir.join("a.c")).unwrap(),
+            "{:?}",
+            args
+        );
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn temp_files() {
+    if !has_tool("gcc") || !has_tool("ld") {
+        eprintln!("Skipping, requires gcc and ld.");
+        return;
+    }
+
+    let dir = work_dir("temp-files");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+    };
+    assert!(run(&["tiny.gif"]).success());
+    assert!(!dir.join("a.c").exists());
+    assert!(dir.join("a.out").exists());
+    assert!(dir.join("a_gdb.py").exists());
+
+    let fire = Path::new(env!("CARGO_MANIFEST_DIR")).join("example/fire.c");
+    let custom = [
+        "-f",
+        "c",
+        "--height",
+        "2",
+        "--width",
+        "2",
+        "--name-prefix",
+        "b",
+    ];
+    assert!(run(&[&custom[..], &[fire.to_str().unwrap()]].concat()).success());
+    for name in ["b.c", "b.o", "b.out", "b.ld", "b2.0x0000.ld"] {
+        assert!(!dir.join(name).exists(), "kept {}", name);
+    }
+    assert!(dir.join("b2.out").exists());
+
+    assert!(run(&[&custom[..], &[fire.to_str().unwrap(), "--keep-temps"]].concat()).success());
+    assert!(dir.join("b.c").exists());
+    assert!(dir.join("b.ld").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn custom_input_includes() {
+    if !has_tool("gcc") || !has_tool("ld") {
+        eprintln!("Skipping, requires gcc and ld.");
+        return;
+    }
+
+    // Callbacks are only defined by the included file.
+    let dir = work_dir("custom-includes");
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("example/fire.c"),
+        dir.join("lib/fire_impl.c"),
+    )
+    .unwrap();
+    std::fs::write(dir.join("main.c"), "/* Defined by included files. */\n").unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["-f", "c", "--height", "2", "--width", "2", "main.c"])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+    };
+    assert!(!run(&[]).success(), "resolved missing callbacks");
+    assert!(!run(&["--include", "lib/missing.c"]).success());
+
+    assert!(run(&["--include", "lib/fire_impl.c", "--keep-temps"]).success());
+    let src = std::fs::read_to_string(dir.join("a.c")).unwrap();
+    let include = std::fs::canonicalize(dir.join("lib/fire_impl.c")).unwrap();
+    assert!(src.contains(&format!("#include \"{}\"", include.display())));
+    assert!(dir.join("a2.out").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn custom_input_header() {
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .arg("--emit-header")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let header = String::from_utf8(output.stdout).unwrap();
+    assert!(header.contains("void draw_line(uint8_t *addr, uint8_t offs, uint16_t n);"));
+
+    if !has_tool("gcc") || !has_tool("ld") {
+        eprintln!("Skipping, requires gcc and ld.");
+        return;
+    }
+
+    let dir = work_dir("custom-header");
+    std::fs::write(
+        dir.join("gray.c"),
+        r#"#include "backgif.h"
+
+static uint16_t width;
+
+void init(uint64_t seed, uint16_t w, uint16_t h) {
+    width = w;
+}
+
+void update_frame(void) {}
+
+void draw_line(uint8_t *addr, uint8_t offs, uint16_t n) {
+    for (uint16_t i = 0; i < width; i++) {
+        BACKGIF_DOT_RGB(addr, offs, i, 0x808080);
+    }
+}
+"#,
+    )
+    .unwrap();
+    let run = |file: &str| {
+        Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["-f", "c", "--height", "2", "--width", "2", file])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+    };
+    assert!(run("gray.c").success());
+    assert!(dir.join("a2.out").exists());
+    assert!(!dir.join("backgif.h").exists());
+
+    // A header emitted next to the output, and possibly edited, is
+    // neither replaced nor removed.
+    let edited = format!("{}\n/* Edited. */\n", header);
+    std::fs::write(dir.join("backgif.h"), &edited).unwrap();
+    assert!(run("gray.c").success());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("backgif.h")).unwrap(),
+        edited
+    );
+
+    // Definitions not matching the declared callbacks fail to compile.
+    std::fs::write(
+        dir.join("mismatch.c"),
+        "void init(void) {}\nvoid update_frame(void) {}\nvoid draw_line(void) {}\n",
+    )
+    .unwrap();
+    assert!(!run("mismatch.c").success());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn custom_input_linker_scripts() {
+    if !has_tool("gcc") || !has_tool("ld") {
+        eprintln!("Skipping, requires gcc and ld.");
+        return;
+    }
+
+    // Linker scripts are written by the conversion, not read
+    // from the working directory.
+    let dir = work_dir("custom-input");
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["-f", "c", "--height", "2", "--width", "2"])
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("example/fire.c"))
+        .arg("--keep-temps")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(dir.join("a.ld").exists());
+    assert!(dir.join("a2.out").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn frame_size_validation() {
+    let dir = work_dir("frame-size-validation");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(args)
+            .stdout(Stdio::null())
+            .output()
+            .unwrap()
+    };
+    let output = run(&["tiny.gif", "--width", "0"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Frame height and width must be at least 1."));
+    assert!(!stderr.contains("panicked"));
+
+    let output = run(&["-f", "c", "--height", "2", "example.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Frame height and width required for custom input"));
+    assert!(!stderr.contains("panicked"));
+
+    let output = run(&["tiny.gif", "--emit-source", "--symbol-warning", "0"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("symbols (above 0)"));
+    let output = run(&["tiny.gif", "--emit-source"]);
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("symbols (above"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn expect_frames() {
+    let dir = work_dir("expect-frames");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["tiny.gif", "--emit-source"])
+            .args(args)
+            .stdout(Stdio::null())
+            .output()
+            .unwrap()
+    };
+    assert!(run(&["--expect-frames", "2"]).status.success());
+    // Frames are counted after selecting them.
+    assert!(
+        run(&["--expect-frames", "1", "--max-frames", "1"])
+            .status
+            .success()
+    );
+
+    let output = run(&["--expect-frames", "3"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Expected 3 frames, but 2 frames were selected from input."));
+    assert!(!stderr.contains("panicked"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn missing_tools() {
+    let dir = work_dir("missing-tools");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["tiny.gif", "--compiler-path", "backgif-missing-cc"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("backgif-missing-cc not found in PATH; install it or pass `--compiler-path`")
+    );
+
+    if !has_tool("gcc") {
+        eprintln!("Skipping linker, requires gcc.");
+        std::fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["-f", "c", "--height", "2", "--width", "2"])
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("example/fire.c"))
+        .args(["--ld-path", "backgif-missing-ld"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("backgif-missing-ld not found in PATH; install it or pass `--ld-path`")
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn section_addrs() {
+    let dir = work_dir("section-addrs");
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["-f", "c", "--height", "2", "--width", "2"])
+            .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("example/fire.c"))
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+    };
+    assert!(
+        !run(&["--text-addr", "0x401800"]).success(),
+        "accepted unaligned"
+    );
+    assert!(
+        !run(&["--text-addr", "0"]).success(),
+        "accepted no header page"
+    );
+    assert!(
+        !run(&["--text-addr", "0x200000", "--data-addr", "0x180000"]).success(),
+        "accepted overlapping sections"
+    );
+
+    if !has_tool("gcc") || !has_tool("ld") {
+        eprintln!("Skipping, requires gcc and ld.");
+        std::fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+
+    assert!(
+        run(&[
+            "--text-addr",
+            "0x800000",
+            "--data-addr",
+            "0x100000",
+            "--keep-temps"
+        ])
+        .success()
+    );
+    let ld = std::fs::read_to_string(dir.join("a.ld")).unwrap();
+    assert!(ld.contains(".text 0x800000"));
+    assert!(ld.contains("ORIGIN = 0x7ff000"));
+    assert!(dir.join("a2.0x100000.ld").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn invalid_custom_input() {
+    let dir = work_dir("invalid-input");
+    std::fs::write(dir.join("broken.c"), "int broken = ;\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["-f", "c", "--width", "2", "broken.c"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("pass `--height` and `--width`"));
+    assert!(!stderr.contains("panicked"));
+
+    if !has_tool("gcc") {
+        eprintln!("Skipping build, requires gcc.");
+        std::fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["-f", "c", "--height", "2", "--width", "2", "broken.c"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Can't build binary: `gcc` failed"));
+    assert!(stderr.contains("expected expression"));
+    assert!(!stderr.contains("panicked"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stdin_gif() {
+    use std::io::Write;
+
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    let dir = work_dir("stdin-gif");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let mut child = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["-", "--delay", "1"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(&std::fs::read(dir.join("tiny.gif")).unwrap())
+        .unwrap();
+    assert!(child.wait().unwrap().success());
+    assert!(dir.join("a.out").exists());
+
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["-", "-f", "apng"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn empty_gif() {
+    let dir = work_dir("empty-gif");
+    // Header, screen descriptor, and trailer, without any frames.
+    let file = std::fs::File::create(dir.join("empty.gif")).unwrap();
+    drop(gif::Encoder::new(file, 2, 1, &[]).unwrap());
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .arg("empty.gif")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("No frames decoded from empty.gif"));
+    assert!(!stderr.contains("panicked"));
+    assert!(!dir.join("a.c").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn optimized_custom_input() {
+    if !has_tool("gcc") || !has_tool("ld") {
+        eprintln!("Skipping, requires gcc and ld.");
+        return;
+    }
+
+    let dir = work_dir("optimized-input");
+    std::fs::write(dir.join("o2cc"), "#!/bin/sh\nexec gcc \"$@\" -O2\n").unwrap();
+    Command::new("chmod")
+        .args(["+x", "o2cc"])
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["-f", "c", "--height", "2", "--width", "2"])
+        .args(["--compiler-path", "./o2cc"])
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("example/fire.c"))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(dir.join("a2.out").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn short_framelines() {
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    // Rows of a single emoji are shorter than their temporary names.
+    let dir = work_dir("short-framelines");
+    let file = std::fs::File::create(dir.join("narrow.gif")).unwrap();
+    let mut encoder = gif::Encoder::new(file, 1, 2, &[]).unwrap();
+    for rgba in [RED, BLUE] {
+        let mut buffer = rgba.repeat(2);
+        encoder
+            .write_frame(&gif::Frame::from_rgba(1, 2, &mut buffer))
+            .unwrap();
+    }
+    drop(encoder);
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["narrow.gif", "--renderer", "emoji"])
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let bin = std::fs::read(dir.join("a.out")).unwrap();
+    for emoji in ["🟥", "🟦"] {
+        let emoji = emoji.as_bytes();
+        assert!(
+            bin.windows(emoji.len() + 1)
+                .filter(|window| window.starts_with(emoji))
+                .all(|window| window[emoji.len()] == 0),
+            "Temporary name left after frame line"
+        );
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn debug_info_names() {
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    let dir = work_dir("debug-info");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["tiny.gif", "--debug-info"])
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // Names are patched in both `.strtab` and `.debug_str`.
+    let bin = std::fs::read(dir.join("a.out")).unwrap();
+    let red = frameline_at_origin(RED);
+    assert_eq!(
+        bin.windows(red.len())
+            .filter(|window| *window == red.as_bytes())
+            .count(),
+        2
+    );
+    assert!(!bin.windows(8).any(|window| window == b"AAAAAAAA"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn bits_32() {
+    let dir = work_dir("bits-32");
+    std::fs::write(dir.join("probe.c"), "void _start() {}\n").unwrap();
+    let has_m32 = Command::new("gcc")
+        .current_dir(&dir)
+        .args(["-m32", "-nostdlib", "-static", "-o", "probe", "probe.c"])
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !has_m32 {
+        eprintln!("Skipping, requires gcc with 32-bit support.");
+        std::fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["tiny.gif", "--bits", "32"])
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let bin = std::fs::read(dir.join("a.out")).unwrap();
+    // ELFCLASS32
+    assert_eq!(bin[4], 1);
+    let red = frameline_at_origin(RED);
+    assert!(
+        bin.windows(red.len())
+            .any(|window| window == red.as_bytes())
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn frame_merge() {
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    let dir = work_dir("frame-merge");
+    write_frames_fixture(&dir.join("hold.gif"), &[RED, RED, BLUE]);
+    let breakpoints = |args: &[&str]| {
+        let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["hold.gif", "--delay", "1"])
+            .args(args)
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let script = std::fs::read_to_string(dir.join("a_gdb.py")).unwrap();
+        script
+            .lines()
+            .filter(|line| line.starts_with("    [0x"))
+            .map(|line| line.rsplit(", ").next().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // Held frame is hit once, for as long as both frames.
+    assert_eq!(breakpoints(&[]), ["20],", "10],"]);
+    assert_eq!(breakpoints(&["--no-frame-merge"]), ["10],", "10],", "10],"]);
+
+    // Held frame isn't called again, so that debuggers setting all
+    // breakpoints at once don't hit it twice.
+    assert!(
+        run_backgif(&dir, ["hold.gif", "--delay", "1", "-d", "r2", "--keep-temps"])
+            .status
+            .success()
+    );
+    let script = std::fs::read_to_string(dir.join("a_r2.py")).unwrap();
+    let delays = script
+        .lines()
+        .filter(|line| line.starts_with("    0x"))
+        .map(|line| line.rsplit(", ").next().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(delays, ["20),", "10),"]);
+    let src = std::fs::read_to_string(dir.join("a.c")).unwrap();
+    let main = src.split("loop:").nth(1).unwrap();
+    assert_eq!(main.split("goto loop;").next().unwrap().matches("();").count(), 2);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn fps() {
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    let dir = work_dir("fps");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["tiny.gif", "--fps", "30"])
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let script = std::fs::read_to_string(dir.join("a_gdb.py")).unwrap();
+    let delays = script
+        .lines()
+        .filter(|line| line.starts_with("    [0x"))
+        .map(|line| line.rsplit(", ").next().unwrap())
+        .collect::<Vec<_>>();
+    // 100 / 30 is rounded to 3 units of 10 ms.
+    assert_eq!(delays, ["30],", "30],"]);
+
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["tiny.gif", "--fps", "30", "--delay", "1"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn min_delay() {
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    let dir = work_dir("min-delay");
+    // Fixture frames have a delay of 0.
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let delays = |args: &[&str]| {
+        let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .arg("tiny.gif")
+            .args(args)
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let script = std::fs::read_to_string(dir.join("a_gdb.py")).unwrap();
+        script
+            .lines()
+            .filter(|line| line.starts_with("    [0x"))
+            .map(|line| line.rsplit(", ").next().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(delays(&[]), ["20],", "20],"]);
+    assert_eq!(delays(&["--min-delay", "5"]), ["50],", "50],"]);
+    assert_eq!(
+        delays(&["--min-delay", "5", "--delay", "1"]),
+        ["10],", "10],"]
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Frame snippets written by a conversion of the fixture with extra `args`.
+/// Snippets are written before compiling, so they don't require gcc.
+fn snippets(name: &str, height: u16, args: &[&str]) -> Vec<String> {
+    let dir = work_dir(name);
+    write_fixture(&dir.join("tiny.gif"), height);
+    Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["tiny.gif", "--emit-snippets", "snippets"])
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    let snippets = ["frame_0.txt", "frame_1.txt"]
+        .iter()
+        .map(|file| std::fs::read_to_string(dir.join("snippets").join(file)).unwrap())
+        .collect();
+    std::fs::remove_dir_all(&dir).unwrap();
+    snippets
+}
+
+#[test]
+fn cursor_restore() {
+    assert!(snippets("hide-cursor", 1, &[])[0].contains("\x1b[?25l"));
+    assert!(!snippets("no-hide-cursor", 1, &["--no-hide-cursor"])[0].contains("\x1b[?25l"));
+
+    if !has_tool("gcc") {
+        eprintln!("Skipping script, requires gcc.");
+        return;
+    }
+
+    let dir = work_dir("cursor-restore");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["tiny.gif", "--loops", "1"])
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let script = std::fs::read_to_string(dir.join("a_gdb.py")).unwrap();
+    assert!(script.contains("atexit.register(restore_terminal)"));
+    assert!(script.contains(r#"sys.stdout.write("\x1b[?25h\x1b[0m\x1b[2J\x1b[H")"#));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn gdb_commands() {
+    let dir = work_dir("gdb-commands");
+    write_fixture(&dir.join("tiny.gif"), 2);
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .arg("tiny.gif")
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+    };
+    assert!(
+        !run(&["--gdb-commands", "--debugger", "lldb"]).success(),
+        "accepted lldb"
+    );
+    assert!(
+        !run(&["--gdb-commands", "--interactive"]).success(),
+        "accepted interactive"
+    );
+
+    if !has_tool("gcc") {
+        eprintln!("Skipping script, requires gcc.");
+        std::fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+
+    assert!(run(&["--gdb-commands", "--loops", "2"]).success());
+    assert!(!dir.join("a_gdb.py").exists());
+    let script = std::fs::read_to_string(dir.join("a_gdb.gdb")).unwrap();
+    assert_eq!(script.matches("\ncommands\n").count(), 2);
+    assert!(script.contains("  shell sleep "));
+    assert!(script.contains("if $passes >= 2"));
+    assert!(script.contains("define hook-quit"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn verify_gif() {
+    let dir = work_dir("verify-gif");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args([
+            "tiny.gif",
+            "--renderer",
+            "ansi16",
+            "--verify-gif",
+            "verify.gif",
+            "--emit-source",
+        ])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let mut decoder = gif::DecodeOptions::new();
+    decoder.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = decoder
+        .read_info(std::fs::File::open(dir.join("verify.gif")).unwrap())
+        .unwrap();
+    // Each frame dot is drawn as a square of 8x8 pixels.
+    assert_eq!((decoder.width(), decoder.height()), (16, 8));
+    let mut frames = vec![];
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        frames.push(frame.buffer[..4].to_vec());
+    }
+    assert_eq!(frames, [RED.to_vec(), BLUE.to_vec()]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cast() {
+    let dir = work_dir("cast");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .env("COLUMNS", "40")
+        .env("LINES", "10")
+        .args([
+            "tiny.gif",
+            "--cast",
+            "tiny.cast",
+            "--loops",
+            "2",
+            "--emit-source",
+        ])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let cast = std::fs::read_to_string(dir.join("tiny.cast")).unwrap();
+    let lines = cast
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(lines[0]["version"], 2);
+    // Two plays of two frames, followed by the terminal being restored.
+    let events = &lines[1..];
+    assert_eq!(events.len(), 5);
+    let times = events
+        .iter()
+        .map(|event| event[0].as_f64().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(times, [0.0, 0.02, 0.04, 0.06, 0.08]);
+    assert!(
+        events[0][2]
+            .as_str()
+            .unwrap()
+            .contains(&frameline_at_origin(RED))
+    );
+    assert!(
+        events[1][2]
+            .as_str()
+            .unwrap()
+            .contains(&frameline_at_origin(BLUE))
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn player_script() {
+    let dir = work_dir("player-script");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    // Palette of a single quote, which must be escaped in the script.
+    std::fs::write(dir.join("quote.json"), r#"[[0, 0, 255, "'"]]"#).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args([
+            "tiny.gif",
+            "--renderer",
+            "emoji",
+            "--emoji-palette",
+            "quote.json",
+            "--no-cache",
+            "--player-script",
+            "player.sh",
+            "--loops",
+            "2",
+            "--emit-source",
+        ])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    if has_tool("bash") {
+        let output = Command::new("bash")
+            .current_dir(&dir)
+            .arg("player.sh")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(stdout.matches("''").count(), 4);
+        assert!(stdout.ends_with("\x1b[0m\x1b[?25h\n"));
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn preview() {
+    let dir = work_dir("preview");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["tiny.gif", "--preview", "--loops", "2", "--delay", "1"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    // Frames are played without compiling.
+    assert!(!dir.join("a.c").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let played = snippets("preview-snippets", 1, &[]).concat().repeat(2);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), played);
+}
+
+#[test]
+fn verbose() {
+    let dir = work_dir("verbose");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let stderr = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["tiny.gif", "--preview", "--loops", "1"])
+            .args(args)
+            .env_remove("RUST_LOG")
+            .env_remove("DEBUG")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    assert!(!stderr(&[]).contains("DEBUG"));
+    let debug = stderr(&["-v"]);
+    assert!(debug.contains("2 frame line functions, 2 after sharing."));
+    assert!(!debug.contains("TRACE"));
+    assert!(stderr(&["-vv"]).contains("dispose"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn quiet() {
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    let dir = work_dir("quiet");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let stdout = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .arg("tiny.gif")
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert!(stdout(&[]).contains("Running `gcc "));
+    let quiet = stdout(&["--quiet"]);
+    assert!(!quiet.contains("Running"));
+    assert!(quiet.contains("gdb ./a.out --command a_gdb.py"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn no_color() {
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    let dir = work_dir("no-color");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let stdout = |args: &[&str], envs: &[(&str, &str)]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .arg("tiny.gif")
+            .args(args)
+            .env("CLICOLOR_FORCE", "1")
+            .env_remove("NO_COLOR")
+            .envs(envs.iter().copied())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert!(stdout(&[], &[]).contains("\x1b["));
+    assert!(!stdout(&["--no-color"], &[]).contains("\x1b["));
+    assert!(!stdout(&[], &[("NO_COLOR", "1")]).contains("\x1b["));
+    // Frame lines are still colored.
+    let preview = stdout(&["--no-color", "--preview", "--loops", "1"], &[]);
+    assert!(preview.contains("\x1b[48:2::255:0:0m"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn truecolor_fallback() {
+    if !has_tool("script") {
+        eprintln!("Skipping, requires script.");
+        return;
+    }
+
+    let dir = work_dir("truecolor-fallback");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    // Renderer only falls back when printing to a terminal.
+    let snippet = |args: &str, colorterm: &str| {
+        let command = format!(
+            "{} tiny.gif --emit-snippets snippets {}",
+            env!("CARGO_BIN_EXE_backgif"),
+            args
+        );
+        let status = Command::new("script")
+            .current_dir(&dir)
+            .args(["-qec", &command, "/dev/null"])
+            .env("COLORTERM", colorterm)
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        std::fs::read_to_string(dir.join("snippets").join("frame_0.txt")).unwrap()
+    };
+
+    assert!(snippet("", "").contains("\x1b[48;5;196m"));
+    assert!(snippet("", "truecolor").contains("\x1b[48:2::255:0:0m"));
+    assert!(snippet("--force-renderer", "").contains("\x1b[48:2::255:0:0m"));
+    assert!(snippet("--fg-glyph", "").contains("\x1b[38:2::255:0:0m"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn emit_source() {
+    let dir = work_dir("emit-source");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["tiny.gif", "--emit-source", "--out-dir", "out"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let files: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(files, ["tiny.gif"]);
+
+    let src = String::from_utf8(output.stdout).unwrap();
+    assert!(src.contains("goto loop;"));
+    if has_tool("gcc") {
+        std::fs::write(dir.join("anim.c"), src).unwrap();
+        let status = Command::new("gcc")
+            .current_dir(&dir)
+            .args(["-c", "anim.c", "-o", "anim.o"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn custom_input_seed() {
+    let emit_source = |extra: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(env!("CARGO_TARGET_TMPDIR"))
+            .args(["-f", "c", "--height", "2", "--width", "3", "--emit-source"])
+            .args(extra)
+            .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("example/rnd_dots.c"))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert!(emit_source(&[]).contains("init(123, 3, 2);"));
+    assert!(emit_source(&["--seed", "42"]).contains("init(42, 3, 2);"));
+}
+
+#[test]
+fn motion_blur() {
+    let unblended = snippets("unblended", 1, &[]);
+    assert_eq!(snippets("blur-zero", 1, &["--motion-blur", "0"]), unblended);
+
+    let blended = snippets("blur-half", 1, &["--motion-blur", "0.5"]);
+    assert_eq!(blended[0], unblended[0]);
+    assert!(blended[1].contains(&frameline_at_origin([128, 0, 128, 255])));
+}
+
+/// Colored frame dots drawn by `text`.
+fn dots(text: &str) -> Vec<&str> {
+    text.split("\x1b[49m")
+        .filter_map(|dot| dot.find("\x1b[48:2::").map(|i| &dot[i..]))
+        .collect()
+}
+
+/// Frame dots of each row of `snippet` in the order rows are drawn on
+/// screen, which is reversed for lines positioned bottom-up.
+fn screen_rows(snippet: &str) -> Vec<Vec<&str>> {
+    let mut rows = snippet
+        .lines()
+        .filter(|line| !dots(line).is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+            let row = line.find(";1H\x1b[2K").map_or(i, |end| {
+                line[..end].rsplit("\x1b[").next().unwrap().parse().unwrap()
+            });
+            (row, dots(line))
+        })
+        .collect::<Vec<_>>();
+    rows.sort_by_key(|(row, _)| *row);
+    rows.into_iter().map(|(_, dots)| dots).collect()
+}
+
+#[test]
+fn cell_aspect() {
+    // Truecolor pixels are twice as high as wide on square cells,
+    // so frames are stretched to twice their height.
+    assert_eq!(
+        snippets("cell-aspect", 2, &["--cell-aspect", "1:1"]),
+        snippets("cell-aspect-tall", 4, &[])
+    );
+    // ASCII pixels are as high as cells, which are twice as high
+    // as wide by default.
+    assert_eq!(
+        snippets("cell-aspect-ascii", 4, &["--renderer", "ascii"]),
+        snippets(
+            "cell-aspect-ascii-square",
+            2,
+            &["--renderer", "ascii", "--cell-aspect", "1:1"]
+        )
+    );
+}
+
+#[test]
+fn resized_gif() {
+    // Fixture frames are filled with a single color, so resizing
+    // them matches a fixture with the resized dimensions.
+    assert_eq!(
+        snippets("resized", 4, &["--width", "2", "--height", "2"]),
+        snippets("unresized", 2, &[])
+    );
+    assert_eq!(
+        snippets("resized-width", 2, &["--width", "4"]),
+        snippets("resized-both", 1, &["--width", "4", "--height", "4"])
+    );
+}
+
+#[test]
+fn fit_terminal() {
+    let dir = work_dir("fit");
+    write_fixture(&dir.join("tiny.gif"), 4);
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .env("COLUMNS", "8")
+            .env("LINES", "2")
+            .args(["tiny.gif", "--emit-snippets", "snippets"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&[]);
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("2x4 frames don't fit the 8x2 terminal")
+    );
+
+    // Frames are downscaled by half to fit 2 rows, keeping their aspect ratio.
+    let output = run(&["--fit"]);
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("don't fit"));
+    let fitted = ["frame_0.txt", "frame_1.txt"]
+        .map(|file| std::fs::read_to_string(dir.join("snippets").join(file)).unwrap());
+    assert_eq!(
+        fitted.to_vec(),
+        snippets("fit-resized", 4, &["--width", "1", "--height", "2"])
+    );
+
+    assert!(!run(&["--fit", "--width", "2"]).status.success());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cropped_gif() {
+    assert_eq!(
+        snippets("cropped", 4, &["--crop", "0,1,2,2"]),
+        snippets("uncropped", 2, &[])
+    );
+    // Out of bounds region is clamped to the bottom row.
+    assert_eq!(
+        snippets("cropped-clamped", 4, &["--crop", "0,3,10,10"]),
+        snippets("cropped-row", 1, &[])
+    );
+    assert_eq!(
+        snippets("cropped-resized", 4, &["--crop", "0,0,1,1", "--width", "2"]),
+        snippets("cropped-square", 2, &[])
+    );
+}
+
+#[test]
+fn frame_range() {
+    let dir = work_dir("frame-range");
+    write_frames_fixture(&dir.join("frames.gif"), &[RED, BLUE, RED]);
+    let snippets = |args: &[&str]| {
+        let out = dir.join("snippets");
+        let _ = std::fs::remove_dir_all(&out);
+        let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["frames.gif", "--emit-snippets", "snippets"])
+            .args(args)
+            .output()
+            .unwrap();
+        let mut paths = std::fs::read_dir(&out)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        paths.sort();
+        let snippets = paths
+            .iter()
+            .map(|path| std::fs::read_to_string(path).unwrap())
+            .collect::<Vec<_>>();
+        (
+            snippets,
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+
+    let (all, _) = snippets(&[]);
+    assert_eq!(all.len(), 3);
+    let (selected, stderr) = snippets(&["--start-frame", "1", "--max-frames", "1"]);
+    assert_eq!(selected, &all[1..2]);
+    assert!(!stderr.contains("[!]"));
+    let (clamped, stderr) = snippets(&["--start-frame", "-1", "--max-frames", "0"]);
+    assert_eq!(clamped, &all[..1]);
+    assert!(stderr.contains("Start frame -1 out of range 0..=2, clamped to 0."));
+    assert!(stderr.contains("Max frames 0 out of range, clamped to 1."));
+    let (clamped, _) = snippets(&["--start-frame", "5"]);
+    assert_eq!(clamped, &all[2..]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn row_order() {
+    let dir = work_dir("row-order");
+    // 2x2 frame with a red top row and a blue bottom row.
+    let file = std::fs::File::create(dir.join("rows.gif")).unwrap();
+    let mut encoder = gif::Encoder::new(file, 2, 2, &[]).unwrap();
+    let mut buffer = [RED, RED, BLUE, BLUE].concat();
+    encoder
+        .write_frame(&gif::Frame::from_rgba(2, 2, &mut buffer))
+        .unwrap();
+    drop(encoder);
+
+    // Lines are printed from the top row by default, or from the
+    // bottom row, positioned on screen, in both cases drawing the
+    // top row first on screen.
+    let dot = |rgba: [u8; 4]| format!("\x1b[48:2::{}:{}:{}m  ", rgba[0], rgba[1], rgba[2]);
+    for (args, lines) in [
+        (&[][..], [RED, BLUE]),
+        (&["--row-order", "top-down"][..], [RED, BLUE]),
+        (&["--row-order", "bottom-up"][..], [BLUE, RED]),
+    ] {
+        let _ = std::fs::remove_dir_all(dir.join("snippets"));
+        let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["rows.gif", "--emit-source", "--emit-snippets", "snippets"])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let snippet = std::fs::read_to_string(dir.join("snippets").join("frame_0.txt")).unwrap();
+        let printed = snippet
+            .lines()
+            .map(dots)
+            .filter(|dots| !dots.is_empty())
+            .collect::<Vec<_>>();
+        let expected = lines.map(|rgba| vec![dot(rgba); 2]);
+        assert_eq!(printed, expected, "{:?}", args);
+        assert_eq!(
+            screen_rows(&snippet),
+            [vec![dot(RED); 2], vec![dot(BLUE); 2]],
+            "{:?}",
+            args
+        );
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn flipped_gif() {
+    let dir = work_dir("flipped");
+    // 2x2 frame with a red top left corner.
+    let file = std::fs::File::create(dir.join("corner.gif")).unwrap();
+    let mut encoder = gif::Encoder::new(file, 2, 2, &[255, 0, 0, 0, 0, 255]).unwrap();
+    encoder
+        .write_frame(&gif::Frame {
+            width: 2,
+            height: 2,
+            buffer: vec![0, 1, 1, 1].into(),
+            ..Default::default()
+        })
+        .unwrap();
+    drop(encoder);
+
+    let dot = |rgba: [u8; 4]| format!("\x1b[48:2::{}:{}:{}m  ", rgba[0], rgba[1], rgba[2]);
+    for (args, rows) in [
+        (&[][..], [[RED, BLUE], [BLUE, BLUE]]),
+        (&["--flip-horizontal"][..], [[BLUE, RED], [BLUE, BLUE]]),
+        (&["--flip-vertical"][..], [[BLUE, BLUE], [RED, BLUE]]),
+        (
+            &["--flip-vertical", "--row-order", "bottom-up"][..],
+            [[BLUE, BLUE], [RED, BLUE]],
+        ),
+        (
+            &["--flip-horizontal", "--flip-vertical"][..],
+            [[BLUE, BLUE], [BLUE, RED]],
+        ),
+    ] {
+        let _ = std::fs::remove_dir_all(dir.join("snippets"));
+        Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["corner.gif", "--emit-snippets", "snippets"])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        let snippet = std::fs::read_to_string(dir.join("snippets").join("frame_0.txt")).unwrap();
+        let expected = rows
+            .iter()
+            .map(|row| row.iter().copied().map(dot).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(screen_rows(&snippet), expected, "{:?}", args);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn color_adjustments() {
+    let first_dot = |args: &[&str]| {
+        let snippets = snippets("adjusted", 1, args);
+        dots(&snippets[0])[0].to_string()
+    };
+    assert_eq!(first_dot(&[]), "\x1b[48:2::255:0:0m  ");
+    assert_eq!(first_dot(&["--invert"]), "\x1b[48:2::0:255:255m  ");
+    assert_eq!(first_dot(&["--brightness", "0"]), "\x1b[48:2::0:0:0m  ");
+    assert_eq!(first_dot(&["--brightness", "0.5"]), "\x1b[48:2::188:0:0m  ");
+    // Without contrast, all colors are mid gray.
+    assert_eq!(first_dot(&["--contrast", "0"]), "\x1b[48:2::128:128:128m  ");
+
+    // Palette renderers match adjusted colors.
+    let ansi256 = snippets("adjusted-ansi256", 1, &["-r", "ansi256"]);
+    assert_ne!(ansi256[0], ansi256[1]);
+    let ansi256 = snippets("adjusted-ansi256", 1, &["-r", "ansi256", "--contrast", "0"]);
+    assert_eq!(ansi256[0], ansi256[1]);
+}
+
+#[test]
+fn fg_glyph() {
+    let snippets = snippets("fg-glyph", 1, &["--fg-glyph"]);
+    assert!(snippets[0].contains("\x1b[38:2::255:0:0m██\x1b[39m"));
+    assert!(snippets[1].contains("\x1b[38:2::0:0:255m██\x1b[39m"));
+    assert!(!snippets[0].contains("\x1b[48:2::"));
+}
+
+#[test]
+fn blank() {
+    let dir = work_dir("blank");
+    write_frames_fixture(&dir.join("blank.gif"), &[[0; 4], RED]);
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["blank.gif", "--emit-snippets", "snippets"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let snippet = |file: &str| std::fs::read_to_string(dir.join("snippets").join(file)).unwrap();
+
+    assert!(run(&["--blank", "··"]).status.success());
+    assert!(snippet("frame_0.txt").contains("····"));
+    assert!(!snippet("frame_1.txt").contains("··"));
+
+    assert!(run(&["-r", "emoji", "--blank", "⬛"]).status.success());
+    assert!(snippet("frame_0.txt").contains("⬛⬛"));
+
+    for args in [
+        &["--blank", "·"][..],
+        &["--blank", "\x1b[1m"],
+        &["-r", "emoji", "--blank", "⬛⬛"],
+    ] {
+        let output = run(args);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid blank"));
+    }
+    let output = run(&["-r", "braille", "--blank", "··"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Custom blank not supported"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn single_line() {
+    let multi_line = snippets("multi-line", 2, &[]);
+    let single_line = snippets("single-line", 2, &["--single-line"]);
+    for (multi_line, single_line) in multi_line.iter().zip(&single_line) {
+        let lines: Vec<_> = multi_line.lines().collect();
+        let line = single_line.lines().next().unwrap();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(single_line.lines().count(), 2);
+
+        let rows: Vec<_> = lines[..2].iter().map(|line| dots(line)).collect();
+        let positioned_rows: Vec<_> = line.split("\x1b[2;1H").map(dots).collect();
+        assert_eq!(positioned_rows, rows);
+    }
+}
+
+#[test]
+fn parallel_jobs() {
+    let args = ["--overlay-index", "--palette-stats"];
+    let serial = snippets("jobs-serial", 2, &[&args[..], &["--jobs", "1"]].concat());
+    let parallel = snippets("jobs-parallel", 2, &[&args[..], &["--jobs", "4"]].concat());
+    assert_eq!(parallel, serial);
+}
+
+#[test]
+fn composite_sprite() {
+    let dir = work_dir("sprite");
+    write_sprite_fixture(&dir.join("sprite.gif"));
+    Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["sprite.gif", "--emit-snippets", "snippets"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+
+    let dot = |rgba: [u8; 4]| format!("\x1b[48:2::{}:{}:{}m  ", rgba[0], rgba[1], rgba[2]);
+    for (file, colors) in [
+        ("frame_0.txt", [RED, RED, RED]),
+        ("frame_1.txt", [BLUE, RED, RED]),
+        ("frame_2.txt", [RED, BLUE, RED]),
+        ("frame_3.txt", [RED, RED, BLUE]),
+    ] {
+        let snippet = std::fs::read_to_string(dir.join("snippets").join(file)).unwrap();
+        let expected: Vec<_> = colors.into_iter().map(dot).collect();
+        assert_eq!(dots(&snippet), expected, "{}", file);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn local_palettes() {
+    let dir = work_dir("local-palettes");
+    write_local_palettes_fixture(&dir.join("local.gif"));
+    let dot = |rgba: [u8; 4]| format!("\x1b[48:2::{}:{}:{}m  ", rgba[0], rgba[1], rgba[2]);
+    // Dots are matched per palette entry, or per dot with palette stats.
+    for args in [&[][..], &["--palette-stats"]] {
+        let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["local.gif", "--emit-snippets", "snippets"])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        // Transparent dots keep the dot of previous frames, and
+        // never draw the color at their index.
+        for (file, colors) in [
+            ("frame_0.txt", [RED, RED, RED]),
+            ("frame_1.txt", [RED, BLUE, RED]),
+            ("frame_2.txt", [BLUE, BLUE, RED]),
+        ] {
+            let snippet = std::fs::read_to_string(dir.join("snippets").join(file)).unwrap();
+            let expected: Vec<_> = colors.into_iter().map(dot).collect();
+            assert_eq!(dots(&snippet), expected, "{} {:?}", file, args);
+        }
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn max_colors() {
+    let dir = work_dir("max-colors");
+    write_frames_fixture(&dir.join("colors.gif"), &[RED, [250, 0, 0, 255], BLUE]);
+    let frame_dots = |args: &[&str]| {
+        let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["colors.gif", "--emit-snippets", "snippets"])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        ["frame_0.txt", "frame_1.txt", "frame_2.txt"].map(|file| {
+            let snippet = std::fs::read_to_string(dir.join("snippets").join(file)).unwrap();
+            dots(&snippet)[0].to_owned()
+        })
+    };
+
+    let [red, dark_red, blue] = frame_dots(&[]);
+    assert_ne!(red, dark_red);
+    // Both reds are reduced to their mean.
+    let [red, dark_red, reduced_blue] = frame_dots(&["--max-colors", "2"]);
+    assert_eq!(red, dark_red);
+    assert_eq!(red, "\x1b[48:2::253:0:0m  ");
+    assert_eq!(reduced_blue, blue);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn gif_loop_count() {
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    let dir = work_dir("loop-count");
+    let file = std::fs::File::create(dir.join("loop.gif")).unwrap();
+    let mut encoder = gif::Encoder::new(file, 1, 1, &[255, 0, 0]).unwrap();
+    encoder.set_repeat(gif::Repeat::Finite(3)).unwrap();
+    encoder
+        .write_frame(&gif::Frame {
+            width: 1,
+            height: 1,
+            buffer: vec![0].into(),
+            ..Default::default()
+        })
+        .unwrap();
+    drop(encoder);
+
+    let script = |args: &[&str]| {
+        let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .arg("loop.gif")
+            .args(args)
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        std::fs::read_to_string(dir.join("a_gdb.py")).unwrap()
+    };
+    assert!(script(&[]).contains("if passes >= 3:"));
+    assert!(script(&["--loops", "5"]).contains("if passes >= 5:"));
+    assert!(!script(&["--loops", "0"]).contains("if passes >="));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn apng_blend_over() {
+    let dir = work_dir("apng");
+    let file = std::fs::File::create(dir.join("blend.png")).unwrap();
+    let mut encoder = png::Encoder::new(file, 2, 1);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(2, 0).unwrap();
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&RED.repeat(2)).unwrap();
+    writer.set_frame_dimension(1, 1).unwrap();
+    writer.set_frame_position(1, 0).unwrap();
+    writer.set_blend_op(png::BlendOp::Over).unwrap();
+    writer.write_image_data(&[0, 0, 255, 128]).unwrap();
+    writer.finish().unwrap();
+
+    Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["blend.png", "-f", "apng", "--emit-snippets", "snippets"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+
+    let dot = |rgba: [u8; 4]| format!("\x1b[48:2::{}:{}:{}m  ", rgba[0], rgba[1], rgba[2]);
+    for (file, colors) in [
+        ("frame_0.txt", [RED, RED]),
+        ("frame_1.txt", [RED, [127, 0, 128, 255]]),
+    ] {
+        let snippet = std::fs::read_to_string(dir.join("snippets").join(file)).unwrap();
+        let expected: Vec<_> = colors.into_iter().map(dot).collect();
+        assert_eq!(dots(&snippet), expected, "{}", file);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn still_image() {
+    let dir = work_dir("still-image");
+    // Pixels without alpha are drawn as opaque.
+    let file = std::fs::File::create(dir.join("logo.png")).unwrap();
+    let mut encoder = png::Encoder::new(file, 2, 1);
+    encoder.set_color(png::ColorType::Rgb);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&[255, 0, 0, 0, 0, 255]).unwrap();
+    drop(writer);
+
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["logo.png", "-f", "image", "--emit-snippets", "snippets"])
+        .args(["--emit-source"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let dot = |rgba: [u8; 4]| format!("\x1b[48:2::{}:{}:{}m  ", rgba[0], rgba[1], rgba[2]);
+    let snippet = std::fs::read_to_string(dir.join("snippets").join("frame_0.txt")).unwrap();
+    assert_eq!(dots(&snippet), vec![dot(RED), dot(BLUE)]);
+    assert!(!dir.join("snippets").join("frame_1.txt").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn text_frames() {
+    let dir = work_dir("text-frames");
+    std::fs::write(
+        dir.join("frames.txt"),
+        "ab\n\x1b[31mc\x1b[0m\n---\nxyz\n---\n",
+    )
+    .unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["frames.txt", "-f", "text", "--emit-source"])
+        .args(["--emit-snippets", "snippets"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // Lines are padded to 3 columns, skipping escape sequences, and
+    // frames to 2 lines.
+    let snippet = std::fs::read_to_string(dir.join("snippets/frame_0.txt")).unwrap();
+    assert!(snippet.contains("ab "));
+    assert!(snippet.contains("\x1b[31mc\x1b[0m  "));
+    let snippet = std::fs::read_to_string(dir.join("snippets/frame_1.txt")).unwrap();
+    assert!(snippet.contains("xyz"));
+    // Frame lines are followed by the line resetting attributes.
+    assert_eq!(snippet.lines().count(), 3);
+    assert!(!dir.join("snippets/frame_2.txt").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn text_tabs_and_hyperlinks() {
+    let dir = work_dir("text-tabs");
+    std::fs::write(
+        dir.join("frames.txt"),
+        "a\tb\n\x1b]8;;https://example.com\x07link\x1b]8;;\x07\n",
+    )
+    .unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["frames.txt", "-f", "text", "--emit-source"])
+        .args(["--emit-snippets", "snippets"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // Tabs are expanded to the next tab stop, and lines are padded to
+    // 9 columns, skipping hyperlinks.
+    let snippet = std::fs::read_to_string(dir.join("snippets/frame_0.txt")).unwrap();
+    assert!(snippet.contains("a       b"));
+    assert!(snippet.contains("link\x1b]8;;\x07     "));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn invalid_frame_line() {
+    let dir = work_dir("invalid-frame-line");
+    std::fs::write(dir.join("frames.txt"), "ab\n---\na\x01\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["frames.txt", "-f", "text", "--emit-source"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Invalid frame line 0 in frame 1: unexpected control character '\\u{1}'")
+    );
+    assert!(!stderr.contains("panicked"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn qoi() {
+    let dir = work_dir("qoi");
+    let frames = dir.join("frames");
+    std::fs::create_dir(&frames).unwrap();
+    // 2x2 images without alpha, with a red top row and a blue
+    // bottom row, then with rows swapped.
+    for (file, rows) in [("1.qoi", [RED, BLUE]), ("2.qoi", [BLUE, RED])] {
+        let pixels = rows
+            .iter()
+            .flat_map(|rgba| rgba[..3].repeat(2))
+            .collect::<Vec<_>>();
+        std::fs::write(
+            frames.join(file),
+            qoi::encode_to_vec(&pixels, 2, 2).unwrap(),
+        )
+        .unwrap();
+    }
+
+    let run = |file: &str, snippets: &str| {
+        let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args([
+                file,
+                "-f",
+                "qoi",
+                "--emit-source",
+                "--emit-snippets",
+                snippets,
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    let dot = |rgba: [u8; 4]| format!("\x1b[48:2::{}:{}:{}m  ", rgba[0], rgba[1], rgba[2]);
+    let grid = |snippet: &Path| {
+        let snippet = std::fs::read_to_string(snippet).unwrap();
+        screen_rows(&snippet)
+            .into_iter()
+            .map(|row| row.into_iter().map(String::from).collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+    };
+
+    run("frames/1.qoi", "single");
+    assert_eq!(
+        grid(&dir.join("single/frame_0.txt")),
+        [[dot(RED), dot(RED)], [dot(BLUE), dot(BLUE)]]
+    );
+    assert!(!dir.join("single/frame_1.txt").exists());
+
+    run("frames", "sequence");
+    assert_eq!(
+        grid(&dir.join("sequence/frame_1.txt")),
+        [[dot(BLUE), dot(BLUE)], [dot(RED), dot(RED)]]
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn video() {
+    if !has_tool("ffmpeg") {
+        eprintln!("Skipping, requires ffmpeg.");
+        return;
+    }
+
+    let dir = work_dir("video");
+    let status = Command::new("ffmpeg")
+        .current_dir(&dir)
+        .args(["-v", "error", "-f", "lavfi", "-i"])
+        .args(["color=c=red:s=2x2:r=10:d=1", "-c:v", "ffv1", "video.mkv"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let run = |args: &[&str]| {
+        let dir_name = format!("snippets-{}", args.len());
+        let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["video.mkv", "-f", "video", "--emit-source"])
+            .args(["--emit-snippets", &dir_name])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        std::fs::read_dir(dir.join(dir_name)).unwrap().count()
+    };
+    assert_eq!(run(&[]), 10);
+    assert_eq!(run(&["--fps", "5"]), 5);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn image_sequence() {
+    let dir = work_dir("sequence");
+    let frames = dir.join("frames");
+    std::fs::create_dir(&frames).unwrap();
+    for (file, width, rgba) in [("frame_2.png", 2, RED), ("frame_10.png", 1, BLUE)] {
+        let file = std::fs::File::create(frames.join(file)).unwrap();
+        let mut encoder = png::Encoder::new(file, width, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&rgba.repeat(width as usize)).unwrap();
+    }
+
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["frames", "-f", "dir", "--emit-snippets", "snippets"])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+    };
+    assert!(!run(&[]).success());
+    assert!(run(&["--width", "1", "--height", "1"]).success());
+
+    let dot = |rgba: [u8; 4]| format!("\x1b[48:2::{}:{}:{}m  ", rgba[0], rgba[1], rgba[2]);
+    for (file, rgba) in [("frame_0.txt", RED), ("frame_1.txt", BLUE)] {
+        let snippet = std::fs::read_to_string(dir.join("snippets").join(file)).unwrap();
+        assert_eq!(dots(&snippet), vec![dot(rgba)], "{}", file);
+    }
+
+    // Globs are ordered like directories, and fail without matches.
+    std::fs::write(frames.join("notes.txt"), "").unwrap();
+    let run_glob = |pattern: &str| {
+        Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args([pattern, "-f", "dir", "--emit-snippets", "glob-snippets"])
+            .args(["--width", "1", "--height", "1", "--emit-source"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+    };
+    assert!(!run_glob("frames/*.jpg").success());
+    assert!(run_glob("frames/frame_*.png").success());
+    for (file, rgba) in [("frame_0.txt", RED), ("frame_1.txt", BLUE)] {
+        let snippet = std::fs::read_to_string(dir.join("glob-snippets").join(file)).unwrap();
+        assert_eq!(dots(&snippet), vec![dot(rgba)], "{}", file);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn ansi256() {
+    let snippets = snippets("ansi256", 1, &["--renderer", "ansi256"]);
+    assert!(snippets[0].contains("\x1b[48;5;196m  \x1b[49m"));
+    assert!(snippets[1].contains("\x1b[48;5;21m  \x1b[49m"));
+}
+
+#[test]
+fn dither() {
+    // Gray between two entries of the xterm-256 grayscale ramp.
+    let codes = |name: &str, args: &[&str]| {
+        let dir = work_dir(name);
+        write_frames_fixture(&dir.join("gray.gif"), &[[103, 103, 103, 255]]);
+        let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args([
+                "gray.gif",
+                "--renderer",
+                "ansi256",
+                "--emit-snippets",
+                "snippets",
+            ])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let snippet = std::fs::read_to_string(dir.join("snippets/frame_0.txt")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        snippet
+            .split("\x1b[48;5;")
+            .skip(1)
+            .map(|dot| dot.split('m').next().unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+    let plain = codes("undithered", &[]);
+    assert_eq!(plain.len(), 2);
+    assert_eq!(plain[0], plain[1]);
+
+    // Error of the first dot is diffused into the second one.
+    let dithered = codes("dithered", &["--dither"]);
+    assert_eq!(dithered.len(), 2);
+    assert_ne!(dithered[0], dithered[1]);
+}
+
+#[test]
+fn ansi16() {
+    let snippets = snippets("ansi16", 1, &["--renderer", "ansi16"]);
+    assert!(snippets[0].contains("\x1b[41m  \x1b[49m"));
+    assert!(snippets[1].contains("\x1b[44m  \x1b[49m"));
+}
+
+#[test]
+fn color_metric() {
+    // Exact palette colors match regardless of the metric.
+    for metric in ["ciede2000", "cie76", "weighted-rgb"] {
+        let ansi256 = snippets(
+            &format!("metric-ansi256-{}", metric),
+            1,
+            &["--renderer", "ansi256", "--color-metric", metric],
+        );
+        assert!(ansi256[0].contains("\x1b[48;5;196m  \x1b[49m"));
+        assert!(ansi256[1].contains("\x1b[48;5;21m  \x1b[49m"));
+
+        let ansi16 = snippets(
+            &format!("metric-ansi16-{}", metric),
+            1,
+            &["--renderer", "ansi16", "--color-metric", metric],
+        );
+        assert!(ansi16[0].contains("\x1b[41m  \x1b[49m"));
+        assert!(ansi16[1].contains("\x1b[44m  \x1b[49m"));
+    }
+}
+
+#[test]
+fn half_block() {
+    let snippets = snippets("half-block", 3, &["--renderer", "half-block"]);
+    let lines: Vec<_> = snippets[0].lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].contains("\x1b[38;2;255;0;0m\x1b[48;2;255;0;0m▀\x1b[0m"));
+    assert!(lines[1].contains("\x1b[38;2;255;0;0m▀\x1b[0m"));
+}
+
+#[test]
+fn braille() {
+    let snippets = snippets("braille", 4, &["--renderer", "braille", "--threshold", "50"]);
+    let lines: Vec<_> = snippets[0].lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains("\u{28ff}"));
+    assert!(snippets[1].contains("\u{2800}"));
+    assert!(!snippets[1].contains("\u{28ff}"));
+}
+
+#[test]
+fn ascii() {
+    let ramp = snippets("ascii", 1, &["--renderer", "ascii"]);
+    assert!(ramp[0].contains("::"));
+    assert!(ramp[1].contains(".."));
+
+    let custom_ramp = snippets(
+        "ascii-ramp",
+        1,
+        &["--renderer", "ascii", "--ascii-ramp", "ab"],
+    );
+    assert!(custom_ramp[0].contains("aa"));
+}
+
+#[test]
+fn sixel() {
+    let snippets = snippets("sixel", 7, &["--renderer", "sixel"]);
+    let lines: Vec<_> = snippets[0].lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(
+        lines[0].contains("\x1bP0;1q\"1;1;6;18#180;2;100;0;0#180!6~$-#180!6~$-#180!6~$-\x1b\\")
+    );
+    assert!(lines[1].contains("\x1bP0;1q\"1;1;6;3#180;2;100;0;0#180!6F$-\x1b\\"));
+}
+
+#[test]
+fn kitty() {
+    let whole_frame = snippets("kitty", 1, &["--renderer", "kitty"]);
+    let lines: Vec<_> = whole_frame[0].lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains("\x1b_Ga=T,f=32,s=2,v=1,q=2,m=0;/wAA//8AAP8=\x1b\\"));
+
+    let chunked = snippets("kitty-chunks", 400, &["--renderer", "kitty"]);
+    assert_eq!(chunked[0].matches("\x1b_G").count(), 2);
+    assert!(chunked[0].contains("\x1b_Gm=0;"));
+}
+
+#[test]
+fn emoji_palette() {
+    let embedded = snippets("emoji", 1, &["--renderer", "emoji"]);
+    assert!(!embedded[0].lines().next().unwrap().contains('\x1b'));
+
+    let palette = std::env::temp_dir().join(format!("backgif-palette-{}.json", std::process::id()));
+    std::fs::write(&palette, r#"[[0, 0, 255, "🟥"]]"#).unwrap();
+    let custom = snippets(
+        "emoji-palette",
+        1,
+        &[
+            "--renderer",
+            "emoji",
+            "--emoji-palette",
+            palette.to_str().unwrap(),
+        ],
+    );
+    assert!(custom[1].starts_with("🟥🟥"));
+    std::fs::remove_file(&palette).unwrap();
+}
+
+#[test]
+fn malformed_emoji_palette() {
+    let dir = work_dir("malformed-palette");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    std::fs::write(
+        dir.join("palette.json"),
+        r#"[[0, 0, 255, "🟥"], [0, 0, 256, "🟦"]]"#,
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["tiny.gif", "-r", "emoji", "--emoji-palette", "palette.json"])
+        .args(["--emit-source", "--no-cache"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid entry 1 in emoji palette palette.json"));
+    assert!(!stderr.contains("panicked"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn emoji_lookup_cache() {
+    let dir = work_dir("emoji-cache");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let run = |cache_home: &str, args: &[&str]| {
+        let status = Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .env("XDG_CACHE_HOME", dir.join(cache_home))
+            .args(["tiny.gif", "-r", "emoji", "--emit-snippets", "snippets"])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        std::fs::read_to_string(dir.join("snippets").join("frame_0.txt")).unwrap()
+    };
+    let cache_files = |cache_home: &str| {
+        std::fs::read_dir(dir.join(cache_home).join("backgif"))
+            .map(|entries| entries.count())
+            .unwrap_or(0)
+    };
+
+    // Lookups and the processed palette are cached, but not in the
+    // current directory.
+    let uncached = run("cache", &[]);
+    assert_eq!(cache_files("cache"), 2);
+    assert!(dir.join("cache/backgif/bgr_to_emoji.bin").exists());
+    assert!(!dir.join("bgr_to_emoji.bin").exists());
+    assert_eq!(run("cache", &[]), uncached);
+
+    assert_eq!(run("no-cache", &["--no-cache"]), uncached);
+    assert_eq!(cache_files("no-cache"), 0);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn build_palette() {
+    let dir = work_dir("build-palette");
+    let images = dir.join("images");
+    std::fs::create_dir(&images).unwrap();
+    for (file, rgba) in [("🟥.png", RED), ("🟦.png", BLUE), ("logo.png", RED)] {
+        let file = std::fs::File::create(images.join(file)).unwrap();
+        let mut encoder = png::Encoder::new(file, 1, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&rgba).unwrap();
+    }
+    // Files other than images are skipped.
+    std::fs::write(images.join("README.md"), "Emoji images.\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_backgif"))
+        .current_dir(&dir)
+        .args(["build-palette", "images", "--out", "palette.json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Skipping images/README.md"));
+    // Names that aren't a single emoji are skipped too.
+    assert!(stderr.contains("Skipping images/logo.png"));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Wrote 2 entries"));
+
+    // Palettes without entries aren't written.
+    std::fs::create_dir(dir.join("empty")).unwrap();
+    let output = run_backgif(&dir, ["build-palette", "empty", "--out", "empty.json"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No images named after an emoji"));
+    assert!(!dir.join("empty.json").exists());
+    let output = run_backgif(&dir, ["build-palette", "missing", "--out", "missing.json"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("panicked"));
+
+    let palette = dir.join("palette.json");
+    let snippets = snippets(
+        "build-palette-render",
+        1,
+        &[
+            "--renderer",
+            "emoji",
+            "--emoji-palette",
+            palette.to_str().unwrap(),
+        ],
+    );
+    assert!(snippets[0].starts_with("🟥🟥"));
+    assert!(snippets[1].starts_with("🟦🟦"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn patch_syms_fixture() {
+    use backgif::conv::fmtr::TrueColorFrameFormatter;
+    use backgif::conv::{
+        Artifacts, FrameConverter, FrameParser, GdbFrameConverter, ScriptOptions,
+        SequenceFrameParser, Verbosity,
+    };
+
+    if !has_tool("gcc") {
+        eprintln!("Skipping, requires gcc.");
+        return;
+    }
+
+    let formatter = TrueColorFrameFormatter {
+        minimal_escapes: true,
+        fg_glyph: false,
+        blank: None,
+        hide_cursor: true,
+    };
+    let parser = SequenceFrameParser {
+        formatter: &formatter,
+        bottom_up: false,
+        single_line: false,
+        height: None,
+        width: None,
+    };
+    let frame_info =
+        parser.prepare_frame(&formatter, vec![String::from("dots")], &mut 0, 0, 1, false);
+    let frameline = frame_info.to_lines()[0].to_owned();
+    let tmp_name = parser.to_tmp_name(&frameline, 0);
+    let (start_name, start_tmp_name) =
+        parser.to_frameline_names(&formatter, &String::from("start"), 1, false, false);
+
+    let dir = work_dir("patch-syms");
+    let bin = dir.join("tiny.out");
+    std::fs::write(
+        dir.join("tiny.c"),
+        format!(
+            "void {0}(void) {{}}\nvoid {1}(void) {{ {0}(); }}\nint main(void) {{ {1}(); return 0; }}\n",
+            tmp_name, start_tmp_name
+        ),
+    )
+    .unwrap();
+    let status = Command::new("gcc")
+        .current_dir(&dir)
+        .args(["-o", "tiny.out", "tiny.c"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let name_offs = |bytes: &[u8], name: &str| {
+        let name = [name.as_bytes(), b"\0"].concat();
+        bytes
+            .windows(name.len())
+            .position(|window| window == name)
+            .unwrap()
+    };
+    let original = std::fs::read(&bin).unwrap();
+    let offs = name_offs(&original, &tmp_name);
+
+    let script = ScriptOptions {
+        debugger: String::from("gdb"),
+        frame_condition: None,
+        merge_frames: true,
+        interactive: false,
+        precise_timing: false,
+        lldb_mem_path: None,
+        lldb_memfd: false,
+        gdb_commands: false,
+        text_addr: None,
+        data_addr: None,
+    };
+    let artifacts = Artifacts::default();
+    let converter = GdbFrameConverter {
+        parser: &parser,
+        script: &script,
+        artifacts: &artifacts,
+        bits: 64,
+    };
+    let bin_info = converter.parse_bin(&bin).unwrap();
+    converter.patch_syms(
+        &bin,
+        &bin_info.name_to_info,
+        &[frame_info],
+        &start_tmp_name,
+        &start_name,
+        Verbosity::Quiet,
+    );
+
+    let patched = std::fs::read(&bin).unwrap();
+    assert_eq!(patched.len(), original.len());
+    assert_eq!(
+        &patched[offs..offs + frameline.len() + 1],
+        [frameline.as_bytes(), b"\0"].concat()
+    );
+    let start_offs = name_offs(&original, &start_tmp_name);
+    assert!(patched[start_offs..].starts_with(start_name.as_bytes()));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn batch_manifest() {
+    let dir = work_dir("batch-manifest");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let run = |manifest: &str| {
+        Command::new(env!("CARGO_BIN_EXE_backgif"))
+            .current_dir(&dir)
+            .args(["batch", manifest])
+            .output()
+            .unwrap()
+    };
+
+    // Relative paths of options are resolved against the current
+    // directory, not against the entry's output directory.
+    std::fs::write(
+        dir.join("manifest.json"),
+        r#"[{ "file": "tiny.gif", "out-dir": "out", "emit-snippets": "snippets" }]"#,
+    )
+    .unwrap();
+    run("manifest.json");
+    assert!(dir.join("snippets/frame_0.txt").exists());
+    assert!(!dir.join("out/snippets").exists());
+
+    std::fs::write(dir.join("bad.json"), "[{").unwrap();
+    std::fs::write(dir.join("object.json"), "{}").unwrap();
+    for (manifest, message) in [
+        ("missing.json", "Can't read manifest missing.json"),
+        ("bad.json", "Can't parse manifest bad.json"),
+        ("object.json", "must be a list of entries"),
+    ] {
+        let output = run(manifest);
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(message));
+        assert!(!stderr.contains("panicked"));
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn invalid_caption() {
+    let dir = work_dir("invalid-caption");
+    write_fixture(&dir.join("tiny.gif"), 1);
+    let output = run_backgif(&dir, ["tiny.gif", "--caption", "Hi!", "--emit-source"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Unsupported character '!' in caption"));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn apng_malformed() {
+    let dir = work_dir("apng-malformed");
+    let mut bytes = vec![];
+    let mut encoder = png::Encoder::new(&mut bytes, 1, 1);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_animated(2, 0).unwrap();
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&RED).unwrap();
+    writer.set_frame_delay(u16::MAX, 1).unwrap();
+    writer.write_image_data(&BLUE).unwrap();
+    writer.finish().unwrap();
+    std::fs::write(dir.join("long.png"), &bytes).unwrap();
+    std::fs::write(dir.join("truncated.png"), &bytes[..bytes.len() - 20]).unwrap();
+    std::fs::write(dir.join("header.png"), &bytes[..20]).unwrap();
+
+    // Delays too long for a frame are clamped instead of wrapping.
+    let output = run_backgif(
+        &dir,
+        ["long.png", "-f", "apng", "--player-script", "player.sh", "--emit-source"],
+    );
+    assert!(output.status.success());
+    let player = std::fs::read_to_string(dir.join("player.sh")).unwrap();
+    assert!(player.contains("sleep 655.35"));
+
+    // Truncated files keep the frames decoded before the error.
+    let output = run_backgif(
+        &dir,
+        ["truncated.png", "-f", "apng", "--emit-snippets", "snippets", "--emit-source"],
+    );
+    assert!(output.status.success());
+    assert!(dir.join("snippets/frame_0.txt").exists());
+    assert!(!dir.join("snippets/frame_1.txt").exists());
+
+    let output = run_backgif(&dir, ["header.png", "-f", "apng", "--emit-source"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("No frames decoded from header.png"));
+    assert!(!stderr.contains("panicked"));
+}